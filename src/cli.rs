@@ -0,0 +1,1068 @@
+use std::{fs, io, path::Path, process::ExitCode};
+
+use crate::{
+    DEFAULT_MACHINE_WITH_H,
+    lsp::LanguageServer,
+    results::{Histogram, ResultsStore, Solution},
+    simulation::{
+        computer::{self, Computer},
+        format,
+        instruction::InstructionEvaluationInterrupt,
+        level::{Level, Scores},
+        network::{Endpoint, Network},
+        program::Program,
+        source::SourceSet,
+        timing::TimingAnalysis,
+        trace::{Trace, TraceReadError},
+    },
+};
+
+pub const USAGE: &str = "\
+Usage:
+    zrho                               Start the editor
+    zrho run <program>... [options]    Run programs without a display
+    zrho level <level> <program> [options]
+                                       Check a program against every case of a level
+    zrho results [<level>] [options]   Show the scores of recorded solutions to a level
+    zrho fmt <file>... [--check]       Format programs in the canonical style
+    zrho disassemble <program> [options]
+                                       Print a program as it was assembled, with labels added
+    zrho timing <program> [options]    Print the ticks taken by each basic block of a program
+    zrho trace <trace>                 Print a trace recorded with run --trace as text
+    zrho replay <trace> <program> [options]
+                                       Check that a program still runs the same as a trace
+    zrho lsp [options]                 Start a language server which talks over stdio
+
+Options for run, disassemble, timing, replay and lsp:
+    --machine <file>    Machine definition file (defaults to the default machine with H)
+
+Options for run:
+    --ticks <limit>     Stop after this many ticks
+    --link <a>=<b>      Link two channel registers, given as <program number>:<register>
+                        (e.g. 1:C=2:C)
+    --profile           Report the runs, ticks, energy and blocked ticks of each line
+    --trace <file>      Record everything the program does to a trace file
+    --load-state <file> Start from a state saved with --save-state
+    --save-state <file> Save the state of the machine when the run stops
+
+Options for level and results:
+    --results <file>    Where solutions are recorded (defaults to zrho_results.toml)
+
+Options for fmt:
+    --check             List the files which aren't formatted instead of changing them
+
+Exit status of run:
+    0    The programs completed
+    1    A program or the machine could not be loaded
+    2    A program was interrupted by an error
+    3    The tick limit was reached
+    4    The programs were deadlocked waiting on each other, or on a channel with no link
+
+Exit status of level:
+    0    Every case passed
+    1    The level or program could not be loaded
+    2    A case failed
+
+Exit status of replay:
+    0    The run matches the trace
+    1    The trace, program or machine could not be loaded
+    2    The run diverged from the trace
+
+Exit status of fmt:
+    0    Every file is formatted
+    1    A file could not be read or written
+    2    A file isn't formatted (with --check)";
+
+/// Runs the subcommand given by the command line arguments, or returns `None` if there isn't one.
+#[must_use]
+pub fn run(arguments: &[String]) -> Option<ExitCode> {
+    let (subcommand, arguments) = arguments.split_first()?;
+
+    Some(match subcommand.as_str() {
+        "run" => run_program(arguments),
+        "level" => run_level(arguments),
+        "results" => show_results(arguments),
+        "fmt" => format_files(arguments),
+        "disassemble" => disassemble_program(arguments),
+        "timing" => analyse_timing(arguments),
+        "trace" => dump_trace(arguments),
+        "replay" => replay_trace(arguments),
+        "lsp" => run_language_server(arguments),
+        "help" | "-h" | "--help" => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        _ => usage_error(&format!("Unknown subcommand \"{subcommand}\"")),
+    })
+}
+
+/// The options of the run subcommand.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct RunOptions<'a> {
+    program_paths: Vec<&'a String>,
+    machine_path: Option<&'a String>,
+    tick_limit: Option<u64>,
+    links: Vec<(Endpoint, Endpoint)>,
+    profile: bool,
+    trace_path: Option<&'a String>,
+    load_state_path: Option<&'a String>,
+    save_state_path: Option<&'a String>,
+}
+
+impl<'a> RunOptions<'a> {
+    /// Parses the arguments after `run`, or returns a message to show with the usage.
+    fn parse(arguments: &'a [String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut arguments = arguments.iter();
+
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "--profile" => options.profile = true,
+                "--trace" => {
+                    let path = arguments.next().ok_or("--trace requires a file")?;
+                    options.trace_path = Some(path);
+                }
+                "--load-state" => {
+                    let path = arguments.next().ok_or("--load-state requires a file")?;
+                    options.load_state_path = Some(path);
+                }
+                "--save-state" => {
+                    let path = arguments.next().ok_or("--save-state requires a file")?;
+                    options.save_state_path = Some(path);
+                }
+                "--machine" => {
+                    let path = arguments.next().ok_or("--machine requires a file")?;
+                    options.machine_path = Some(path);
+                }
+                "--ticks" => {
+                    let Some(Ok(limit)) = arguments.next().map(|limit| limit.parse::<u64>()) else {
+                        return Err("--ticks requires a number of ticks".to_owned());
+                    };
+
+                    options.tick_limit = Some(limit);
+                }
+                "--link" => {
+                    let Some(Some(link)) = arguments.next().map(|link| parse_link(link)) else {
+                        return Err("--link requires two registers (e.g. 1:C=2:C)".to_owned());
+                    };
+
+                    options.links.push(link);
+                }
+                _ if argument.starts_with("--") => {
+                    return Err(format!("Unexpected argument \"{argument}\""));
+                }
+                _ => options.program_paths.push(argument),
+            }
+        }
+
+        if options.program_paths.is_empty() {
+            return Err("No program given".to_owned());
+        }
+
+        if options.program_paths.len() > 1 || !options.links.is_empty() {
+            if options.trace_path.is_some() {
+                return Err("--trace can only be used with one program".to_owned());
+            }
+
+            if options.load_state_path.is_some() || options.save_state_path.is_some() {
+                return Err("States can only be saved and loaded with one program".to_owned());
+            }
+        }
+
+        if options.load_state_path.is_some() && options.trace_path.is_some() {
+            return Err(
+                "--trace can't be used with --load-state, since replays start anew".to_owned(),
+            );
+        }
+
+        Ok(options)
+    }
+}
+
+fn run_program(arguments: &[String]) -> ExitCode {
+    let RunOptions {
+        program_paths,
+        machine_path,
+        tick_limit,
+        links,
+        profile,
+        trace_path,
+        load_state_path,
+        save_state_path,
+    } = match RunOptions::parse(arguments) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message),
+    };
+
+    let Some(mut computer) = load_machine(machine_path) else {
+        return ExitCode::from(1);
+    };
+
+    if profile {
+        computer.profile = Some(Box::default());
+    }
+
+    if program_paths.len() > 1 || !links.is_empty() {
+        return run_network(&program_paths, computer, &links, tick_limit);
+    }
+
+    let Some((program, sources)) = load_program_with_sources(program_paths[0], &computer) else {
+        return ExitCode::from(1);
+    };
+
+    if let Some(path) = load_state_path {
+        let Some(source) = read_file(path) else {
+            return ExitCode::from(1);
+        };
+
+        if let Err(errors) = computer.load_state(&source, &program) {
+            for error in errors {
+                eprintln!("{path}: {error}");
+            }
+
+            return ExitCode::from(1);
+        }
+    }
+
+    if trace_path.is_some() {
+        computer.trace = Some(Box::new(Trace {
+            program_length: program.instructions.len() as u32,
+            ..Trace::default()
+        }));
+    }
+
+    let mut deadlocked = false;
+
+    while computer.interrupt.is_none() {
+        if tick_limit.is_some_and(|limit| computer.runtime >= limit) {
+            break;
+        }
+
+        computer.step_tick(&program);
+
+        // Nothing is linked to the channels of a single program, so it would wait forever
+        if computer.waiting_for_channel.is_some() {
+            deadlocked = true;
+            break;
+        }
+    }
+
+    let exit_code = match computer.interrupt {
+        Some(InstructionEvaluationInterrupt::ProgramComplete) => {
+            println!("Program complete");
+            ExitCode::SUCCESS
+        }
+        Some(interrupt) => {
+            if let Some(instruction) = program.instructions.get(computer.instruction as usize) {
+                println!(
+                    "Halted on {location} ({instruction}):",
+                    location = program.describe_location(instruction.location),
+                );
+            }
+
+            println!("{interrupt:?}");
+            ExitCode::from(2)
+        }
+        None if deadlocked => {
+            println!("Deadlocked after {} ticks", computer.runtime);
+            ExitCode::from(4)
+        }
+        None => {
+            println!("Reached the limit of {} ticks", computer.runtime);
+            ExitCode::from(3)
+        }
+    };
+
+    println!(
+        "{registers}\nRuntime: {runtime}\nEnergy used: {energy}",
+        registers = computer.registers,
+        runtime = computer.runtime,
+        energy = computer.energy_used,
+    );
+
+    if let Some(profile) = &computer.profile {
+        print!("\n{}", profile.report(&program, &sources));
+    }
+
+    if let Some(path) = trace_path
+        && let Some(trace) = &computer.trace
+    {
+        let mut bytes = Vec::new();
+        trace.write_to(&mut bytes).unwrap();
+
+        if let Err(error) = fs::write(path, bytes) {
+            eprintln!("{path}: {error}");
+            return ExitCode::from(1);
+        }
+    }
+
+    if let Some(path) = save_state_path
+        && let Err(error) = fs::write(path, computer.save_state())
+    {
+        eprintln!("{path}: {error}");
+        return ExitCode::from(1);
+    }
+
+    exit_code
+}
+
+fn run_network(
+    program_paths: &[&String],
+    computer: Computer,
+    links: &[(Endpoint, Endpoint)],
+    tick_limit: Option<u64>,
+) -> ExitCode {
+    let mut network = Network::default();
+    let mut sources = Vec::new();
+
+    for path in program_paths {
+        let Some((program, program_sources)) = load_program_with_sources(path, &computer) else {
+            return ExitCode::from(1);
+        };
+
+        network.add_node(computer.clone(), program);
+        sources.push(program_sources);
+    }
+
+    for &(a, b) in links {
+        if let Err(error) = network.link(a, b) {
+            eprintln!("{error}");
+            return ExitCode::from(1);
+        }
+    }
+
+    network.run(tick_limit);
+
+    let mut exit_code = if network.is_deadlocked() {
+        println!("Deadlocked after {} ticks", network.runtime);
+        ExitCode::from(4)
+    } else if network.is_finished() {
+        ExitCode::SUCCESS
+    } else {
+        println!("Reached the limit of {} ticks", network.runtime);
+        ExitCode::from(3)
+    };
+
+    for ((node, path), sources) in network.nodes.iter().zip(program_paths).zip(&sources) {
+        let computer = &node.computer;
+
+        println!("\n{path}:");
+
+        match computer.interrupt {
+            Some(InstructionEvaluationInterrupt::ProgramComplete) => println!("Program complete"),
+            Some(interrupt) => {
+                if let Some(instruction) =
+                    node.program.instructions.get(computer.instruction as usize)
+                {
+                    println!(
+                        "Halted on {location} ({instruction}):",
+                        location = node.program.describe_location(instruction.location),
+                    );
+                }
+
+                println!("{interrupt:?}");
+                exit_code = ExitCode::from(2);
+            }
+            None => {
+                if let Some(register) = computer.waiting_for_channel {
+                    println!(
+                        "Waiting on {}",
+                        computer::name_of_register(register).unwrap()
+                    );
+                }
+            }
+        }
+
+        println!(
+            "{registers}\nRuntime: {runtime}\nEnergy used: {energy}",
+            registers = computer.registers,
+            runtime = computer.runtime,
+            energy = computer.energy_used,
+        );
+
+        if let Some(profile) = &computer.profile {
+            print!("\n{}", profile.report(&node.program, sources));
+        }
+    }
+
+    exit_code
+}
+
+fn run_level(arguments: &[String]) -> ExitCode {
+    let mut paths = Vec::new();
+    let mut results_path = ResultsStore::DEFAULT_PATH;
+
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--results" => {
+                let Some(path) = arguments.next() else {
+                    return usage_error("--results requires a file");
+                };
+
+                results_path = path;
+            }
+            _ if argument.starts_with("--") => {
+                return usage_error(&format!("Unexpected argument \"{argument}\""));
+            }
+            _ => paths.push(argument),
+        }
+    }
+
+    let [level_path, program_path] = paths[..] else {
+        return usage_error("level requires a level and a program");
+    };
+
+    let Some(level) = load_level(level_path) else {
+        return ExitCode::from(1);
+    };
+
+    let Some(program) = load_program(program_path, &level.computer) else {
+        return ExitCode::from(1);
+    };
+
+    println!(
+        "{name}\n{description}\n",
+        name = level.name,
+        description = level.description
+    );
+
+    let results = level.run(&program);
+
+    for result in &results {
+        print!(
+            "Case {case} (seed {seed}): ",
+            case = result.case + 1,
+            seed = result.seed,
+        );
+
+        match &result.failure {
+            Some(failure) => println!("failed: {failure}"),
+            None => println!(
+                "passed in {runtime} ticks using {energy} energy",
+                runtime = result.runtime,
+                energy = result.energy_used,
+            ),
+        }
+    }
+
+    match Level::scores(&results, &program) {
+        Some(scores) => {
+            println!(
+                "\nPassed\nRuntime: {runtime}\nEnergy used: {energy}\nSize: {size}",
+                runtime = scores.runtime,
+                energy = scores.energy,
+                size = scores.size,
+            );
+
+            record_solution(results_path, &level, program_path, scores);
+
+            ExitCode::SUCCESS
+        }
+        None => {
+            println!("\nFailed");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Compares a passing program to the other recorded solutions of its level, then records it.
+fn record_solution(results_path: &str, level: &Level, program_path: &str, scores: Scores) {
+    let Some(mut store) = load_results(results_path) else {
+        return;
+    };
+
+    // Solutions are told apart by their full path, since many are likely to share a file name
+    let program = fs::canonicalize(program_path).map_or_else(
+        |_| program_path.to_owned(),
+        |path| path.display().to_string(),
+    );
+
+    let others = store
+        .solutions(&level.name)
+        .iter()
+        .filter(|solution| solution.program != program)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if !others.is_empty() {
+        println!();
+        print_histograms(&others, Some(scores));
+    }
+
+    store.record(&level.name, Solution { program, scores });
+
+    if let Err(error) = fs::write(results_path, store.to_string()) {
+        eprintln!("{results_path}: {error}");
+    }
+}
+
+fn show_results(arguments: &[String]) -> ExitCode {
+    let mut level = None;
+    let mut results_path = ResultsStore::DEFAULT_PATH;
+
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--results" => {
+                let Some(path) = arguments.next() else {
+                    return usage_error("--results requires a file");
+                };
+
+                results_path = path;
+            }
+            _ if argument.starts_with("--") || level.is_some() => {
+                return usage_error(&format!("Unexpected argument \"{argument}\""));
+            }
+            _ => level = Some(argument),
+        }
+    }
+
+    let Some(store) = load_results(results_path) else {
+        return ExitCode::from(1);
+    };
+
+    let Some(level) = level else {
+        if store.levels.is_empty() {
+            println!("No solutions recorded");
+        }
+
+        for (name, solutions) in &store.levels {
+            println!("{name}: {} solutions", solutions.len());
+        }
+
+        return ExitCode::SUCCESS;
+    };
+
+    // Levels can be given by name or by their file
+    let name = if level.ends_with(".toml") {
+        let Some(level) = load_level(level) else {
+            return ExitCode::from(1);
+        };
+
+        level.name
+    } else {
+        level.clone()
+    };
+
+    let solutions = store.solutions(&name);
+
+    if solutions.is_empty() {
+        println!("No solutions recorded for {name}");
+        return ExitCode::SUCCESS;
+    }
+
+    println!("{name}\n");
+
+    for solution in solutions {
+        println!(
+            "{program}: runtime {runtime}, energy {energy}, size {size}",
+            program = solution.program,
+            runtime = solution.scores.runtime,
+            energy = solution.scores.energy,
+            size = solution.scores.size,
+        );
+    }
+
+    println!();
+    print_histograms(solutions, None);
+
+    ExitCode::SUCCESS
+}
+
+fn print_histograms(solutions: &[Solution], highlight: Option<Scores>) {
+    let histogram = |title, metric: fn(&Scores) -> u64| Histogram {
+        title,
+        values: solutions
+            .iter()
+            .map(|solution| metric(&solution.scores))
+            .collect(),
+        highlight: highlight.as_ref().map(metric),
+    };
+
+    print!(
+        "{}\n{}\n{}",
+        histogram("Runtime", |scores| scores.runtime),
+        histogram("Energy used", |scores| scores.energy),
+        histogram("Size", |scores| scores.size as u64),
+    );
+}
+
+fn format_files(arguments: &[String]) -> ExitCode {
+    let mut paths = Vec::new();
+    let mut check = false;
+
+    for argument in arguments {
+        match argument.as_str() {
+            "--check" => check = true,
+            _ if argument.starts_with("--") => {
+                return usage_error(&format!("Unexpected argument \"{argument}\""));
+            }
+            _ => paths.push(argument),
+        }
+    }
+
+    if paths.is_empty() {
+        return usage_error("No file given");
+    }
+
+    let mut exit_code = ExitCode::SUCCESS;
+
+    for path in paths {
+        let Some(source) = read_file(path) else {
+            exit_code = ExitCode::from(1);
+            continue;
+        };
+
+        let formatted = format::format_source(&source);
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("{path} isn't formatted");
+
+            if exit_code == ExitCode::SUCCESS {
+                exit_code = ExitCode::from(2);
+            }
+        } else if let Err(error) = fs::write(path, formatted) {
+            eprintln!("{path}: {error}");
+            exit_code = ExitCode::from(1);
+        }
+    }
+
+    exit_code
+}
+
+fn disassemble_program(arguments: &[String]) -> ExitCode {
+    let (program, _) = match load_program_and_machine(arguments) {
+        Ok(loaded) => loaded,
+        Err(exit_code) => return exit_code,
+    };
+
+    print!("{}", format::disassemble(&program));
+
+    ExitCode::SUCCESS
+}
+
+fn analyse_timing(arguments: &[String]) -> ExitCode {
+    let (program, computer) = match load_program_and_machine(arguments) {
+        Ok(loaded) => loaded,
+        Err(exit_code) => return exit_code,
+    };
+
+    let analysis = TimingAnalysis::analyse(&program, &computer);
+
+    print!("{}", analysis.listing(&program));
+
+    let over_limit = (analysis.blocks.iter())
+        .filter(|block| block.is_over_limit())
+        .count();
+
+    if over_limit > 0 {
+        println!(
+            "\n{over_limit} of {} blocks can go over a calls per tick limit",
+            analysis.blocks.len()
+        );
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Loads the program and machine given by `<program> [--machine <file>]`.
+fn load_program_and_machine(arguments: &[String]) -> Result<(Program, Computer), ExitCode> {
+    let mut program_path = None;
+    let mut machine_path = None;
+
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--machine" => {
+                let Some(path) = arguments.next() else {
+                    return Err(usage_error("--machine requires a file"));
+                };
+
+                machine_path = Some(path);
+            }
+            _ if argument.starts_with("--") || program_path.is_some() => {
+                return Err(usage_error(&format!("Unexpected argument \"{argument}\"")));
+            }
+            _ => program_path = Some(argument),
+        }
+    }
+
+    let Some(program_path) = program_path else {
+        return Err(usage_error("No program given"));
+    };
+
+    let computer = load_machine(machine_path).ok_or(ExitCode::from(1))?;
+
+    let program = load_program(program_path, &computer).ok_or(ExitCode::from(1))?;
+
+    Ok((program, computer))
+}
+
+fn dump_trace(arguments: &[String]) -> ExitCode {
+    let [path] = arguments else {
+        return usage_error("trace requires a trace file");
+    };
+
+    let Some(trace) = load_trace(path) else {
+        return ExitCode::from(1);
+    };
+
+    print!("{}", trace.dump());
+
+    ExitCode::SUCCESS
+}
+
+fn replay_trace(arguments: &[String]) -> ExitCode {
+    let Some((trace_path, arguments)) = arguments.split_first() else {
+        return usage_error("replay requires a trace file and a program");
+    };
+
+    let Some(trace) = load_trace(trace_path) else {
+        return ExitCode::from(1);
+    };
+
+    let (program, computer) = match load_program_and_machine(arguments) {
+        Ok(loaded) => loaded,
+        Err(exit_code) => return exit_code,
+    };
+
+    match trace.check(computer, &program) {
+        Ok(()) => {
+            println!(
+                "The run matches the trace ({events} events over {runtime} ticks)",
+                events = trace.events.len(),
+                runtime = trace.runtime,
+            );
+            ExitCode::SUCCESS
+        }
+        Err(divergence) => {
+            println!("{divergence}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn load_trace(path: &str) -> Option<Trace> {
+    fs::File::open(path)
+        .map_err(TraceReadError::Io)
+        .and_then(Trace::read_from)
+        .inspect_err(|error| eprintln!("{path}: {error}"))
+        .ok()
+}
+
+fn run_language_server(arguments: &[String]) -> ExitCode {
+    let mut machine_path = None;
+
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--machine" => {
+                let Some(path) = arguments.next() else {
+                    return usage_error("--machine requires a file");
+                };
+
+                machine_path = Some(path);
+            }
+            _ => return usage_error(&format!("Unexpected argument \"{argument}\"")),
+        }
+    }
+
+    let Some(computer) = load_machine(machine_path) else {
+        return ExitCode::from(1);
+    };
+
+    let mut server = LanguageServer::new(computer);
+
+    // The client should ask the server to shut down before it exits
+    match server.serve(io::stdin().lock(), io::stdout().lock()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Parses a link such as `1:C=2:C`.
+fn parse_link(link: &str) -> Option<(Endpoint, Endpoint)> {
+    let parse_endpoint = |endpoint: &str| {
+        let (node, register) = endpoint.split_once(':')?;
+
+        let node = node.parse::<usize>().ok()?.checked_sub(1)?;
+
+        let mut register = register.chars();
+        let name = register.next()?;
+
+        if register.next().is_some() {
+            return None;
+        }
+
+        Some(Endpoint {
+            node,
+            register: computer::register_with_name(name.to_ascii_uppercase())?,
+        })
+    };
+
+    let (a, b) = link.split_once('=')?;
+
+    Some((parse_endpoint(a)?, parse_endpoint(b)?))
+}
+
+/// Reads and loads a level, printing any errors.
+fn load_level(path: &str) -> Option<Level> {
+    let source = read_file(path)?;
+
+    Level::load_from(&source)
+        .inspect_err(|errors| {
+            for error in errors {
+                eprintln!("{path}: {error}");
+            }
+        })
+        .ok()
+}
+
+/// Reads the results store, which is empty if the file doesn't exist yet.
+fn load_results(path: &str) -> Option<ResultsStore> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Some(ResultsStore::default());
+        }
+        Err(error) => {
+            eprintln!("{path}: {error}");
+            return None;
+        }
+    };
+
+    ResultsStore::load_from(&source)
+        .inspect_err(|errors| {
+            for error in errors {
+                eprintln!("{path}: {error}");
+            }
+        })
+        .ok()
+}
+
+/// Loads a machine definition, or the default machine with H if there isn't one, printing any
+/// errors.
+fn load_machine(path: Option<&String>) -> Option<Computer> {
+    let Some(path) = path else {
+        return Some(Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap());
+    };
+
+    let source = read_file(path)?;
+
+    Computer::load_from(&source)
+        .inspect_err(|errors| {
+            for error in errors {
+                eprintln!("{path}: {error}");
+            }
+        })
+        .ok()
+}
+
+/// Reads and assembles a program, printing any errors.
+pub fn load_program(path: &str, computer: &Computer) -> Option<Program> {
+    load_program_with_sources(path, computer).map(|(program, _)| program)
+}
+
+/// Like [`load_program`], but also returns the files the program was assembled from.
+pub fn load_program_with_sources(path: &str, computer: &Computer) -> Option<(Program, SourceSet)> {
+    if !Path::new(path)
+        .extension()
+        .is_some_and(|extension| ["zρ", "zrho"].contains(&&*extension.to_string_lossy()))
+    {
+        eprintln!("{path}: Programs must have the extension .zρ or .zrho");
+        return None;
+    }
+
+    let sources = SourceSet::load(path)
+        .inspect_err(|error| eprintln!("{error}"))
+        .ok()?;
+
+    let name = Path::new(path).file_stem().map_or_else(
+        || path.to_owned(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+
+    match Program::assemble_from_sources(name, &sources, computer) {
+        Ok(program) => Some((program, sources)),
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error.render(&sources));
+            }
+            None
+        }
+    }
+}
+
+/// Reads a file to a string, printing the error if it can't be read.
+pub fn read_file(path: &str) -> Option<String> {
+    fs::read_to_string(path)
+        .inspect_err(|error| eprintln!("{path}: {error}"))
+        .ok()
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("{message}\n\n{USAGE}");
+    ExitCode::from(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_files::TestDirectory;
+
+    fn strings(arguments: &[&str]) -> Vec<String> {
+        arguments
+            .iter()
+            .map(|&argument| argument.to_owned())
+            .collect()
+    }
+
+    fn endpoint(node: usize, name: char) -> Endpoint {
+        Endpoint {
+            node,
+            register: computer::register_with_name(name).unwrap(),
+        }
+    }
+
+    #[test]
+    fn run_options_are_parsed() {
+        let arguments = strings(&[
+            "a.zρ",
+            "--machine",
+            "m.toml",
+            "--ticks",
+            "50",
+            "--profile",
+            "--save-state",
+            "s.toml",
+        ]);
+        let options = RunOptions::parse(&arguments).unwrap();
+
+        assert_eq!(
+            options,
+            RunOptions {
+                program_paths: vec![&arguments[0]],
+                machine_path: Some(&arguments[2]),
+                tick_limit: Some(50),
+                profile: true,
+                save_state_path: Some(&arguments[7]),
+                ..RunOptions::default()
+            }
+        );
+
+        let arguments = strings(&["a.zρ", "b.zρ", "--link", "1:C=2:C"]);
+        let options = RunOptions::parse(&arguments).unwrap();
+
+        assert_eq!(options.program_paths, [&arguments[0], &arguments[1]]);
+        assert_eq!(options.links, [(endpoint(0, 'C'), endpoint(1, 'C'))]);
+    }
+
+    #[test]
+    fn invalid_run_options_are_rejected() {
+        for (arguments, message) in [
+            (&[][..], "No program given"),
+            (&["--profile"], "No program given"),
+            (&["a.zρ", "--ticks"], "--ticks requires a number of ticks"),
+            (
+                &["a.zρ", "--ticks", "-1"],
+                "--ticks requires a number of ticks",
+            ),
+            (&["a.zρ", "--machine"], "--machine requires a file"),
+            (
+                &["a.zρ", "--link", "1:C"],
+                "--link requires two registers (e.g. 1:C=2:C)",
+            ),
+            (&["a.zρ", "--fast"], "Unexpected argument \"--fast\""),
+            (
+                &["a.zρ", "b.zρ", "--trace", "t.bin"],
+                "--trace can only be used with one program",
+            ),
+            (
+                &["a.zρ", "--link", "1:C=2:C", "--load-state", "s.toml"],
+                "States can only be saved and loaded with one program",
+            ),
+            (
+                &["a.zρ", "--load-state", "s.toml", "--trace", "t.bin"],
+                "--trace can't be used with --load-state, since replays start anew",
+            ),
+        ] {
+            let arguments = strings(arguments);
+            assert_eq!(
+                RunOptions::parse(&arguments),
+                Err(message.to_owned()),
+                "{arguments:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn links_are_parsed() {
+        assert_eq!(
+            parse_link("1:C=2:c"),
+            Some((endpoint(0, 'C'), endpoint(1, 'C')))
+        );
+        assert_eq!(
+            parse_link("3:A=12:B"),
+            Some((endpoint(2, 'A'), endpoint(11, 'B')))
+        );
+
+        for link in [
+            "", "1:C", "1:C=", "0:C=1:C", "1:CC=2:C", "1:1=2:C", "a:C=2:C", "1C=2C",
+        ] {
+            assert_eq!(parse_link(link), None, "{link}");
+        }
+    }
+
+    #[test]
+    fn subcommands_exit_with_their_status() {
+        assert_eq!(run(&[]), None);
+        assert_eq!(run(&strings(&["help"])), Some(ExitCode::SUCCESS));
+        assert_eq!(run(&strings(&["launch"])), Some(ExitCode::from(1)));
+        assert_eq!(run(&strings(&["run"])), Some(ExitCode::from(1)));
+        assert_eq!(
+            run(&strings(&["run", "missing.zρ"])),
+            Some(ExitCode::from(1))
+        );
+
+        // The run subcommand only reads programs from disk
+        let directory = TestDirectory::new("cli-exit-status");
+
+        let complete = directory.write("complete.zρ", "SET X 1\n");
+        let interrupted = directory.write("interrupted.zρ", "DIV X 0 Y\n");
+        let endless = directory.write("endless.zρ", "LBL LOOP\nJMP LOOP\n");
+
+        assert_eq!(run(&strings(&["run", &complete])), Some(ExitCode::SUCCESS));
+        assert_eq!(
+            run(&strings(&["run", &interrupted])),
+            Some(ExitCode::from(2))
+        );
+        assert_eq!(
+            run(&strings(&["run", &endless, "--ticks", "10"])),
+            Some(ExitCode::from(3))
+        );
+
+        let machine = directory.write(
+            "channel.toml",
+            &format!("{DEFAULT_MACHINE_WITH_H}\n[registers.C]\nchannel = true\n"),
+        );
+        let waiting = directory.write("waiting.zρ", "SET X C\n");
+
+        assert_eq!(
+            run(&strings(&["run", &waiting, "--machine", &machine])),
+            Some(ExitCode::from(4))
+        );
+    }
+}
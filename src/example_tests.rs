@@ -0,0 +1,317 @@
+use std::{
+    env,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    DEFAULT_MACHINE_WITH_H,
+    simulation::{
+        computer::{self, Computer, RegisterValues},
+        network::{Endpoint, Network},
+        program::Program,
+        source::SourceSet,
+    },
+};
+
+/// Set to write the results of each example to its expected results, instead of checking them.
+const UPDATE_VARIABLE: &str = "ZRHO_UPDATE_EXPECTED";
+
+/// Stops programs which never end. This is a few times the longest expected runtime, which is
+/// time_assertion.zρ at just over 10⁸ ticks.
+const TICK_LIMIT: u64 = 300_000_000;
+
+/// Examples which take too long to run with the rest, and are only checked by
+/// `cargo test -- --ignored`.
+const SLOW_EXAMPLES: &[&str] = &["time_assertion.zρ"];
+
+/// The number of values on each line of a vector register.
+const VALUES_PER_LINE: usize = 20;
+
+/// Examples which have to run together. The results are expected next to the first program.
+const NETWORKS: &[ExampleNetwork] = &[ExampleNetwork {
+    programs: &["network_squares.zρ", "network_sum.zρ"],
+    machine: "networked.toml",
+    links: &[("1:C", "2:C")],
+}];
+
+struct ExampleNetwork {
+    programs: &'static [&'static str],
+    /// A file in `resources/machines`
+    machine: &'static str,
+    /// Given like `zrho run --link`
+    links: &'static [(&'static str, &'static str)],
+}
+
+#[test]
+fn examples_match_expected_results() {
+    let examples = examples_directory();
+
+    let mut paths = fs::read_dir(&examples)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "zρ"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut failures = Vec::new();
+
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+
+        if SLOW_EXAMPLES.contains(&name.as_str())
+            || NETWORKS
+                .iter()
+                .any(|network| network.programs.contains(&name.as_str()))
+        {
+            continue;
+        }
+
+        failures.extend(check_alone(&path));
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+#[test]
+#[ignore = "runs for over 10⁸ ticks"]
+fn slow_examples_match_expected_results() {
+    let examples = examples_directory();
+
+    let failures = (SLOW_EXAMPLES.iter())
+        .filter_map(|name| check_alone(&examples.join(name)))
+        .collect::<Vec<_>>();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+#[test]
+fn networks_match_expected_results() {
+    let examples = examples_directory();
+
+    let mut failures = Vec::new();
+
+    for network_definition in NETWORKS {
+        let machine = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("resources/machines")
+            .join(network_definition.machine);
+        let computer = Computer::load_from(&fs::read_to_string(machine).unwrap()).unwrap();
+
+        let mut network = Network::default();
+
+        for name in network_definition.programs {
+            let (program, _) = assemble(&examples.join(name), &computer);
+            network.add_node(computer.clone(), program);
+        }
+
+        for &(a, b) in network_definition.links {
+            network.link(endpoint(a), endpoint(b)).unwrap();
+        }
+
+        network.run(Some(TICK_LIMIT));
+
+        let mut results = String::new();
+
+        if network.is_deadlocked() {
+            writeln!(results, "deadlocked after {} ticks", network.runtime).unwrap();
+        }
+
+        for (name, node) in network_definition.programs.iter().zip(&network.nodes) {
+            write!(
+                results,
+                "\n[{name}]\n{}",
+                describe_node(node.computer.clone())
+            )
+            .unwrap();
+        }
+
+        failures.extend(compare(
+            &examples.join(network_definition.programs[0]),
+            results.trim_start(),
+        ));
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+fn examples_directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/examples")
+}
+
+fn assemble(path: &Path, computer: &Computer) -> (Program, SourceSet) {
+    let sources = SourceSet::load(path.to_str().unwrap()).unwrap();
+    let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+
+    match Program::assemble_from_sources(name, &sources, computer) {
+        Ok(program) => (program, sources),
+        Err(errors) => panic!(
+            "{}",
+            (errors.iter())
+                .map(|error| error.render(&sources).to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    }
+}
+
+/// Runs an example on its own on the default machine with H, returning a description of how its
+/// results differ from the expected ones, if they do.
+fn check_alone(path: &Path) -> Option<String> {
+    let computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+    let (program, _) = assemble(path, &computer);
+
+    compare(path, &describe_node(run_alone(computer, &program)))
+}
+
+fn run_alone(mut computer: Computer, program: &Program) -> Computer {
+    while computer.interrupt.is_none() && computer.runtime < TICK_LIMIT {
+        computer.step_tick(program);
+    }
+
+    computer
+}
+
+/// Parses an endpoint such as `1:C`, numbering the programs from 1 like `zrho run --link`.
+fn endpoint(endpoint: &str) -> Endpoint {
+    let (node, register) = endpoint.split_once(':').unwrap();
+
+    Endpoint {
+        node: node.parse::<usize>().unwrap() - 1,
+        register: computer::register_with_name(register.chars().next().unwrap()).unwrap(),
+    }
+}
+
+/// Writes out the final interrupt, runtime, energy used and every value of every register.
+fn describe_node(computer: Computer) -> String {
+    let mut description = String::new();
+
+    match computer.interrupt {
+        Some(interrupt) => writeln!(description, "interrupt: {interrupt:?}").unwrap(),
+        None if computer.runtime >= TICK_LIMIT => {
+            writeln!(description, "interrupt: none (reached the tick limit)").unwrap();
+        }
+        None => writeln!(description, "interrupt: none").unwrap(),
+    }
+
+    writeln!(description, "runtime: {}", computer.runtime).unwrap();
+    writeln!(description, "energy used: {}", computer.energy_used).unwrap();
+
+    for (i, register) in computer.registers.registers.iter().enumerate() {
+        let Some(register) = register else {
+            continue;
+        };
+
+        let name = computer::name_of_register(i as u32).unwrap();
+
+        match &register.values {
+            RegisterValues::Scalar(value) => {
+                writeln!(description, "{name}: {}", value.get()).unwrap();
+            }
+            RegisterValues::Vector { values, offset, .. } => {
+                // Trailing zeros are left out, so that mostly empty registers stay short
+                let used = values
+                    .iter()
+                    .rposition(|value| value.get() != 0)
+                    .map_or(0, |last| last + 1);
+
+                for (line, chunk) in values[..used].chunks(VALUES_PER_LINE).enumerate() {
+                    let start = line * VALUES_PER_LINE + *offset as usize;
+
+                    let chunk = (chunk.iter())
+                        .map(|value| value.get().to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+
+                    writeln!(description, "{name}[{start}]: {chunk}").unwrap();
+                }
+
+                if used < values.len() {
+                    writeln!(
+                        description,
+                        "{name}[{start}..{end}]: 0",
+                        start = used + *offset as usize,
+                        end = values.len() + *offset as usize,
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    description
+}
+
+/// Compares the results of an example with the file of expected results next to it, or writes
+/// the file if [`UPDATE_VARIABLE`] is set. Returns a description of any difference.
+fn compare(example: &Path, results: &str) -> Option<String> {
+    let expected_path = example.with_extension("expected");
+    let name = example.file_name().unwrap().to_string_lossy();
+
+    if env::var_os(UPDATE_VARIABLE).is_some() {
+        fs::write(&expected_path, results).unwrap();
+        return None;
+    }
+
+    let Ok(expected) = fs::read_to_string(&expected_path) else {
+        return Some(format!(
+            "{name}: No expected results at {path}, run with {UPDATE_VARIABLE}=1 to write them",
+            path = expected_path.display(),
+        ));
+    };
+
+    if expected == results {
+        return None;
+    }
+
+    Some(format!(
+        "{name}: Results differ from {path} (- expected, + got), run with {UPDATE_VARIABLE}=1 \
+         if this is intended:\n{diff}",
+        path = expected_path.display(),
+        diff = diff(&expected, results),
+    ))
+}
+
+/// A line by line diff of two texts, listing only the lines which differ along with their line
+/// numbers.
+fn diff(expected: &str, got: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let got = got.lines().collect::<Vec<_>>();
+
+    // The length of the longest common subsequence of the lines after each pair of lines
+    let mut common = vec![vec![0_usize; got.len() + 1]; expected.len() + 1];
+
+    for i in (0..expected.len()).rev() {
+        for j in (0..got.len()).rev() {
+            common[i][j] = if expected[i] == got[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < expected.len() || j < got.len() {
+        if i < expected.len() && j < got.len() && expected[i] == got[j] {
+            i += 1;
+            j += 1;
+        } else if j == got.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1]) {
+            writeln!(
+                diff,
+                "-{line:>5} | {text}",
+                line = i + 1,
+                text = expected[i]
+            )
+            .unwrap();
+            i += 1;
+        } else {
+            writeln!(diff, "+{line:>5} | {text}", line = j + 1, text = got[j]).unwrap();
+            j += 1;
+        }
+    }
+
+    diff
+}
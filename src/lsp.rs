@@ -0,0 +1,977 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{Value, json};
+
+use crate::simulation::{
+    computer::{self, Computer},
+    instruction::{ArgumentRequirement, InstructionProperties},
+    program::{
+        COMMENT_SEPARATOR, CONSTANT_PSEUDO_INSTRUCTION, EXPORT_PSEUDO_INSTRUCTION,
+        LABEL_PSEUDO_INSTRUCTION, MACRO_PSEUDO_INSTRUCTION, PSEUDO_INSTRUCTIONS, Program,
+        ProgramAssemblyError,
+    },
+    source::{self, SourceLocation, SourceSet},
+};
+
+/// A language server for zρ, which talks to an editor with JSON-RPC messages.
+#[derive(Debug)]
+pub struct LanguageServer {
+    /// The machine programs are assembled for
+    pub computer: Computer,
+    /// The text of each open document, by URI
+    pub documents: HashMap<String, String>,
+
+    shutdown_requested: bool,
+    exited: bool,
+}
+
+/// An error returned in response to a request.
+#[derive(Clone, Debug)]
+pub struct ResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// A token in a line of source code.
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    text: &'a str,
+    /// The byte offset of the token in its line
+    start: usize,
+    /// Whether the token is the first on its line, and so names an operation
+    first: bool,
+}
+
+impl LanguageServer {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+
+    /// Full text synchronisation: each change holds the whole document
+    pub const SYNC_FULL: u32 = 1;
+
+    pub const SEVERITY_ERROR: u32 = 1;
+
+    pub const COMPLETION_FUNCTION: u32 = 3;
+    pub const COMPLETION_VARIABLE: u32 = 6;
+    pub const COMPLETION_KEYWORD: u32 = 14;
+    pub const COMPLETION_REFERENCE: u32 = 18;
+    pub const COMPLETION_CONSTANT: u32 = 21;
+
+    #[must_use]
+    pub fn new(computer: Computer) -> Self {
+        Self {
+            computer,
+            documents: HashMap::new(),
+
+            shutdown_requested: false,
+            exited: false,
+        }
+    }
+
+    /// Handles messages until the client exits or closes the input. Returns whether the client
+    /// asked to shut down before exiting.
+    pub fn serve(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<bool> {
+        while !self.exited {
+            let Some(content) = read_message(&mut input)? else {
+                break;
+            };
+
+            let responses = match serde_json::from_slice::<Value>(&content) {
+                Ok(message) => self.handle_message(&message),
+                Err(error) => vec![error_response(
+                    Value::Null,
+                    ResponseError {
+                        code: Self::PARSE_ERROR,
+                        message: error.to_string(),
+                    },
+                )],
+            };
+
+            for response in responses {
+                write_message(&mut output, &response)?;
+            }
+        }
+
+        Ok(self.shutdown_requested)
+    }
+
+    /// Handles a request or notification, returning the messages to send back.
+    pub fn handle_message(&mut self, message: &Value) -> Vec<Value> {
+        let Some(method) = message["method"].as_str() else {
+            // Responses to requests from the server, which it never makes
+            if message.get("id").is_some() && message.get("result").is_none() {
+                return vec![error_response(
+                    message["id"].clone(),
+                    ResponseError {
+                        code: Self::INVALID_REQUEST,
+                        message: "Expected a method".to_owned(),
+                    },
+                )];
+            }
+
+            return Vec::new();
+        };
+
+        let params = &message["params"];
+
+        let Some(id) = message.get("id") else {
+            return self.handle_notification(method, params);
+        };
+
+        let response = match self.handle_request(method, params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => error_response(id.clone(), error),
+        };
+
+        vec![response]
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Value) -> Result<Value, ResponseError> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": Self::SYNC_FULL,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "zrho" },
+            })),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let (uri, line, column) = position_of(params)?;
+                Ok(self.hover(uri, line, column))
+            }
+            "textDocument/definition" => {
+                let (uri, line, column) = position_of(params)?;
+                Ok(self.definition(uri, line, column))
+            }
+            "textDocument/references" => {
+                let (uri, line, column) = position_of(params)?;
+                let include_declaration = params["context"]["includeDeclaration"]
+                    .as_bool()
+                    .unwrap_or(true);
+
+                Ok(self.references(uri, line, column, include_declaration))
+            }
+            "textDocument/completion" => {
+                let (uri, line, column) = position_of(params)?;
+                Ok(self.completion(uri, line, column))
+            }
+            _ => Err(ResponseError {
+                code: Self::METHOD_NOT_FOUND,
+                message: format!("Unknown method \"{method}\""),
+            }),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_owned(), text.to_owned());
+            }
+            "textDocument/didChange" => {
+                let Some(change) = params["contentChanges"].as_array().and_then(|c| c.last())
+                else {
+                    return Vec::new();
+                };
+
+                let text = change["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_owned(), text.to_owned());
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![publish_diagnostics(uri, Vec::new())];
+            }
+            "exit" => {
+                self.exited = true;
+                return Vec::new();
+            }
+            _ => return Vec::new(),
+        }
+
+        // Files which include the changed one may have new errors as well
+        let uris = self.documents.keys().cloned().collect::<Vec<_>>();
+
+        uris.iter()
+            .map(|uri| publish_diagnostics(uri, self.diagnostics(uri)))
+            .collect()
+    }
+
+    /// Assembles a document along with the files it includes, preferring the text of open
+    /// documents to the files on disk.
+    pub fn assemble(&self, uri: &str) -> (SourceSet, Result<Program, Vec<ProgramAssemblyError>>) {
+        let path = path_of_uri(uri);
+
+        let sources = SourceSet::load_with(&path, |path| {
+            let document = (self.documents.iter()).find(|(uri, _)| path_of_uri(uri) == path);
+
+            match document {
+                Some((_, text)) => Ok(text.clone()),
+                None => fs::read_to_string(path),
+            }
+        })
+        .unwrap_or_else(|_| {
+            let text = self.documents.get(uri).cloned().unwrap_or_default();
+            SourceSet::single(path.clone(), text)
+        });
+
+        let name = path.rsplit('/').next().unwrap_or_default().to_owned();
+        let program = Program::assemble_from_sources(name, &sources, &self.computer);
+
+        (sources, program)
+    }
+
+    /// The errors in a document, including those in files it includes, which are shown on the
+    /// lines including them.
+    fn diagnostics(&self, uri: &str) -> Vec<Value> {
+        let (sources, Err(errors)) = self.assemble(uri) else {
+            return Vec::new();
+        };
+
+        errors
+            .iter()
+            .filter_map(|error| {
+                let span = error.spans.first()?;
+
+                let range = if span.file == SourceLocation::MAIN_FILE {
+                    let line = sources.line(span.location()).unwrap_or_default();
+
+                    range_of(span.line, line, span.start, span.end)
+                } else {
+                    let file = sources.name_of(span.file);
+                    let (line, text) = include_line_of(&sources, file)?;
+
+                    range_of(line, text, 0, text.len())
+                };
+
+                let message = match &error.suggestion {
+                    Some(suggestion) => format!("{} (did you mean \"{suggestion}\"?)", error.kind),
+                    None => error.kind.to_string(),
+                };
+
+                let message = if span.file == SourceLocation::MAIN_FILE {
+                    message
+                } else {
+                    format!("{}: {message}", sources.name_of(span.file))
+                };
+
+                Some(json!({
+                    "range": range,
+                    "severity": Self::SEVERITY_ERROR,
+                    "source": "zrho",
+                    "message": message,
+                }))
+            })
+            .collect()
+    }
+
+    fn hover(&self, uri: &str, line: u32, column: u32) -> Value {
+        let Some((token, text)) = self.token_at(uri, line, column) else {
+            return Value::Null;
+        };
+
+        let properties = (self.computer.instruction_properties)
+            .instruction_with_name(token.text)
+            .filter(|_| token.first);
+
+        let Some(properties) = properties else {
+            return Value::Null;
+        };
+
+        json!({
+            "contents": { "kind": "markdown", "value": describe_operation(properties) },
+            "range": range_of(line, text, token.start, token.start + token.text.len()),
+        })
+    }
+
+    fn definition(&self, uri: &str, line: u32, column: u32) -> Value {
+        let Some((token, _)) = self.token_at(uri, line, column) else {
+            return Value::Null;
+        };
+
+        let (sources, _) = self.assemble(uri);
+
+        let label =
+            label_file_of(&sources, SourceLocation::MAIN_FILE, token.text).and_then(|file| {
+                definitions_in(&sources, LABEL_PSEUDO_INSTRUCTION, token.text)
+                    .into_iter()
+                    .find(|&(other, ..)| other == file)
+            });
+
+        // Constants and macros are shared by every file
+        let shared = || {
+            [CONSTANT_PSEUDO_INSTRUCTION, MACRO_PSEUDO_INSTRUCTION]
+                .into_iter()
+                .find_map(|pseudo_instruction| {
+                    definitions_in(&sources, pseudo_instruction, token.text)
+                        .into_iter()
+                        .next()
+                })
+        };
+
+        match label.or_else(shared) {
+            Some((file, line, token)) => location_of(&sources, uri, file, line, token),
+            None => Value::Null,
+        }
+    }
+
+    fn references(&self, uri: &str, line: u32, column: u32, include_declaration: bool) -> Value {
+        let Some((token, _)) = self.token_at(uri, line, column) else {
+            return Value::Null;
+        };
+
+        if token.first {
+            return Value::Null;
+        }
+
+        let (sources, _) = self.assemble(uri);
+
+        let Some(label_file) = label_file_of(&sources, SourceLocation::MAIN_FILE, token.text)
+        else {
+            return Value::Null;
+        };
+
+        // Files with a label of the same name use their own instead
+        let references = (0..sources.files.len() as u32)
+            .filter(|&file| label_file_of(&sources, file, token.text) == Some(label_file))
+            .flat_map(|file| {
+                let text = &sources.files[file as usize].text;
+
+                text.lines().enumerate().flat_map(move |(i, line)| {
+                    let tokens = tokens_of(line);
+
+                    let is_definition = tokens.first().is_some_and(|first| {
+                        first.text == LABEL_PSEUDO_INSTRUCTION
+                            || first.text == EXPORT_PSEUDO_INSTRUCTION
+                    });
+
+                    tokens
+                        .into_iter()
+                        .filter(move |candidate| {
+                            !candidate.first
+                                && candidate.text == token.text
+                                && (include_declaration || !is_definition)
+                        })
+                        .map(move |token| (file, i as u32, token))
+                })
+            })
+            .map(|(file, line, token)| location_of(&sources, uri, file, line, token))
+            .collect::<Vec<_>>();
+
+        Value::Array(references)
+    }
+
+    fn completion(&self, uri: &str, line: u32, column: u32) -> Value {
+        let Some(text) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+
+        let line_text = text.lines().nth(line as usize).unwrap_or_default();
+        let byte = byte_of_column(line_text, column);
+
+        let before = &line_text[..byte];
+
+        if before.contains(COMMENT_SEPARATOR) {
+            return json!([]);
+        }
+
+        // The cursor is in the first token if there's no whitespace between it and the cursor
+        let in_operation = !before.trim_start().contains(char::is_whitespace);
+
+        let mut items = Vec::new();
+
+        if in_operation {
+            for properties in &self.computer.instruction_properties.0 {
+                if !properties.enabled {
+                    continue;
+                }
+
+                items.push(json!({
+                    "label": properties.name,
+                    "kind": Self::COMPLETION_FUNCTION,
+                    "detail": signature_of(properties),
+                }));
+            }
+
+            for name in PSEUDO_INSTRUCTIONS {
+                items.push(json!({ "label": name, "kind": Self::COMPLETION_KEYWORD }));
+            }
+
+            for (_, name) in labels_in(text, MACRO_PSEUDO_INSTRUCTION) {
+                items.push(json!({ "label": name.text, "kind": Self::COMPLETION_FUNCTION }));
+            }
+        } else {
+            for (register, _) in (self.computer.registers.registers.iter().enumerate())
+                .filter(|(_, register)| register.is_some())
+            {
+                let name = computer::name_of_register(register as u32).unwrap();
+
+                items.push(json!({
+                    "label": name.to_string(),
+                    "kind": Self::COMPLETION_VARIABLE,
+                }));
+            }
+
+            for (_, label) in labels_in(text, LABEL_PSEUDO_INSTRUCTION) {
+                items.push(json!({ "label": label.text, "kind": Self::COMPLETION_REFERENCE }));
+            }
+
+            for (_, constant) in labels_in(text, CONSTANT_PSEUDO_INSTRUCTION) {
+                items.push(json!({ "label": constant.text, "kind": Self::COMPLETION_CONSTANT }));
+            }
+        }
+
+        Value::Array(items)
+    }
+
+    /// The token under the cursor in an open document, and the text of its line.
+    fn token_at(&self, uri: &str, line: u32, column: u32) -> Option<(Token<'_>, &str)> {
+        let text = self.documents.get(uri)?.lines().nth(line as usize)?;
+        let byte = byte_of_column(text, column);
+
+        let token = tokens_of(text)
+            .into_iter()
+            .find(|token| token.start <= byte && byte <= token.start + token.text.len())?;
+
+        Some((token, text))
+    }
+}
+
+/// The file defining the label `name` as it's used in `file`, which is `file` itself if it has
+/// one, or otherwise the file which exports it.
+fn label_file_of(sources: &SourceSet, file: u32, name: &str) -> Option<u32> {
+    let has = |file: u32, pseudo_instruction| {
+        labels_in(&sources.files[file as usize].text, pseudo_instruction)
+            .any(|(_, label)| label.text == name)
+    };
+
+    if has(file, LABEL_PSEUDO_INSTRUCTION) {
+        return Some(file);
+    }
+
+    (0..sources.files.len() as u32).find(|&other| {
+        has(other, LABEL_PSEUDO_INSTRUCTION) && has(other, EXPORT_PSEUDO_INSTRUCTION)
+    })
+}
+
+/// Every line defining `name` with `pseudo_instruction`, as the file and line it's on along with
+/// the token of the name.
+fn definitions_in<'a>(
+    sources: &'a SourceSet,
+    pseudo_instruction: &'a str,
+    name: &str,
+) -> Vec<(u32, u32, Token<'a>)> {
+    (sources.files.iter().enumerate())
+        .flat_map(|(file, source)| {
+            labels_in(&source.text, pseudo_instruction)
+                .filter(|(_, token)| token.text == name)
+                .map(move |(line, token)| (file as u32, line, token))
+        })
+        .collect()
+}
+
+/// The argument of each line starting with `pseudo_instruction`, along with its line.
+fn labels_in<'a>(
+    text: &'a str,
+    pseudo_instruction: &'a str,
+) -> impl Iterator<Item = (u32, Token<'a>)> {
+    text.lines().enumerate().filter_map(move |(i, line)| {
+        let tokens = tokens_of(line);
+
+        if tokens.first()?.text != pseudo_instruction {
+            return None;
+        }
+
+        Some((i as u32, *tokens.get(1)?))
+    })
+}
+
+/// The tokens of a line, excluding comments.
+fn tokens_of(line: &str) -> Vec<Token<'_>> {
+    let code = line.split(COMMENT_SEPARATOR).next().unwrap_or_default();
+
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (i, character) in code.char_indices().chain([(code.len(), ' ')]) {
+        match (start, character.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(token_start), true) => {
+                tokens.push(Token {
+                    text: &code[token_start..i],
+                    start: token_start,
+                    first: tokens.is_empty(),
+                });
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    tokens
+}
+
+/// The line of the main file which includes `file`, or the first include if it's included through
+/// another file.
+fn include_line_of<'a>(sources: &'a SourceSet, file: &str) -> Option<(u32, &'a str)> {
+    let main = sources.files.first()?;
+
+    let mut includes = (main.text.lines().enumerate())
+        .filter_map(|(i, line)| Some((i as u32, line, source::include_of_line(line)?)))
+        .map(|(i, line, include)| (i, line, source::include_path(&main.name, include)))
+        .collect::<Vec<_>>();
+
+    let direct = includes.iter().position(|(.., path)| path == file);
+    let (line, text, _) = includes.swap_remove(direct.unwrap_or(0));
+
+    Some((line, text))
+}
+
+fn describe_operation(properties: &InstructionProperties) -> String {
+    let mut description = format!("```\n{}\n```\n", signature_of(properties));
+
+    for (i, requirement) in properties.arguments.iter().enumerate() {
+        if *requirement != ArgumentRequirement::Empty {
+            description.push_str(&format!("\nArgument {}: {requirement}", i + 1));
+        }
+    }
+
+    description.push_str(&format!("\n\nTime: {}", properties.base_time));
+
+    if let Some((time, condition)) = properties.conditional_time {
+        description.push_str(&format!(", or {time} {condition}"));
+    }
+
+    description.push_str(&format!("\n\nEnergy: {}", properties.base_energy));
+
+    if let (Some(energy), Some((_, condition))) =
+        (properties.conditional_energy, properties.conditional_time)
+    {
+        description.push_str(&format!(", or {energy} {condition}"));
+    }
+
+    description
+}
+
+/// The name of an operation followed by the kind of each argument.
+fn signature_of(properties: &InstructionProperties) -> String {
+    let mut signature = properties.name.to_owned();
+
+    for requirement in properties.arguments {
+        let argument = match requirement {
+            ArgumentRequirement::Empty => continue,
+            ArgumentRequirement::Constant => "constant",
+            ArgumentRequirement::ConstantOrEmpty => "[constant]",
+            ArgumentRequirement::RegisterWriteOnly | ArgumentRequirement::Register => "register",
+            ArgumentRequirement::ConstantOrRegister => "value",
+            ArgumentRequirement::Comparison => "comparison",
+            ArgumentRequirement::AnyValue => "condition",
+            ArgumentRequirement::AnyValueOrEmpty => "[condition]",
+            ArgumentRequirement::Instruction => "label",
+        };
+
+        signature.push(' ');
+        signature.push_str(argument);
+    }
+
+    signature
+}
+
+/// Reads the content of a message, or `None` if the input has ended.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Message is missing a Content-Length header",
+        ));
+    };
+
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+
+    Ok(Some(content))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+
+    write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    output.flush()
+}
+
+fn error_response(id: Value, error: ResponseError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// The document, line and column of a request about a position in a document.
+fn position_of(params: &Value) -> Result<(&str, u32, u32), ResponseError> {
+    let uri = params["textDocument"]["uri"].as_str();
+    let line = params["position"]["line"].as_u64();
+    let column = params["position"]["character"].as_u64();
+
+    match (uri, line, column) {
+        (Some(uri), Some(line), Some(column)) => Ok((uri, line as u32, column as u32)),
+        _ => Err(ResponseError {
+            code: LanguageServer::INVALID_PARAMS,
+            message: "Expected a text document and position".to_owned(),
+        }),
+    }
+}
+
+/// The location of a token, where `uri` is the URI of the main file.
+fn location_of(sources: &SourceSet, uri: &str, file: u32, line: u32, token: Token) -> Value {
+    let text = sources
+        .line(SourceLocation { file, line })
+        .unwrap_or_default();
+
+    let uri = if file == SourceLocation::MAIN_FILE {
+        uri.to_owned()
+    } else {
+        uri_of_path(sources.name_of(file))
+    };
+
+    json!({
+        "uri": uri,
+        "range": range_of(line, text, token.start, token.start + token.text.len()),
+    })
+}
+
+/// A range within a line, given in bytes.
+fn range_of(line: u32, text: &str, start: usize, end: usize) -> Value {
+    json!({
+        "start": { "line": line, "character": column_of_byte(text, start) },
+        "end": { "line": line, "character": column_of_byte(text, end) },
+    })
+}
+
+/// Positions are counted in UTF-16 code units, which differ from bytes for characters like ρ.
+fn column_of_byte(line: &str, byte: usize) -> usize {
+    line.get(..byte)
+        .map_or(byte, |text| text.encode_utf16().count())
+}
+
+fn byte_of_column(line: &str, column: u32) -> usize {
+    let mut units = 0;
+
+    for (i, character) in line.char_indices() {
+        if units >= column as usize {
+            return i;
+        }
+
+        units += character.len_utf16();
+    }
+
+    line.len()
+}
+
+#[must_use]
+pub fn path_of_uri(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+
+    // Decode escapes such as %20
+    while let Some((&byte, remaining)) = rest.split_first() {
+        if byte == b'%'
+            && let Some(hex) = remaining.get(..2)
+            && let Ok(value) = u8::from_str_radix(&String::from_utf8_lossy(hex), 16)
+        {
+            bytes.push(value);
+            rest = &remaining[2..];
+        } else {
+            bytes.push(byte);
+            rest = remaining;
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[must_use]
+pub fn uri_of_path(path: &str) -> String {
+    let mut uri = "file://".to_owned();
+
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DEFAULT_MACHINE_WITH_H, test_files::TestDirectory};
+
+    fn server() -> LanguageServer {
+        LanguageServer::new(Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap())
+    }
+
+    /// Sends messages to the server as a client would, returning the messages sent back.
+    fn exchange(server: &mut LanguageServer, messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+
+        let mut output = Vec::new();
+        server.serve(&input[..], &mut output).unwrap();
+
+        let mut output = &output[..];
+        let mut responses = Vec::new();
+
+        while let Some(content) = read_message(&mut output).unwrap() {
+            responses.push(serde_json::from_slice(&content).unwrap());
+        }
+
+        responses
+    }
+
+    fn open(uri: &str, text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": { "uri": uri, "languageId": "zrho", "version": 1, "text": text },
+            },
+        })
+    }
+
+    fn request(id: u32, method: &str, uri: &str, line: u32, character: u32) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": true },
+            },
+        })
+    }
+
+    /// The line and starting column of each location, with the file name at the end of its URI.
+    fn locations(result: &Value) -> Vec<(String, u64, u64)> {
+        let mut locations = (result.as_array().unwrap().iter())
+            .map(|location| {
+                let uri = location["uri"].as_str().unwrap();
+                let start = &location["range"]["start"];
+
+                (
+                    uri.rsplit('/').next().unwrap().to_owned(),
+                    start["line"].as_u64().unwrap(),
+                    start["character"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        locations.sort();
+
+        locations
+    }
+
+    const MAIN: &str = "\
+INC \"lib.zrho\"
+LBL LOOP
+JMP LOOP
+CAL DONE
+";
+
+    const LIB: &str = "\
+EXP DONE
+LBL DONE
+JMP DONE
+LBL LOOP
+JMP LOOP
+";
+
+    #[test]
+    fn messages_round_trip() {
+        let message = json!({ "jsonrpc": "2.0", "method": "exit" });
+
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, &message).unwrap();
+
+        let content = read_message(&mut &bytes[..]).unwrap().unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&content).unwrap(), message);
+
+        assert!(read_message(&mut &b""[..]).unwrap().is_none());
+        assert!(read_message(&mut &b"Content-Type: x\r\n\r\n"[..]).is_err());
+    }
+
+    #[test]
+    fn opening_a_document_publishes_diagnostics() {
+        let uri = "file:///zrho-test/program.zrho";
+
+        let responses = exchange(
+            &mut server(),
+            &[
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+                open(uri, "SET X 1\nADD X 1\n"),
+            ],
+        );
+
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["hoverProvider"],
+            true
+        );
+
+        assert_eq!(responses[1]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(responses[1]["params"]["uri"], uri);
+
+        let diagnostics = responses[1]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+    }
+
+    #[test]
+    fn columns_are_counted_in_utf16() {
+        let line = "INC \"missing.zρ\" ; ρ";
+
+        assert_eq!(column_of_byte(line, line.find(';').unwrap()), 17);
+        assert_eq!(byte_of_column(line, 17), line.find(';').unwrap());
+
+        let uri = "file:///zrho-test/program.zrho";
+        let responses = exchange(&mut server(), &[open(uri, line)]);
+
+        let range = &responses[0]["params"]["diagnostics"][0]["range"];
+        assert_eq!(range["start"]["character"], 4);
+        assert_eq!(range["end"]["character"], 16);
+    }
+
+    #[test]
+    fn hover_and_completion() {
+        let uri = "file:///zrho-test/program.zrho";
+        let mut server = server();
+
+        let responses = exchange(
+            &mut server,
+            &[
+                open(uri, "LBL LOOP\nADD X 1 X\nJMP \n"),
+                request(1, "textDocument/hover", uri, 1, 1),
+                request(2, "textDocument/hover", uri, 1, 4),
+                request(3, "textDocument/completion", uri, 2, 0),
+                request(4, "textDocument/completion", uri, 2, 4),
+            ],
+        );
+
+        let hover = &responses[1]["result"];
+        assert!(hover["contents"]["value"].as_str().unwrap().contains("ADD"));
+        assert_eq!(hover["range"]["end"]["character"], 3);
+
+        assert_eq!(responses[2]["result"], Value::Null);
+
+        let labels = |response: &Value| {
+            (response["result"].as_array().unwrap().iter())
+                .map(|item| item["label"].as_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let operations = labels(&responses[3]);
+        assert!(operations.contains(&"ADD".to_owned()));
+        assert!(operations.contains(&"LBL".to_owned()));
+        assert!(!operations.contains(&"LOOP".to_owned()));
+
+        let arguments = labels(&responses[4]);
+        assert!(arguments.contains(&"LOOP".to_owned()));
+        assert!(arguments.contains(&"X".to_owned()));
+        assert!(!arguments.contains(&"ADD".to_owned()));
+    }
+
+    #[test]
+    fn definitions_and_references_follow_label_scopes() {
+        // Included files are only found on disk
+        let directory = TestDirectory::new("lsp-scopes");
+        directory.write("lib.zrho", LIB);
+        let uri = uri_of_path(&directory.write("main.zrho", MAIN));
+
+        let responses = exchange(
+            &mut server(),
+            &[
+                open(&uri, MAIN),
+                request(1, "textDocument/definition", &uri, 3, 5),
+                request(2, "textDocument/references", &uri, 2, 5),
+                request(3, "textDocument/references", &uri, 3, 5),
+            ],
+        );
+
+        let definition = &responses[1]["result"];
+        assert!(definition["uri"].as_str().unwrap().ends_with("lib.zrho"));
+        assert_eq!(definition["range"]["start"]["line"], 1);
+
+        // The label of the same name in the included file is a different label
+        assert_eq!(
+            locations(&responses[2]["result"]),
+            [
+                ("main.zrho".to_owned(), 1, 4),
+                ("main.zrho".to_owned(), 2, 4)
+            ],
+        );
+
+        // Exported labels are used from every file
+        assert_eq!(
+            locations(&responses[3]["result"]),
+            [
+                ("lib.zrho".to_owned(), 0, 4),
+                ("lib.zrho".to_owned(), 1, 4),
+                ("lib.zrho".to_owned(), 2, 4),
+                ("main.zrho".to_owned(), 3, 4),
+            ],
+        );
+    }
+}
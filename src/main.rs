@@ -1,18 +1,31 @@
+use std::{env, process::ExitCode};
+
 use crate::{
-    simulation::{
-        computer::{self, BlockCondition, Computer, Register, RegisterSet, RegisterValues},
-        instruction,
-        integer::{DigitInteger, Integer},
-        program::Program,
-    },
+    simulation::{computer::Computer, level::Level, program::Program, source::SourceSet},
     ui2::state::State,
 };
 
+pub mod cli;
+pub mod lsp;
+pub mod results;
 pub mod simulation;
 pub mod ui2;
 
-fn main() {
+#[cfg(test)]
+mod example_tests;
+#[cfg(test)]
+mod test_files;
+
+fn main() -> ExitCode {
+    let arguments = env::args().skip(1).collect::<Vec<_>>();
+
+    if let Some(exit_code) = cli::run(&arguments) {
+        return exit_code;
+    }
+
     State::default().run().unwrap();
+
+    ExitCode::SUCCESS
 }
 
 // let mut fullscreen = START_IN_FULLSCREEN;
@@ -132,154 +145,43 @@ fn main() {
 // }
 
 pub fn run_test_computer() {
-    let mut computer = default_computer(true);
-
-    let program = match Program::assemble_from("Test Program".to_owned(), PROGRAM, &computer) {
-        Ok(program) => program,
-        Err(errors) => {
-            for error in errors {
-                println!("{error}");
+    let mut computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+
+    let sources = SourceSet::single("Test Program".to_owned(), PROGRAM.to_owned());
+
+    let program =
+        match Program::assemble_from_sources("Test Program".to_owned(), &sources, &computer) {
+            Ok(program) => program,
+            Err(errors) => {
+                for error in errors {
+                    println!("{}", error.render(&sources));
+                }
+                return;
             }
-            return;
-        }
-    };
+        };
 
     println!(
         "Program length: {} instructions",
         program.instructions.len(),
     );
 
+    let initial = computer.clone();
+
     simulation::interactively_run(&mut computer, &program);
 
     if !std::ptr::eq(PROGRAM, KOLAKOSKI_SEQUENCE_LONG) {
         return;
     }
 
-    let length = computer
-        .registers
-        .get(computer::register_with_name('H').unwrap())
-        .unwrap()
-        .all_values()
-        .len();
-
-    let mut sequence = vec![1, 2, 2];
-    let mut i = 2;
-
-    while sequence.len() < length {
-        for _ in 0..sequence[i] {
-            sequence.push((i % 2 + 1) as i32);
-        }
-
-        i += 1;
-    }
+    let level = Level::load_from(KOLAKOSKI_SEQUENCE_LONG_LEVEL).unwrap();
 
-    // There may be an excess element
-    while sequence.len() > length {
-        sequence.pop();
-    }
-
-    let register = computer
-        .registers
-        .get(computer::register_with_name('H').unwrap())
-        .unwrap();
-
-    for (i, (computed, actual)) in register.all_values().iter().zip(sequence).enumerate() {
-        assert_eq!(
-            computed.get(),
-            actual,
-            "Element {} is incorrect",
-            i as Integer + register.offset()
-        );
+    if let Err(failure) = level.check(&initial, &computer) {
+        panic!("{failure}");
     }
 
     println!("Verified Kolakoski Sequence stored in H");
 }
 
-#[must_use]
-pub fn default_computer(h_register: bool) -> Computer {
-    const DIGITS: u8 = 3;
-
-    let registers = RegisterSet::new_empty()
-        .with_register(
-            'D',
-            Register {
-                values: RegisterValues::Vector {
-                    values: Box::new([DigitInteger::new(0, DIGITS).unwrap(); 100]),
-                    index: 0,
-                    offset: 0,
-                },
-                indexed_by: Some(computer::register_with_name('I').unwrap()),
-                read_time: 1,
-                write_time: 1,
-                ..Register::DEFAULT
-            },
-        )
-        .with_register(
-            'I',
-            Register {
-                values: RegisterValues::Scalar(DigitInteger::new(0, DIGITS).unwrap()),
-                indexes_array: Some(computer::register_with_name('D').unwrap()),
-                ..Register::DEFAULT
-            },
-        )
-        .with_register(
-            'X',
-            Register {
-                values: RegisterValues::Scalar(DigitInteger::new(0, DIGITS).unwrap()),
-                ..Register::DEFAULT
-            },
-        )
-        .with_register(
-            'Y',
-            Register {
-                values: RegisterValues::Scalar(DigitInteger::new(0, DIGITS).unwrap()),
-                ..Register::DEFAULT
-            },
-        )
-        .with_register(
-            'Z',
-            Register {
-                values: RegisterValues::Scalar(DigitInteger::new(0, DIGITS).unwrap()),
-                ..Register::DEFAULT
-            },
-        );
-
-    let registers = if h_register {
-        registers
-            .with_register(
-                'H',
-                Register {
-                    values: RegisterValues::Vector {
-                        values: vec![DigitInteger::new(0, DIGITS).unwrap(); 10000]
-                            .into_boxed_slice(),
-                        index: 0,
-                        offset: 0,
-                    },
-                    block_condition: Some(BlockCondition::IndexChange {
-                        minimum_change: 2,
-                        block_time: 16,
-                    }),
-                    indexed_by: Some(computer::register_with_name('M').unwrap()),
-                    read_time: 2,
-                    write_time: 4,
-                    ..Register::DEFAULT
-                },
-            )
-            .with_register(
-                'M',
-                Register {
-                    values: RegisterValues::Scalar(DigitInteger::new(0, DIGITS).unwrap()),
-                    indexes_array: Some(computer::register_with_name('H').unwrap()),
-                    ..Register::DEFAULT
-                },
-            )
-    } else {
-        registers
-    };
-
-    Computer::new(DIGITS, registers, instruction::DEFAULT_INSTRUCTIONS)
-}
-
 const PROGRAM: &str = KOLAKOSKI_SEQUENCE_LONG;
 
 pub const KOLAKOSKI_SEQUENCE_LONG: &str =
@@ -296,3 +198,10 @@ pub const FIBONACCI_SEQUENCE: &str = include_str!("../resources/examples/fibonac
 pub const PRIME_NUMBERS: &str = include_str!("../resources/examples/prime_numbers.zρ");
 
 pub const PRIME_NUMBERS_FAST: &str = include_str!("../resources/examples/prime_numbers_fast.zρ");
+
+pub const KOLAKOSKI_SEQUENCE_LONG_LEVEL: &str =
+    include_str!("../resources/levels/kolakoski_sequence_long.toml");
+
+pub const DEFAULT_MACHINE: &str = include_str!("../resources/machines/default.toml");
+
+pub const DEFAULT_MACHINE_WITH_H: &str = include_str!("../resources/machines/default_with_h.toml");
@@ -0,0 +1,302 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use toml::{Table, Value};
+
+use crate::simulation::{
+    definition::{self, DefinitionError, DefinitionErrorKind, Entry},
+    level::Scores,
+};
+
+/// The scores of solutions to each level, kept between runs.
+#[derive(Clone, Debug, Default)]
+pub struct ResultsStore {
+    /// The solutions to each level, by the name of the level
+    pub levels: BTreeMap<String, Vec<Solution>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Solution {
+    /// The path to the program
+    pub program: String,
+    pub scores: Scores,
+}
+
+/// A text histogram of the scores of many solutions, which can mark where one solution falls.
+#[derive(Clone, Debug)]
+pub struct Histogram<'a> {
+    pub title: &'a str,
+    pub values: Vec<u64>,
+    pub highlight: Option<u64>,
+}
+
+impl ResultsStore {
+    pub const DEFAULT_PATH: &str = "zrho_results.toml";
+
+    pub fn load_from(source_code: &str) -> Result<Self, Vec<DefinitionError>> {
+        let table = definition::parse(source_code)?;
+
+        let mut errors = Vec::new();
+        let mut root = Entry::new(String::new(), &table, &mut errors);
+
+        let mut store = Self::default();
+
+        if let Some(levels) = root.optional_table("levels") {
+            let mut levels_entry = root.child("levels", levels);
+
+            for (name, solutions) in levels {
+                let Some(solutions) = solutions.as_array() else {
+                    levels_entry.error(
+                        name,
+                        DefinitionErrorKind::IncorrectType {
+                            expected: "an array of tables",
+                        },
+                    );
+                    continue;
+                };
+
+                let solutions = solutions
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, solution)| {
+                        let key = format!("{name}[{i}]");
+                        let table = levels_entry.as_table(&key, solution)?;
+
+                        load_solution(levels_entry.child(&key, table))
+                    })
+                    .collect();
+
+                store.levels.insert(name.clone(), solutions);
+            }
+        }
+
+        root.finish(&["levels"]);
+
+        if errors.is_empty() {
+            Ok(store)
+        } else {
+            Err(errors)
+        }
+    }
+
+    #[must_use]
+    pub fn solutions(&self, level: &str) -> &[Solution] {
+        self.levels.get(level).map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds a solution to a level, replacing any earlier solution from the same program.
+    pub fn record(&mut self, level: &str, solution: Solution) {
+        let solutions = self.levels.entry(level.to_owned()).or_default();
+
+        solutions.retain(|other| other.program != solution.program);
+        solutions.push(solution);
+    }
+}
+
+fn load_solution(mut entry: Entry) -> Option<Solution> {
+    let program = entry.string("program");
+    let runtime = entry.integer("runtime", 0, i64::MAX);
+    let energy = entry.integer("energy", 0, i64::MAX);
+    let size = entry.integer("size", 0, i64::MAX);
+
+    entry.finish(&["program", "runtime", "energy", "size"]);
+
+    Some(Solution {
+        program: program?.to_owned(),
+        scores: Scores {
+            runtime: runtime? as u64,
+            energy: energy? as u64,
+            size: size? as usize,
+        },
+    })
+}
+
+impl Display for ResultsStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let integer = |value: u64| Value::Integer(i64::try_from(value).unwrap_or(i64::MAX));
+
+        let levels = self
+            .levels
+            .iter()
+            .map(|(name, solutions)| {
+                let solutions = solutions
+                    .iter()
+                    .map(|solution| {
+                        Value::Table(Table::from_iter([
+                            (
+                                "program".to_owned(),
+                                Value::String(solution.program.clone()),
+                            ),
+                            ("runtime".to_owned(), integer(solution.scores.runtime)),
+                            ("energy".to_owned(), integer(solution.scores.energy)),
+                            ("size".to_owned(), integer(solution.scores.size as u64)),
+                        ]))
+                    })
+                    .collect();
+
+                (name.clone(), Value::Array(solutions))
+            })
+            .collect::<Table>();
+
+        let mut table = Table::new();
+        table.insert("levels".to_owned(), Value::Table(levels));
+
+        write!(f, "{table}")
+    }
+}
+
+impl Histogram<'_> {
+    const BINS: u64 = 10;
+    const WIDTH: usize = 30;
+
+    /// The percentage of values which are worse (higher) than the highlighted value.
+    #[must_use]
+    pub fn percent_beaten(&self) -> Option<usize> {
+        let highlight = self.highlight?;
+
+        if self.values.is_empty() {
+            return None;
+        }
+
+        let beaten = self
+            .values
+            .iter()
+            .filter(|&&value| value > highlight)
+            .count();
+
+        Some(beaten * 100 / self.values.len())
+    }
+}
+
+impl Display for Histogram<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.title)?;
+
+        let all_values = || self.values.iter().copied().chain(self.highlight);
+
+        let (Some(minimum), Some(maximum)) = (all_values().min(), all_values().max()) else {
+            return writeln!(f, "    (no solutions)");
+        };
+
+        let bin_size = (maximum - minimum) / Self::BINS + 1;
+
+        let mut counts = vec![0; ((maximum - minimum) / bin_size + 1) as usize];
+
+        for value in &self.values {
+            counts[((value - minimum) / bin_size) as usize] += 1;
+        }
+
+        let most = counts.iter().copied().max().unwrap_or(0).max(1);
+
+        let labels = (0..counts.len() as u64)
+            .map(|i| {
+                let start = minimum + i * bin_size;
+
+                if bin_size == 1 {
+                    start.to_string()
+                } else {
+                    format!("{start}-{}", start + bin_size - 1)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+
+        for (i, (label, count)) in labels.iter().zip(&counts).enumerate() {
+            let length = (count * Self::WIDTH).div_ceil(most);
+            let bar = "█".repeat(length);
+
+            write!(f, "    {label:>label_width$} │{bar} {count}")?;
+
+            if self
+                .highlight
+                .is_some_and(|highlight| (highlight - minimum) / bin_size == i as u64)
+            {
+                write!(f, " ◀")?;
+            }
+
+            writeln!(f)?;
+        }
+
+        if let (Some(highlight), Some(percent)) = (self.highlight, self.percent_beaten()) {
+            writeln!(f, "    {highlight} is better than {percent}% of solutions")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solution(program: &str, runtime: u64) -> Solution {
+        Solution {
+            program: program.to_owned(),
+            scores: Scores {
+                runtime,
+                energy: runtime * 2,
+                size: 3,
+            },
+        }
+    }
+
+    #[test]
+    fn results_round_trip() {
+        let mut store = ResultsStore::default();
+        store.record("Array Sum", solution("/a/solution.zρ", 100));
+        store.record("Array Sum", solution("/b/solution.zρ", 80));
+        store.record("Array Sum", solution("/a/solution.zρ", 90));
+
+        let loaded = ResultsStore::load_from(&store.to_string()).unwrap();
+        let solutions = loaded.solutions("Array Sum");
+
+        // Programs with the same file name in different directories are kept apart
+        assert_eq!(solutions.len(), 2);
+        assert_eq!(solutions[1].program, "/a/solution.zρ");
+        assert_eq!(solutions[1].scores, solution("/a/solution.zρ", 90).scores);
+        assert!(loaded.solutions("Other").is_empty());
+    }
+
+    #[test]
+    fn histograms_mark_the_highlighted_value() {
+        let histogram = Histogram {
+            title: "Runtime",
+            values: vec![1, 2, 2, 3],
+            highlight: Some(2),
+        };
+
+        assert_eq!(histogram.percent_beaten(), Some(25));
+        assert_eq!(
+            histogram.to_string(),
+            "Runtime
+    1 │███████████████ 1
+    2 │██████████████████████████████ 2 ◀
+    3 │███████████████ 1
+    2 is better than 25% of solutions
+",
+        );
+    }
+
+    #[test]
+    fn histograms_group_wide_ranges_into_bins() {
+        let histogram = Histogram {
+            title: "Energy",
+            values: (0..100).collect(),
+            highlight: None,
+        };
+
+        let text = histogram.to_string();
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 1 + 10);
+        assert!(lines[1].trim_start().starts_with("0-9 │"), "{text}");
+        assert!(lines[10].ends_with(" 10"), "{text}");
+
+        let empty = Histogram {
+            title: "Size",
+            values: Vec::new(),
+            highlight: None,
+        };
+        assert_eq!(empty.to_string(), "Size\n    (no solutions)\n");
+    }
+}
@@ -5,35 +5,60 @@ use std::{
 
 use computer::Computer;
 
-use crate::simulation::program::Program;
+use crate::simulation::{debugger::Debugger, history::History, program::Program};
 
 pub mod argument;
 pub mod computer;
+pub mod debugger;
+pub mod definition;
+pub mod format;
+pub mod history;
 pub mod instruction;
 pub mod integer;
+pub mod level;
+pub mod machine;
+pub mod network;
+pub mod profile;
 pub mod program;
+pub mod snapshot;
+pub mod source;
+pub mod timing;
+pub mod trace;
 
 pub fn interactively_run(computer: &mut Computer, program: &Program) {
     let mut skip_ticks = 0;
     let mut repeat_ns = None;
     let mut print_execution = true;
     let mut timer_start: Option<Instant> = None;
+    let mut debugger = Debugger::default();
+    let mut history = History::new(computer);
 
     loop {
         let instruction = computer.instruction;
 
-        let modified = computer.step_cycle(program);
+        let modified = history.step_cycle(computer, program);
         let block_time = computer.block_time;
 
         if computer.block_time > 0 {
-            computer.step_instruction(program);
+            history.step_instruction(computer, program);
+        }
+
+        if debugger.check(computer, modified) && skip_ticks > 0 {
+            skip_ticks = 0;
+            print_execution = true;
+
+            if let Some(start) = timer_start {
+                println!("(took {:?})", start.elapsed());
+
+                timer_start = None;
+            }
         }
 
         if let Some(interrupt) = computer.interrupt {
             if let Some(instruction) = program.instructions.get(instruction as usize) {
                 print!(
-                    "\nHalted on line {line} ({instruction}):",
-                    line = instruction.line
+                    "\nHalted on {location} ({instruction}):",
+                    location = program.describe_location(instruction.location),
                 );
             }
 
@@ -48,94 +73,127 @@ pub fn interactively_run(computer: &mut Computer, program: &Program) {
                 energy = computer.energy_used,
             );
 
-            break;
-        }
+            if let Some(stack) = debugger::call_stack(computer, program) {
+                println!("{stack}");
+            }
 
-        if computer.tick_complete {
-            if print_execution {
-                if block_time == 0 {
-                    println!("completed tick");
-                } else {
-                    println!("completed {} ticks...", block_time + 1);
+            // Give a chance to step back from the interrupt before exiting
+            skip_ticks = 0;
+            print_execution = true;
+            timer_start = None;
+        } else {
+            if computer.tick_complete {
+                if print_execution {
+                    if block_time == 0 {
+                        println!("completed tick");
+                    } else {
+                        println!("completed {} ticks...", block_time + 1);
+                    }
                 }
-            }
 
-            if skip_ticks > 0 {
-                skip_ticks -= 1;
+                if skip_ticks > 0 {
+                    skip_ticks -= 1;
 
-                if skip_ticks == 0 {
-                    print_execution = true;
+                    if skip_ticks == 0 {
+                        print_execution = true;
 
-                    if let Some(start) = timer_start {
-                        println!("(took {:?})", start.elapsed());
+                        if let Some(start) = timer_start {
+                            println!("(took {:?})", start.elapsed());
 
-                        timer_start = None;
+                            timer_start = None;
+                        }
                     }
                 }
             }
-        }
 
-        if print_execution {
-            if let Some(instruction) = program.instructions.get(instruction as usize) {
-                print!("Line {line} ({instruction})", line = instruction.line);
-            } else {
-                print!("Instruction {instruction}");
-            }
+            if print_execution {
+                if let Some(instruction) = program.instructions.get(instruction as usize) {
+                    print!(
+                        "At {location} ({instruction})",
+                        location = program.describe_location(instruction.location),
+                    );
+                } else {
+                    print!("Instruction {instruction}");
+                }
+
+                println!(":");
 
-            println!(":");
+                if modified {
+                    println!("{}", computer.registers);
 
-            if modified {
-                println!("{}", computer.registers);
+                    if let Some(stack) = debugger::call_stack(computer, program) {
+                        println!("{stack}");
+                    }
+                }
             }
         }
 
         if skip_ticks != 0 {
-            if let Some(repeat_ns) = repeat_ns {
-                if repeat_ns > 0 {
-                    std::thread::sleep(Duration::from_nanos(repeat_ns));
-                }
+            if let Some(repeat_ns) = repeat_ns
+                && repeat_ns > 0
+            {
+                std::thread::sleep(Duration::from_nanos(repeat_ns));
             }
         } else {
-            let string = &mut String::new();
+            loop {
+                let string = &mut String::new();
 
-            std::io::stdin().lock().read_line(string).unwrap();
+                std::io::stdin().lock().read_line(string).unwrap();
 
-            let mut tokens = string.split_whitespace();
+                let mut tokens = string.split_whitespace();
 
-            if string.is_empty() {
-                // When not running interactively, add the missing newline
-                println!();
-            } else if let Some(input) = tokens.next() {
-                if ["e", "end"].contains(&input) {
-                    skip_ticks = u64::MAX;
-                } else if let Ok(input) = input.parse() {
-                    skip_ticks = input;
-                }
+                if string.is_empty() {
+                    // When not running interactively, add the missing newline
+                    println!();
 
-                if tokens.next() == Some("delay") {
-                    if let Some(Some(input)) = tokens.next().map(|input| input.parse::<u64>().ok())
-                    {
-                        let conversion_factor = match tokens.next() {
-                            Some("ns" | "nanosecond" | "nanoseconds") => 0,
-                            Some("μs" | "us" | "microsecond" | "microseconds") => 3,
-                            Some("ms" | "millisecond" | "milliseconds") => 6,
-                            Some("s" | "second" | "seconds") | None => 9,
-                            _ => 9,
-                        };
-
-                        repeat_ns = Some(input * 10u64.pow(conversion_factor));
-                    } else {
-                        repeat_ns = None;
+                    if computer.interrupt.is_some() {
+                        return;
+                    }
+                } else if let Some(input) = tokens.next() {
+                    if debugger.run_command(input, &mut tokens, computer, &mut history, program) {
+                        continue;
+                    }
+
+                    if computer.interrupt.is_some() {
+                        return;
+                    }
+
+                    if ["e", "end", "c", "continue"].contains(&input) {
+                        skip_ticks = u64::MAX;
+                    } else if let Ok(input) = input.parse() {
+                        skip_ticks = input;
+                    }
+
+                    if tokens.next() == Some("delay") {
+                        if let Some(Some(input)) =
+                            tokens.next().map(|input| input.parse::<u64>().ok())
+                        {
+                            let conversion_factor = match tokens.next() {
+                                Some("ns" | "nanosecond" | "nanoseconds") => 0,
+                                Some("μs" | "us" | "microsecond" | "microseconds") => 3,
+                                Some("ms" | "millisecond" | "milliseconds") => 6,
+                                Some("s" | "second" | "seconds") | None => 9,
+                                _ => 9,
+                            };
+
+                            repeat_ns = Some(input * 10u64.pow(conversion_factor));
+                        } else {
+                            repeat_ns = None;
+                        }
                     }
-                }
 
-                if repeat_ns.is_none() {
-                    timer_start = Some(Instant::now());
+                    if repeat_ns.is_none() {
+                        timer_start = Some(Instant::now());
 
-                    if skip_ticks > 0 {
-                        print_execution = false;
+                        if skip_ticks > 0 {
+                            print_execution = false;
+                        }
                     }
+                } else if computer.interrupt.is_some() {
+                    return;
                 }
+
+                break;
             }
         }
     }
@@ -165,13 +165,17 @@ impl NumberSource {
                             error: RegisterAccessError::NoSuchRegister { got: *index },
                         })?;
 
-                register
-                    .value()
-                    .map(|value| (value.get(), Some(*index)))
-                    .map_err(|error| InstructionEvaluationInterrupt::RegisterError {
+                let value = match register.channel {
+                    Some(channel) => channel.incoming.ok_or(RegisterAccessError::ChannelEmpty),
+                    None => register.value().map(|value| value.get()),
+                };
+
+                value.map(|value| (value, Some(*index))).map_err(|error| {
+                    InstructionEvaluationInterrupt::RegisterError {
                         register: *index,
                         error,
-                    })
+                    }
+                })
             }
             NumberSource::Constant(value) => Ok((*value, None)),
         }
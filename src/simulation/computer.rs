@@ -6,12 +6,17 @@ use std::{
     slice,
 };
 
+use strum::EnumCount;
+
 use super::{
     instruction::{
-        ArgumentValues, InstructionEvaluationInterrupt, InstructionKindMap, InstructionProperties,
+        ArgumentValues, FaultClass, Instruction, InstructionEvaluationInterrupt,
+        InstructionKindMap, InstructionProperties,
     },
-    integer::{AssignIntegerError, DigitInteger, Integer},
+    integer::{AssignIntegerError, BiggerInteger, DigitInteger, Integer, OverflowMode},
+    profile::Profile,
     program::Program,
+    trace::{Trace, TraceEvent},
 };
 
 #[derive(Clone, Debug)]
@@ -25,7 +30,9 @@ pub struct Computer {
     pub block_time: u32,
     pub tick_complete: bool,
 
-    next_instruction: u32,
+    pub(super) next_instruction: u32,
+    /// The channel register the computer is waiting on, if any
+    pub waiting_for_channel: Option<u32>,
 
     pub runtime: u64,
     pub energy_used: u64,
@@ -35,6 +42,14 @@ pub struct Computer {
 
     pub previous_instruction: Option<(u32, ArgumentValues)>,
     pub interrupt: Option<InstructionEvaluationInterrupt>,
+
+    pub return_stack: ReturnStack,
+    pub fault_handlers: FaultHandlers,
+
+    /// Records the cost of each instruction if set, which is kept when the computer is reset
+    pub profile: Option<Box<Profile>>,
+    /// Records everything the computer does if set, which is cleared when the computer is reset
+    pub trace: Option<Box<Trace>>,
 }
 
 impl Computer {
@@ -55,6 +70,7 @@ impl Computer {
             tick_complete: false,
 
             next_instruction: 0,
+            waiting_for_channel: None,
 
             runtime: 0,
             energy_used: 0,
@@ -64,6 +80,12 @@ impl Computer {
 
             previous_instruction: None,
             interrupt: None,
+
+            return_stack: ReturnStack::new(ReturnStack::DEFAULT_DEPTH),
+            fault_handlers: FaultHandlers::default(),
+
+            profile: None,
+            trace: None,
         }
     }
 
@@ -72,8 +94,21 @@ impl Computer {
         registers.reset_to_zero();
 
         let instruction_properties = std::mem::take(&mut self.instruction_properties);
+        let profile = self.profile.take();
+        let trace = self.trace.take();
+        let return_stack_depth = self.return_stack.depth;
+        let fault_register = self.fault_handlers.code_register;
 
         *self = Computer::new(self.maximum_digits, registers, instruction_properties);
+        self.return_stack = ReturnStack::new(return_stack_depth);
+        self.fault_handlers.code_register = fault_register;
+        self.profile = profile;
+        self.trace = trace.map(|trace| {
+            Box::new(Trace {
+                program_length: trace.program_length,
+                ..Trace::default()
+            })
+        });
     }
 
     pub fn step_tick(&mut self, program: &Program) {
@@ -105,6 +140,21 @@ impl Computer {
         ticks
     }
 
+    /// Sets which channel register is being waited on. A register which was being waited on is no
+    /// longer blocked once the wait is over, so it doesn't hold up the instruction which waited.
+    fn wait_for_channel(&mut self, register: Option<u32>) {
+        if let Some(previous) = self.waiting_for_channel
+            && Some(previous) != register
+            && let Some(previous) = self.registers.get_mut(previous)
+            && previous.block_reason == Some(BlockReason::Channel)
+        {
+            previous.block_time = 0;
+            previous.block_reason = None;
+        }
+
+        self.waiting_for_channel = register;
+    }
+
     /// Returns whether or not there was any operation run (includes time spent blocking).
     pub fn step_cycle(&mut self, program: &Program) -> bool {
         self.tick_complete = true;
@@ -119,6 +169,35 @@ impl Computer {
             let instruction = program.instructions.get(self.next_instruction as usize);
 
             if let Some(instruction) = instruction {
+                // Wait for the peer to read the last value sent, or to send a value to read
+                self.wait_for_channel(self.registers.unread_channel().or_else(|| {
+                    instruction.empty_channel(&self.registers, &self.instruction_properties)
+                }));
+
+                if let Some(register) = self.waiting_for_channel {
+                    self.record(|| TraceEvent::Blocked {
+                        register,
+                        reason: BlockReason::Channel,
+                        time: 1,
+                    });
+
+                    if let Some(profile) = &mut self.profile {
+                        let profile = profile.instruction_mut(self.next_instruction);
+                        profile.ticks += 1;
+                        profile.blocked_ticks += 1;
+                    }
+
+                    self.end_of_tick();
+
+                    // The register is blocked a tick at a time, after the end of the tick so that
+                    // it's still shown as blocked until the channel is checked again
+                    if let Some(register) = self.registers.get_mut(register) {
+                        register.block(1, BlockReason::Channel);
+                    }
+
+                    return false;
+                }
+
                 let previous_instruction = self.previous_instruction.as_ref().map(
                     |&(instruction, ref argument_values)| {
                         (&program.instructions[instruction as usize], argument_values)
@@ -133,6 +212,14 @@ impl Computer {
 
                 if limit.is_some_and(|limit| self.executed_instruction_groups[group] >= limit.get())
                 {
+                    let instruction = self.next_instruction;
+
+                    self.record(|| TraceEvent::WaitedForLimit { instruction });
+
+                    if let Some(profile) = &mut self.profile {
+                        profile.instruction_mut(instruction).ticks += 1;
+                    }
+
                     self.end_of_tick();
                     return false;
                 }
@@ -140,11 +227,19 @@ impl Computer {
                 self.executed_instructions[instruction.kind] += 1;
                 self.executed_instruction_groups[group] += 1;
 
+                let index = self.next_instruction;
+
+                let register_block_time = self.profile.is_some().then(|| {
+                    instruction.register_block_time(&self.registers, &self.instruction_properties)
+                });
+
                 match instruction.evaluate(
                     &mut self.registers,
                     &self.instruction_properties,
                     previous_instruction,
                     &mut self.next_instruction,
+                    &mut self.return_stack,
+                    &mut self.fault_handlers,
                     self.runtime,
                 ) {
                     Ok((time, energy, argument_values, update_previous_instruction)) => {
@@ -156,28 +251,73 @@ impl Computer {
                             _ => self.block_time = time - 1,
                         }
 
-                        if let Some(energy_used) = self.energy_used.checked_add(energy.into()) {
-                            self.energy_used = energy_used;
-                        } else {
-                            self.interrupt =
-                                Some(InstructionEvaluationInterrupt::EnergyCounterOverflow);
-                            self.previous_instruction = None;
+                        self.record(|| TraceEvent::Executed {
+                            instruction: index,
+                            argument_values,
+                            time,
+                            energy,
+                        });
+
+                        self.record_reads(instruction);
+
+                        if let Some(profile) = &mut self.profile {
+                            let profile = profile.instruction_mut(index);
+                            profile.runs += 1;
+                            profile.ticks += u64::from(time);
+                            profile.energy += u64::from(energy);
+                            profile.blocked_ticks +=
+                                u64::from(register_block_time.unwrap_or(0).min(time));
                         }
+
+                        self.use_energy(energy, index);
                     }
                     Err(interrupt) => {
-                        self.interrupt = Some(interrupt);
                         self.previous_instruction = None;
+
+                        if self.handle_fault(interrupt, index) {
+                            // A handled fault costs as much as the instruction would have, so
+                            // faulting isn't a cheaper way to branch than a jump
+                            let (time, energy) = (properties.base_time, properties.base_energy);
+
+                            match time {
+                                0 => self.tick_complete = false,
+                                _ => self.block_time = time - 1,
+                            }
+
+                            if let Some(profile) = &mut self.profile {
+                                let profile = profile.instruction_mut(index);
+                                profile.runs += 1;
+                                profile.ticks += u64::from(time);
+                                profile.energy += u64::from(energy);
+                            }
+
+                            self.use_energy(energy, index);
+                        } else {
+                            if let Some(profile) = &mut self.profile {
+                                let profile = profile.instruction_mut(index);
+                                profile.runs += 1;
+                                profile.ticks += 1;
+                            }
+
+                            self.interrupt = Some(interrupt);
+                            self.record_interrupt(index);
+                        }
                     }
                 }
             } else {
                 self.interrupt = Some(InstructionEvaluationInterrupt::ProgramComplete);
                 self.previous_instruction = None;
+                self.record_interrupt(self.next_instruction);
             }
         }
 
         if self.block_time == 0 {
             self.instruction = self.next_instruction;
-            self.registers.apply_buffered_writes();
+
+            match &mut self.trace {
+                Some(trace) => trace.apply_buffered_writes(&mut self.registers),
+                None => self.registers.apply_buffered_writes(),
+            }
         }
 
         if self.tick_complete {
@@ -187,6 +327,160 @@ impl Computer {
         true
     }
 
+    /// Jumps to the handler of an interrupt if the program has set one, writing its fault code to
+    /// the fault register. Returns whether or not the interrupt was handled.
+    fn handle_fault(
+        &mut self,
+        interrupt: InstructionEvaluationInterrupt,
+        instruction: u32,
+    ) -> bool {
+        let Some(class) = FaultClass::of(interrupt) else {
+            return false;
+        };
+
+        let Some(handler) = self.fault_handlers.handler(class) else {
+            return false;
+        };
+
+        if let Some(register) = self.fault_handlers.code_register
+            && self
+                .registers
+                .buffered_write(register, class.code().into())
+                .is_err()
+        {
+            return false;
+        }
+
+        self.next_instruction = handler;
+
+        self.record(|| TraceEvent::Faulted {
+            instruction,
+            code: class.code(),
+        });
+
+        true
+    }
+
+    /// Adds the energy used by an instruction to the total, stopping the computer if the total
+    /// overflows.
+    fn use_energy(&mut self, energy: u32, instruction: u32) {
+        if let Some(energy_used) = self.energy_used.checked_add(energy.into()) {
+            self.energy_used = energy_used;
+        } else {
+            self.interrupt = Some(InstructionEvaluationInterrupt::EnergyCounterOverflow);
+            self.previous_instruction = None;
+            self.record_interrupt(instruction);
+        }
+    }
+
+    /// Counts the registers read by an instruction towards [`BlockCondition::Contention`], like the
+    /// writes counted by [`RegisterSet::write`].
+    fn record_reads(&mut self, instruction: &Instruction) {
+        if !self.registers.has_contention {
+            return;
+        }
+
+        for register in instruction.registers_read(&self.instruction_properties) {
+            let Some(data) = self.registers.get_mut(register) else {
+                continue;
+            };
+
+            if data.record_access() {
+                let time = data.block_time;
+
+                self.record(|| TraceEvent::Blocked {
+                    register,
+                    reason: BlockReason::Contention,
+                    time,
+                });
+            }
+        }
+    }
+
+    /// Records what [`Computer::undo_cycle`] needs to undo a cycle, apart from the values and
+    /// states of registers.
+    #[must_use]
+    pub fn cycle_state(&self) -> CycleState {
+        CycleState {
+            instruction: self.instruction,
+            block_time: self.block_time,
+            tick_complete: self.tick_complete,
+            next_instruction: self.next_instruction,
+            waiting_for_channel: self.waiting_for_channel,
+            runtime: self.runtime,
+            energy_used: self.energy_used,
+            executed_instructions: self.executed_instructions,
+            executed_instruction_groups: self.executed_instruction_groups,
+            previous_instruction: self.previous_instruction,
+            interrupt: self.interrupt,
+            fault_handlers: self.fault_handlers,
+            return_stack_length: self.return_stack.addresses.len(),
+            return_address: self.return_stack.addresses.last().copied(),
+            buffered_writes: self.registers.buffered_writes.clone(),
+        }
+    }
+
+    /// Undoes a cycle, given the state recorded before it, the earlier states of the registers it
+    /// changed (see [`RegisterSet::changed_states`]) and every value it overwrote.
+    pub fn undo_cycle(
+        &mut self,
+        state: CycleState,
+        registers: &[(u32, RegisterState)],
+        overwritten: &[OverwrittenValue],
+    ) {
+        self.instruction = state.instruction;
+        self.block_time = state.block_time;
+        self.tick_complete = state.tick_complete;
+        self.next_instruction = state.next_instruction;
+        self.waiting_for_channel = state.waiting_for_channel;
+        self.runtime = state.runtime;
+        self.energy_used = state.energy_used;
+        self.executed_instructions = state.executed_instructions;
+        self.executed_instruction_groups = state.executed_instruction_groups;
+        self.previous_instruction = state.previous_instruction;
+        self.interrupt = state.interrupt;
+        self.fault_handlers = state.fault_handlers;
+        self.registers.buffered_writes = state.buffered_writes;
+
+        // A cycle runs at most one instruction, so it pushes or pops at most one return address
+        let addresses = &mut self.return_stack.addresses;
+        addresses.truncate(state.return_stack_length);
+
+        if addresses.len() < state.return_stack_length {
+            addresses.extend(state.return_address);
+        }
+
+        for (index, state) in registers {
+            if let Some(register) = self.registers.get_mut(*index) {
+                register.restore_state(state);
+            }
+        }
+
+        for change in overwritten.iter().rev() {
+            if let Some(register) = self.registers.get_mut(change.register)
+                && let Some(value) = register.all_values_mut().get_mut(change.element)
+            {
+                *value = change.value;
+            }
+        }
+    }
+
+    /// Adds an event to the trace, if the computer is recording one.
+    fn record(&mut self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(trace) = &mut self.trace {
+            trace.events.push(event());
+        }
+    }
+
+    fn record_interrupt(&mut self, instruction: u32) {
+        if let Some(interrupt) = self.interrupt {
+            self.record(|| TraceEvent::Interrupted {
+                instruction,
+                interrupt,
+            });
+        }
+    }
+
     fn end_of_tick(&mut self) {
         self.executed_instructions = InstructionKindMap::from_element(0);
         self.executed_instruction_groups = InstructionKindMap::from_element(0);
@@ -196,6 +490,12 @@ impl Computer {
         } else {
             self.interrupt = Some(InstructionEvaluationInterrupt::RuntimeCounterOverflow);
             self.previous_instruction = None;
+            self.record_interrupt(self.instruction);
+        }
+
+        if let Some(trace) = &mut self.trace {
+            trace.events.push(TraceEvent::EndOfTick);
+            trace.runtime = self.runtime;
         }
 
         for register in self.registers.registers.iter_mut().flatten() {
@@ -204,26 +504,171 @@ impl Computer {
     }
 }
 
+/// Where to jump when an instruction causes a fault, instead of stopping the program.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FaultHandlers {
+    /// The instruction to jump to for each [`FaultClass`], set by `HDL`
+    pub handlers: [Option<u32>; FaultClass::COUNT],
+    /// The register the code of a fault is written to before jumping to its handler
+    pub code_register: Option<u32>,
+}
+
+impl FaultHandlers {
+    #[must_use]
+    pub fn handler(&self, class: FaultClass) -> Option<u32> {
+        self.handlers[class as usize]
+    }
+
+    /// Sets the handler of a fault class, or of every class if there isn't one.
+    pub fn set(&mut self, class: Option<FaultClass>, handler: u32) {
+        match class {
+            Some(class) => self.handlers[class as usize] = Some(handler),
+            None => self.handlers = [Some(handler); FaultClass::COUNT],
+        }
+    }
+}
+
+/// Where to continue from after each `CAL` which hasn't returned yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReturnStack {
+    /// The most calls which can be waiting to return at once
+    pub depth: u32,
+    /// The instruction after each call, with the most recent last
+    pub addresses: Vec<u32>,
+}
+
+impl ReturnStack {
+    pub const DEFAULT_DEPTH: u32 = 8;
+
+    #[must_use]
+    pub fn new(depth: u32) -> Self {
+        Self {
+            depth,
+            addresses: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, address: u32) -> Result<(), InstructionEvaluationInterrupt> {
+        if self.addresses.len() >= self.depth as usize {
+            return Err(InstructionEvaluationInterrupt::ReturnStackOverflow);
+        }
+
+        self.addresses.push(address);
+
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<u32, InstructionEvaluationInterrupt> {
+        self.addresses
+            .pop()
+            .ok_or(InstructionEvaluationInterrupt::ReturnStackUnderflow)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RegisterSet {
     pub registers: Box<[Option<Register>; NUM_REGISTERS]>,
+    pub(super) buffered_writes: Vec<(u32, Integer)>,
+    journal: Option<Vec<OverwrittenValue>>,
+    /// Whether any register has a [`BlockCondition::Contention`], so reads only need recording
+    /// when one does
+    has_contention: bool,
+}
+
+/// A value in a register from before it was written to.
+#[derive(Clone, Copy, Debug)]
+pub struct OverwrittenValue {
+    pub register: u32,
+    pub element: usize,
+    pub value: DigitInteger,
+}
+
+/// The parts of a [`Computer`] which a cycle can change, other than its registers. See
+/// [`Computer::cycle_state`].
+#[derive(Clone, Debug)]
+pub struct CycleState {
+    instruction: u32,
+    block_time: u32,
+    tick_complete: bool,
+    next_instruction: u32,
+    waiting_for_channel: Option<u32>,
+    runtime: u64,
+    energy_used: u64,
+    executed_instructions: InstructionKindMap<u8>,
+    executed_instruction_groups: InstructionKindMap<u8>,
+    previous_instruction: Option<(u32, ArgumentValues)>,
+    interrupt: Option<InstructionEvaluationInterrupt>,
+    fault_handlers: FaultHandlers,
+    return_stack_length: usize,
+    /// The return address at the top of the stack, in case the cycle pops it
+    return_address: Option<u32>,
     buffered_writes: Vec<(u32, Integer)>,
 }
 
+/// The parts of a [`Register`] which change as it's used, other than its values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegisterState {
+    /// The index of a vector register, or 0 for a scalar
+    index: Integer,
+    block_time: u32,
+    block_reason: Option<BlockReason>,
+    block_state: BlockState,
+    indexed_by: Option<u32>,
+    channel: Option<Channel>,
+}
+
 impl RegisterSet {
     #[must_use]
     pub fn new_empty() -> Self {
         Self {
             registers: Box::new(array::from_fn(|_| None)),
             buffered_writes: Vec::new(),
+            journal: None,
+            has_contention: false,
         }
     }
 
+    /// Starts recording the previous value of everything written to, until
+    /// [`RegisterSet::take_journal`] is called.
+    pub fn start_journal(&mut self) {
+        self.journal = Some(Vec::new());
+    }
+
+    pub fn take_journal(&mut self) -> Vec<OverwrittenValue> {
+        self.journal.take().unwrap_or_default()
+    }
+
+    /// The state of every register, to compare with later by [`RegisterSet::changed_states`].
+    #[must_use]
+    pub fn states(&self) -> [Option<RegisterState>; NUM_REGISTERS] {
+        array::from_fn(|i| self.registers[i].as_ref().map(Register::state))
+    }
+
+    /// Returns the earlier state of each register which has changed since `states` were taken.
+    #[must_use]
+    pub fn changed_states(
+        &self,
+        states: [Option<RegisterState>; NUM_REGISTERS],
+    ) -> Vec<(u32, RegisterState)> {
+        (states.into_iter().zip(self.registers.iter()).enumerate())
+            .filter_map(|(i, (state, register))| {
+                let state = state?;
+                (register.as_ref()?.state() != state).then_some((i as u32, state))
+            })
+            .collect()
+    }
+
     pub fn reset_to_zero(&mut self) {
         self.buffered_writes.clear();
 
         for register in self.registers.iter_mut().flatten() {
             register.block_time = 0;
+            register.block_reason = None;
+            register.block_state = BlockState::default();
+
+            if let Some(channel) = &mut register.channel {
+                *channel = Channel::default();
+            }
 
             match &mut register.values {
                 RegisterValues::Scalar(value) => value.try_set(0).unwrap(),
@@ -238,6 +683,12 @@ impl RegisterSet {
         }
     }
 
+    /// The values which will be written to registers when the current instruction finishes.
+    #[must_use]
+    pub fn buffered_writes(&self) -> &[(u32, Integer)] {
+        &self.buffered_writes
+    }
+
     pub fn apply_buffered_writes(&mut self) {
         let mut buffered_writes = std::mem::take(&mut self.buffered_writes);
 
@@ -260,7 +711,16 @@ impl RegisterSet {
             },
         )?;
 
-        Ok(register_entry.replace(register))
+        let previous = register_entry.replace(register);
+
+        self.has_contention = (self.registers.iter().flatten()).any(|register| {
+            matches!(
+                register.block_condition,
+                Some(BlockCondition::Contention { .. })
+            )
+        });
+
+        Ok(previous)
     }
 
     #[must_use]
@@ -270,44 +730,92 @@ impl RegisterSet {
         self
     }
 
+    /// Returns the first channel register with a value which the peer hasn't read yet.
+    #[must_use]
+    pub fn unread_channel(&self) -> Option<u32> {
+        self.registers
+            .iter()
+            .position(|register| {
+                register
+                    .as_ref()
+                    .and_then(|register| register.channel)
+                    .is_some_and(|channel| channel.outgoing.is_some())
+            })
+            .map(|i| i as u32)
+    }
+
+    /// Marks the value sent to a channel register as read. Does nothing for other registers.
+    pub fn take_incoming(&mut self, index: u32) {
+        if let Some(channel) = self
+            .get_mut(index)
+            .and_then(|register| register.channel.as_mut())
+        {
+            channel.incoming = None;
+        }
+    }
+
     #[must_use]
     pub fn get(&self, index: u32) -> Option<&Register> {
         self.registers.get(index as usize)?.as_ref()
     }
 
     #[must_use]
-    fn get_mut(&mut self, index: u32) -> Option<&mut Register> {
+    pub(super) fn get_mut(&mut self, index: u32) -> Option<&mut Register> {
         self.registers.get_mut(index as usize)?.as_mut()
     }
 
+    /// Writes a value once the instruction finishes. The value the register will have is worked
+    /// out now, so that it's only brought into range once.
     pub fn buffered_write(
         &mut self,
         index: u32,
-        value: Integer,
+        value: BiggerInteger,
     ) -> Result<(), RegisterAccessError> {
-        let register = self
+        let value = self
             .get(index)
-            .ok_or(RegisterAccessError::NoSuchRegister { got: index })?;
-        register
-            .value()?
-            .is_valid(value)
-            .map_err(|error| RegisterAccessError::InvalidAssignment { error })?;
+            .ok_or(RegisterAccessError::NoSuchRegister { got: index })?
+            .value_after_write(value)?;
 
         self.buffered_writes.push((index, value));
 
         Ok(())
     }
 
+    /// Sets the value of a register, which should come from [`Self::buffered_write`].
     pub fn write(&mut self, index: u32, value: Integer) -> Result<(), RegisterAccessError> {
         let register = self
             .get_mut(index)
             .ok_or(RegisterAccessError::NoSuchRegister { got: index })?;
-        register
-            .value_mut()?
+
+        let element = register.element_index();
+        let destination = register.value_mut()?;
+        let overwritten = *destination;
+
+        destination
             .try_set(value)
             .map_err(|error| RegisterAccessError::InvalidAssignment { error })?;
 
-        if let Some(array_index) = register.indexes_array {
+        register.record_access();
+
+        if let Some(BlockCondition::WriteBack { latency }) = register.block_condition {
+            register.block(latency, BlockReason::WriteBack);
+        }
+
+        if let Some(channel) = &mut register.channel {
+            channel.outgoing = Some(value);
+        }
+
+        let indexes_array = register.indexes_array;
+
+        if let Some(journal) = &mut self.journal {
+            journal.push(OverwrittenValue {
+                register: index,
+                element,
+                value: overwritten,
+            });
+        }
+
+        if let Some(array_index) = indexes_array {
             let indexed_register = self
                 .get_mut(array_index)
                 .ok_or(RegisterAccessError::NoSuchRegister { got: array_index })?;
@@ -315,24 +823,11 @@ impl RegisterSet {
             indexed_register.indexed_by = Some(index);
 
             match &mut indexed_register.values {
-                RegisterValues::Vector { index, .. } => {
-                    if let Some(BlockCondition::IndexChange {
-                        minimum_change,
-                        block_time,
-                    }) = indexed_register.block_condition
-                    {
-                        if index.abs_diff(value) >= minimum_change {
-                            indexed_register.block_reason = Some(match value.cmp(index) {
-                                std::cmp::Ordering::Less => BlockReason::IndexDecreased,
-                                std::cmp::Ordering::Equal => BlockReason::IndexWrittenNoOp,
-                                std::cmp::Ordering::Greater => BlockReason::IndexIncreased,
-                            });
-
-                            indexed_register.block_time = block_time;
-                        }
-                    }
-
+                RegisterValues::Vector { index, offset, .. } => {
+                    let (previous, offset) = (*index, *offset);
                     *index = value;
+
+                    indexed_register.index_moved(previous, value, offset);
                 }
                 RegisterValues::Scalar(_) => {
                     return Err(RegisterAccessError::NoSuchRegister { got: array_index });
@@ -377,25 +872,111 @@ pub struct Register {
     pub block_time: u32,
     pub block_reason: Option<BlockReason>,
     pub block_condition: Option<BlockCondition>,
+    pub block_state: BlockState,
     pub indexes_array: Option<u32>,
     pub indexed_by: Option<u32>,
     pub read_time: u32,
     pub write_time: u32,
+    pub channel: Option<Channel>,
+    /// Whether writing to the register adds to its value instead of replacing it
+    pub seeking: bool,
+    pub overflow: OverflowMode,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// The state of a register which passes values to another computer in a
+/// [`Network`](super::network::Network). Reading takes the value sent by the peer, and writing
+/// sends a value to the peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Channel {
+    /// A value written to the register which the peer hasn't read yet
+    pub outgoing: Option<Integer>,
+    /// Whether or not `outgoing` has been passed to the peer
+    pub delivered: bool,
+    /// A value from the peer which hasn't been read yet
+    pub incoming: Option<Integer>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BlockReason {
     IndexIncreased,
     IndexWrittenNoOp,
     IndexDecreased,
+    NotSequential,
+    CacheMiss,
+    WriteBack,
+    Contention,
+    /// Waiting for the other end of a channel, which doesn't take a set number of ticks
+    Channel,
+}
+
+impl Display for BlockReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockReason::IndexIncreased => write!(f, "index increased too far"),
+            BlockReason::IndexWrittenNoOp => write!(f, "index written"),
+            BlockReason::IndexDecreased => write!(f, "index decreased too far"),
+            BlockReason::NotSequential => write!(f, "index moved other than to the next element"),
+            BlockReason::CacheMiss => write!(f, "element wasn't cached"),
+            BlockReason::WriteBack => write!(f, "writing back"),
+            BlockReason::Contention => write!(f, "too many accesses this tick"),
+            BlockReason::Channel => write!(f, "waiting for the other end of the channel"),
+        }
+    }
 }
 
+/// Makes a register wait before it can be used again, to model slow storage.
 #[derive(Clone, Copy, Debug)]
 pub enum BlockCondition {
     IndexChange {
         minimum_change: u32,
         block_time: u32,
     },
+    /// Moving the index to the next element is free, but moving it anywhere else waits
+    Sequential { block_time: u32 },
+    /// Elements are loaded in lines of `line_length`, and the last `lines` lines used are kept.
+    /// Moving the index to an element in any other line waits.
+    Cache {
+        line_length: u32,
+        lines: u32,
+        miss_time: u32,
+    },
+    /// Writing waits for the value to be written back before the register can be used again
+    WriteBack { latency: u32 },
+    /// Reading or writing `accesses` times in a tick waits
+    Contention { accesses: u32, cooldown: u32 },
+}
+
+impl BlockCondition {
+    pub const MAXIMUM_CACHE_LINES: usize = 8;
+}
+
+/// What a [`BlockCondition`] needs to remember between accesses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockState {
+    /// The lines of a [`BlockCondition::Cache`], most recently used first
+    pub cached_lines: [Option<Integer>; BlockCondition::MAXIMUM_CACHE_LINES],
+    /// The reads and writes during this tick, for [`BlockCondition::Contention`]
+    pub accesses: u32,
+}
+
+impl BlockState {
+    /// Moves a line to the front of the cache, loading it in place of the least recently used
+    /// line if it isn't there. Returns whether or not it was already cached.
+    pub fn use_line(&mut self, line: Integer, lines: u32) -> bool {
+        let lines = &mut self.cached_lines[..lines as usize];
+
+        match lines.iter().position(|&cached| cached == Some(line)) {
+            Some(position) => {
+                lines[..=position].rotate_right(1);
+                true
+            }
+            None => {
+                lines.rotate_right(1);
+                lines[0] = Some(line);
+                false
+            }
+        }
+    }
 }
 
 impl Register {
@@ -404,13 +985,26 @@ impl Register {
         block_time: 0,
         block_reason: None,
         block_condition: None,
+        block_state: BlockState {
+            cached_lines: [None; BlockCondition::MAXIMUM_CACHE_LINES],
+            accesses: 0,
+        },
         indexes_array: None,
         indexed_by: None,
         read_time: 0,
         write_time: 0,
+        channel: None,
+        seeking: false,
+        overflow: OverflowMode::Trap,
     };
 
+    /// The most elements a vector register can have. Anything longer in a machine definition is
+    /// far more likely to be a typo than a machine someone wants to allocate.
+    pub const MAXIMUM_LENGTH: usize = 1 << 20;
+
     pub fn end_of_tick(&mut self) {
+        self.block_state.accesses = 0;
+
         if self.block_time > 0 {
             self.block_time -= 1;
 
@@ -420,10 +1014,122 @@ impl Register {
         }
     }
 
+    #[must_use]
+    pub fn state(&self) -> RegisterState {
+        RegisterState {
+            index: match self.values {
+                RegisterValues::Scalar(_) => 0,
+                RegisterValues::Vector { index, .. } => index,
+            },
+            block_time: self.block_time,
+            block_reason: self.block_reason,
+            block_state: self.block_state,
+            indexed_by: self.indexed_by,
+            channel: self.channel,
+        }
+    }
+
+    pub fn restore_state(&mut self, state: &RegisterState) {
+        if let RegisterValues::Vector { index, .. } = &mut self.values {
+            *index = state.index;
+        }
+
+        self.block_time = state.block_time;
+        self.block_reason = state.block_reason;
+        self.block_state = state.block_state;
+        self.indexed_by = state.indexed_by;
+        self.channel = state.channel;
+    }
+
+    pub fn block(&mut self, time: u32, reason: BlockReason) {
+        self.block_time = time;
+        self.block_reason = Some(reason);
+    }
+
+    /// Blocks the register if its block condition says that moving its index takes time.
+    pub fn index_moved(&mut self, from: Integer, to: Integer, offset: Integer) {
+        let block = match self.block_condition {
+            Some(BlockCondition::IndexChange {
+                minimum_change,
+                block_time,
+            }) if from.abs_diff(to) >= minimum_change => Some((
+                block_time,
+                match to.cmp(&from) {
+                    std::cmp::Ordering::Less => BlockReason::IndexDecreased,
+                    std::cmp::Ordering::Equal => BlockReason::IndexWrittenNoOp,
+                    std::cmp::Ordering::Greater => BlockReason::IndexIncreased,
+                },
+            )),
+            Some(BlockCondition::Sequential { block_time })
+                if to != from && Some(to) != from.checked_add(1) =>
+            {
+                Some((block_time, BlockReason::NotSequential))
+            }
+            Some(BlockCondition::Cache {
+                line_length,
+                lines,
+                miss_time,
+            }) => {
+                let line = to.saturating_sub(offset).div_euclid(line_length as Integer);
+
+                (!self.block_state.use_line(line, lines))
+                    .then_some((miss_time, BlockReason::CacheMiss))
+            }
+            _ => None,
+        };
+
+        if let Some((time, reason)) = block {
+            self.block(time, reason);
+        }
+    }
+
+    /// Counts a read or write towards [`BlockCondition::Contention`]. Returns whether or not the
+    /// register was blocked.
+    pub fn record_access(&mut self) -> bool {
+        if let Some(BlockCondition::Contention { accesses, cooldown }) = self.block_condition {
+            self.block_state.accesses += 1;
+
+            if self.block_state.accesses == accesses {
+                self.block(cooldown, BlockReason::Contention);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// The value the register will have once `value` is written to it.
+    pub fn value_after_write(&self, value: BiggerInteger) -> Result<Integer, RegisterAccessError> {
+        let current = self.value()?;
+
+        // Seeking registers add what's written to them, so the index of an array can be moved
+        // without knowing where it is
+        let value = if self.seeking {
+            current.get_bigger().saturating_add(value)
+        } else {
+            value
+        };
+
+        current
+            .fit(value, self.overflow)
+            .map_err(|error| RegisterAccessError::InvalidAssignment { error })
+    }
+
     pub fn set_time_to_write(&self, write_time: &mut u32, block_time: &mut u32) {
-        *write_time += self.write_time;
+        *write_time += self.time_to_write();
         *block_time = self.block_time.max(*block_time);
     }
+
+    /// Seeking registers have to read their value before adding to it, so writes also take the
+    /// read time.
+    #[must_use]
+    pub fn time_to_write(&self) -> u32 {
+        if self.seeking {
+            self.write_time + self.read_time
+        } else {
+            self.write_time
+        }
+    }
 }
 
 impl Default for Register {
@@ -498,17 +1204,39 @@ impl Display for Register {
             }
         }
 
+        if self.seeking {
+            write!(f, " (seeking)")?;
+        }
+
         if let Some(array) = self.indexes_array {
             write!(f, " → {}", name_of_register(array).unwrap())?;
         }
 
+        if let Some(channel) = self.channel {
+            if let Some(value) = channel.outgoing {
+                write!(f, " (sending {value})")?;
+            }
+
+            if let Some(value) = channel.incoming {
+                write!(f, " (received {value})")?;
+            }
+        }
+
         if self.block_time > 0 {
             write!(
                 f,
-                "\n(waiting for {} tick{})",
+                "\n(waiting for {} tick{}",
                 self.block_time,
                 if self.block_time == 1 { "" } else { "s" }
             )?;
+
+            if let Some(reason) = self.block_reason {
+                write!(f, ": {reason}")?;
+            }
+
+            write!(f, ")")?;
+        } else if let Some(reason) = self.block_reason {
+            write!(f, "\n({reason})")?;
         }
 
         Ok(())
@@ -586,6 +1314,16 @@ impl RegisterValues {
         }
     }
 
+    /// The position of the current value in [`RegisterValues::all_values`], assuming the index is
+    /// in bounds.
+    #[must_use]
+    pub fn element_index(&self) -> usize {
+        match self {
+            RegisterValues::Scalar(_) => 0,
+            RegisterValues::Vector { index, offset, .. } => index.saturating_sub(*offset) as usize,
+        }
+    }
+
     #[must_use]
     pub fn all_values(&self) -> &[DigitInteger] {
         match self {
@@ -634,12 +1372,13 @@ impl RegisterValues {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RegisterAccessError {
     IndexTooBig { got: Integer, maximum: Integer },
     IndexTooSmall { got: Integer, minimum: Integer },
     NoSuchRegister { got: u32 },
     InvalidAssignment { error: AssignIntegerError },
+    ChannelEmpty,
 }
 
 pub const NUM_REGISTERS: usize = 26;
@@ -709,3 +1448,443 @@ impl<T> DerefMut for RegisterMap<T> {
         &mut self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{
+        argument::{Argument, NumberSource},
+        instruction::InstructionKind,
+        source::SourceLocation,
+    };
+
+    fn load(machine: &str) -> Computer {
+        Computer::load_from(machine).unwrap()
+    }
+
+    /// Runs a program until it's interrupted, returning the interrupt.
+    fn run(computer: &mut Computer, source_code: &str) -> InstructionEvaluationInterrupt {
+        let program = Program::assemble_from("test".into(), source_code, computer).unwrap();
+
+        for _ in 0..10_000 {
+            computer.step_cycle(&program);
+
+            if let Some(interrupt) = computer.interrupt {
+                return interrupt;
+            }
+        }
+
+        panic!("the program didn't finish");
+    }
+
+    fn value(computer: &Computer, name: char) -> Integer {
+        let register = computer.registers.get(register_with_name(name).unwrap());
+        register.unwrap().value().unwrap().get()
+    }
+
+    fn register_mut(computer: &mut Computer, name: char) -> &mut Register {
+        let index = register_with_name(name).unwrap();
+        computer.registers.get_mut(index).unwrap()
+    }
+
+    #[test]
+    fn seeking_registers_add_what_is_written() {
+        let mut computer = load(
+            "digits = 2
+            [registers.S]
+            seeking = true
+            read_time = 1
+            write_time = 2
+            [registers.X]",
+        );
+
+        let interrupt = run(&mut computer, "SET S 5\nSET S 3\nSUB 0 10 S\nSET X S");
+
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::ProgramComplete
+        ));
+        assert_eq!(value(&computer, 'S'), -2);
+        assert_eq!(value(&computer, 'X'), -2);
+        assert_eq!(register_mut(&mut computer, 'S').time_to_write(), 3);
+    }
+
+    #[test]
+    fn seeking_registers_bring_the_sum_into_range() {
+        let mut computer = load("digits = 1\n[registers.S]\nseeking = true");
+        let interrupt = run(&mut computer, "SET S 90\nSET S 10");
+
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::RegisterError {
+                error: RegisterAccessError::InvalidAssignment {
+                    error: AssignIntegerError::ValueTooBig { got: 100, .. },
+                },
+                ..
+            }
+        ));
+        assert_eq!(value(&computer, 'S'), 90);
+
+        for (mode, expected) in [("wrap", -99), ("saturate", 99)] {
+            let mut computer = load(&format!(
+                "digits = 1\noverflow = \"{mode}\"\n[registers.S]\nseeking = true"
+            ));
+            run(&mut computer, "SET S 90\nSET S 10");
+
+            assert_eq!(value(&computer, 'S'), expected, "overflow = \"{mode}\"");
+        }
+    }
+
+    #[test]
+    fn cache_misses_block_until_the_line_is_loaded() {
+        let mut register = Register {
+            block_condition: Some(BlockCondition::Cache {
+                line_length: 10,
+                lines: 2,
+                miss_time: 5,
+            }),
+            ..Register::DEFAULT
+        };
+
+        // Each move is checked against the lines used most recently, after the ones before it
+        for (to, miss) in [
+            (3, true),
+            (7, false),
+            (15, true),
+            (4, false),
+            (25, true),
+            (12, true),
+        ] {
+            register.block_time = 0;
+            register.index_moved(0, to, 0);
+
+            match miss {
+                true => assert_eq!(register.block_time, 5, "moving to {to} should miss"),
+                false => assert_eq!(register.block_time, 0, "moving to {to} should hit"),
+            }
+        }
+
+        assert_eq!(register.block_reason, Some(BlockReason::CacheMiss));
+    }
+
+    #[test]
+    fn cache_lines_start_at_the_offset() {
+        let mut register = Register {
+            block_condition: Some(BlockCondition::Cache {
+                line_length: 4,
+                lines: 1,
+                miss_time: 2,
+            }),
+            ..Register::DEFAULT
+        };
+
+        register.index_moved(0, -10, -10);
+        register.block_time = 0;
+        register.index_moved(-10, -7, -10);
+
+        assert_eq!(register.block_time, 0);
+    }
+
+    #[test]
+    fn writing_back_blocks_for_the_latency() {
+        let mut computer = load(
+            "digits = 2
+            [registers.X]
+            write_time = 1
+            [registers.X.block_condition]
+            kind = \"write_back\"
+            latency = 3
+            [registers.Y]",
+        );
+        let unblocked = computer.clone();
+
+        let interrupt = run(&mut computer, "SET X 1\nSET Y X");
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::ProgramComplete
+        ));
+
+        let mut computer = unblocked;
+        let x = register_with_name('X').unwrap();
+        computer.registers.write(x, 1).unwrap();
+
+        let register = register_mut(&mut computer, 'X');
+        assert_eq!(register.block_time, 3);
+        assert_eq!(register.block_reason, Some(BlockReason::WriteBack));
+
+        for _ in 0..3 {
+            register.end_of_tick();
+        }
+
+        assert_eq!(register.block_time, 0);
+        assert_eq!(register.block_reason, None);
+    }
+
+    #[test]
+    fn contention_blocks_after_too_many_accesses_in_a_tick() {
+        let mut register = Register {
+            block_condition: Some(BlockCondition::Contention {
+                accesses: 2,
+                cooldown: 4,
+            }),
+            ..Register::DEFAULT
+        };
+
+        assert!(!register.record_access());
+        register.end_of_tick();
+        assert!(!register.record_access());
+        assert!(register.record_access());
+
+        assert_eq!(register.block_time, 4);
+        assert_eq!(register.block_reason, Some(BlockReason::Contention));
+    }
+
+    #[test]
+    fn reads_are_only_recorded_on_machines_with_contention() {
+        let contention = "digits = 2
+            [registers.X]
+            [registers.X.block_condition]
+            kind = \"contention\"
+            accesses = 1
+            cooldown = 4";
+
+        assert!(load(contention).registers.has_contention);
+        assert!(!load("digits = 2\n[registers.X]").registers.has_contention);
+
+        let registers = RegisterSet::new_empty().with_register(
+            'X',
+            Register {
+                block_condition: Some(BlockCondition::Contention {
+                    accesses: 1,
+                    cooldown: 4,
+                }),
+                ..Register::DEFAULT
+            },
+        );
+        assert!(registers.has_contention);
+
+        // Replacing the only register with contention leaves none
+        let registers = registers.with_register('X', Register::DEFAULT);
+        assert!(!registers.has_contention);
+    }
+
+    #[test]
+    fn block_conditions_add_to_the_runtime() {
+        let source_code = "SET X 1\nSET X 2\nSET X 3";
+        let machine = "digits = 2
+            [registers.X]
+            [registers.X.block_condition]
+            kind = \"write_back\"
+            latency = 4";
+
+        let mut free = load("digits = 2\n[registers.X]");
+        run(&mut free, source_code);
+
+        let mut blocked = load(machine);
+        run(&mut blocked, source_code);
+
+        // Each write takes a tick of the latency, and the writes after it wait for the rest
+        assert_eq!(blocked.runtime, free.runtime + 2 * 2);
+    }
+
+    #[test]
+    fn calls_return_to_the_instruction_after_them() {
+        let mut computer = load("digits = 2\n[registers.X]\n[registers.Y]");
+        let interrupt = run(
+            &mut computer,
+            "CAL F\nADD X 1 X\nEND\nLBL F\nCAL G\nADD X 10 X\nRET\nLBL G\nSET Y 1\nRET",
+        );
+
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::ProgramComplete
+        ));
+        assert_eq!(value(&computer, 'X'), 11);
+        assert_eq!(value(&computer, 'Y'), 1);
+        assert!(computer.return_stack.addresses.is_empty());
+    }
+
+    #[test]
+    fn the_return_stack_overflows_and_underflows() {
+        let mut computer = load("digits = 2\nreturn_stack_depth = 3\n[registers.X]");
+        let interrupt = run(&mut computer, "LBL F\nADD X 1 X\nCAL F");
+
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::ReturnStackOverflow
+        ));
+        assert_eq!(value(&computer, 'X'), 4);
+        assert_eq!(computer.return_stack.addresses, [2, 2, 2]);
+
+        let mut computer = load("digits = 2\n[registers.X]");
+        let interrupt = run(&mut computer, "CAL F\nRET\nLBL F\nRET");
+
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::ReturnStackUnderflow
+        ));
+    }
+
+    #[test]
+    fn faults_jump_to_their_handler() {
+        let mut computer = load("digits = 2\nfault_register = \"Z\"\n[registers.X]\n[registers.Z]");
+        let interrupt = run(
+            &mut computer,
+            "HDL 4 DIVIDED\nDIV 1 0 X\nSET X 1\nLBL DIVIDED\nADD X 2 X",
+        );
+
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::ProgramComplete
+        ));
+        assert_eq!(value(&computer, 'X'), 2);
+        assert_eq!(value(&computer, 'Z'), 4);
+    }
+
+    #[test]
+    fn handled_faults_cost_as_much_as_the_instruction() {
+        let source_code = |divisor| format!("HDL 4 DIVIDED\nDIV 1 {divisor} X\nLBL DIVIDED");
+
+        let mut faulted = load("digits = 2\n[registers.X]");
+        run(&mut faulted, &source_code(0));
+
+        let mut divided = load("digits = 2\n[registers.X]");
+        run(&mut divided, &source_code(1));
+
+        assert_eq!(faulted.runtime, divided.runtime);
+        assert_eq!(faulted.energy_used, divided.energy_used);
+    }
+
+    #[test]
+    fn faults_without_a_handler_halt() {
+        // Handling code 2 doesn't handle code 3, but code 0 handles everything
+        let mut computer = load("digits = 1\n[registers.X]");
+        let interrupt = run(&mut computer, "HDL 2 END\nSET X -99\nSUB X 1 X\nLBL END");
+
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::RegisterError {
+                error: RegisterAccessError::InvalidAssignment {
+                    error: AssignIntegerError::ValueTooSmall { .. },
+                },
+                ..
+            }
+        ));
+
+        let mut computer = load("digits = 1\n[registers.X]");
+        let interrupt = run(
+            &mut computer,
+            "HDL 0 END\nSET X -99\nSUB X 1 X\nSET X 1\nLBL END",
+        );
+
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::ProgramComplete
+        ));
+        assert_eq!(value(&computer, 'X'), -99);
+
+        // The assembler rejects unknown codes, so the instruction is built by hand
+        let mut computer = load("digits = 2\n[registers.X]");
+        let program = Program::new_empty("test".into()).instruction(Instruction {
+            kind: InstructionKind::Handle,
+            location: SourceLocation::default(),
+            arguments: [
+                Argument::Number(NumberSource::Constant(9)),
+                Argument::Instruction(0),
+                Argument::Empty,
+            ],
+        });
+
+        computer.step_cycle(&program);
+
+        assert!(matches!(
+            computer.interrupt,
+            Some(InstructionEvaluationInterrupt::NoSuchFaultClass { got: 9 })
+        ));
+    }
+
+    #[test]
+    fn unread_channels_are_waited_on() {
+        let mut computer = load("digits = 2\n[registers.C]\nchannel = true\n[registers.X]");
+        let program = Program::assemble_from("test".into(), "SET X C", &computer).unwrap();
+
+        for _ in 0..10 {
+            computer.step_cycle(&program);
+        }
+
+        let c = register_with_name('C').unwrap();
+        assert!(computer.interrupt.is_none());
+        assert_eq!(computer.waiting_for_channel, Some(c));
+        assert_eq!(
+            register_mut(&mut computer, 'C').block_reason,
+            Some(BlockReason::Channel)
+        );
+    }
+
+    #[test]
+    fn channel_waits_are_traced_and_profiled_as_blocked() {
+        let mut computer = load("digits = 2\n[registers.C]\nchannel = true\n[registers.X]");
+        let program = Program::assemble_from("test".into(), "SET X C", &computer).unwrap();
+        computer.profile = Some(Box::default());
+        computer.trace = Some(Box::default());
+
+        for _ in 0..3 {
+            computer.step_cycle(&program);
+        }
+
+        let c = register_with_name('C').unwrap();
+        let trace = computer.trace.as_ref().unwrap();
+        let waits = (trace.events.iter())
+            .filter(|event| {
+                **event
+                    == TraceEvent::Blocked {
+                        register: c,
+                        reason: BlockReason::Channel,
+                        time: 1,
+                    }
+            })
+            .count();
+
+        assert_eq!(waits, 3);
+        assert_eq!(
+            computer.profile.as_ref().unwrap().instructions[0].blocked_ticks,
+            3
+        );
+
+        // Once a value arrives, the wait is over and the instruction runs as normal
+        register_mut(&mut computer, 'C')
+            .channel
+            .as_mut()
+            .unwrap()
+            .incoming = Some(7);
+        computer.step_cycle(&program);
+
+        assert_eq!(value(&computer, 'X'), 7);
+        assert_eq!(computer.waiting_for_channel, None);
+        assert_eq!(register_mut(&mut computer, 'C').block_reason, None);
+    }
+
+    #[test]
+    fn faulting_instructions_leave_channel_values_for_their_handler() {
+        let mut computer = load(
+            "digits = 2\nfault_register = \"Z\"\n[registers.C]\nchannel = true\n[registers.X]\n\
+             [registers.Z]",
+        );
+        register_mut(&mut computer, 'C')
+            .channel
+            .as_mut()
+            .unwrap()
+            .incoming = Some(6);
+
+        let interrupt = run(
+            &mut computer,
+            "HDL 4 DIVIDED\nDIV C 0 X\nLBL DIVIDED\nSET X C",
+        );
+
+        assert!(matches!(
+            interrupt,
+            InstructionEvaluationInterrupt::ProgramComplete
+        ));
+        assert_eq!(value(&computer, 'X'), 6);
+    }
+}
@@ -0,0 +1,844 @@
+use std::{
+    fmt::{Display, Write},
+    fs,
+    iter::Peekable,
+};
+
+use super::{
+    argument::Argument,
+    computer::{self, Computer, RegisterSet},
+    definition::DefinitionError,
+    history::History,
+    integer::Integer,
+    program::{ArgumentIntermediate, ParseArgumentError, Program},
+};
+
+/// Breakpoints and watchpoints for [`interactively_run`](super::interactively_run).
+#[derive(Clone, Debug, Default)]
+pub struct Debugger {
+    /// Deleted stops are kept as `None` so that the numbers of the others stay the same
+    stops: Vec<Option<Stop>>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Stop {
+    Breakpoint {
+        instruction: u32,
+        /// A description of where the instruction is
+        location: String,
+        label: Option<String>,
+        condition: Option<Argument>,
+    },
+    Watchpoint {
+        register: u32,
+        element: Option<Integer>,
+        previous_value: Option<Integer>,
+    },
+}
+
+impl Debugger {
+    pub const HELP: &'static str = "\
+Commands:
+    <n>                         Run for n ticks (1 if not given)
+    e, end, c, continue         Run until the program halts or a stop is hit
+    <n> delay <time> [unit]     Run for n ticks, waiting between each one
+    b, break <line|label> [if <condition>]
+                                Stop before running the instruction at a line or label
+    w, watch <register>         Stop when a register changes (e.g. X, D, or D[42])
+    l, list                     List breakpoints and watchpoints
+    d, delete <number>          Delete a breakpoint or watchpoint
+    stack                       Show the calls which haven't returned yet
+    back [n]                    Undo n ticks (1 if not given)
+    back cycle|instruction [n]  Undo n cycles or instructions
+    rewind <runtime>            Return to the start of a tick
+    save <file>                 Save the state of the machine to a file
+    load <file>                 Load a state saved with \"save\", clearing the history
+    h, help                     Show this message";
+
+    /// Runs a debugger command, returning `false` if `command` isn't one.
+    pub fn run_command<'a>(
+        &mut self,
+        command: &str,
+        arguments: impl Iterator<Item = &'a str>,
+        computer: &mut Computer,
+        history: &mut History,
+        program: &Program,
+    ) -> bool {
+        let mut arguments = arguments.peekable();
+
+        let result = match command {
+            "b" | "break" => self.add_breakpoint(&mut arguments, computer, program),
+            "w" | "watch" => self.add_watchpoint(&mut arguments, computer),
+            "back" => self.step_back(&mut arguments, computer, history, program),
+            "rewind" => self.rewind(&mut arguments, computer, history, program),
+            "save" => Self::save_state(&mut arguments, computer),
+            "load" => self.load_state(&mut arguments, computer, history, program),
+            "l" | "list" => {
+                self.list();
+                Ok(())
+            }
+            "d" | "delete" => self.delete(&mut arguments),
+            "stack" => {
+                match call_stack(computer, program) {
+                    Some(stack) => println!("{stack}"),
+                    None => println!("No calls waiting to return"),
+                }
+                Ok(())
+            }
+            "h" | "help" => {
+                println!("{}", Self::HELP);
+                Ok(())
+            }
+            _ => return false,
+        };
+
+        match result {
+            Ok(()) => {
+                if let Some(extra) = arguments.next() {
+                    println!("Ignored unexpected argument \"{extra}\"");
+                }
+            }
+            Err(error) => println!("{error}"),
+        }
+
+        true
+    }
+
+    fn add_breakpoint<'a>(
+        &mut self,
+        arguments: &mut Peekable<impl Iterator<Item = &'a str>>,
+        computer: &Computer,
+        program: &Program,
+    ) -> Result<(), DebuggerError> {
+        let target = arguments.next().ok_or(DebuggerError::MissingArgument)?;
+
+        let (instruction, label) = if let Ok(line) = target.parse::<u32>() {
+            let instruction = (program.instructions.iter())
+                .position(|instruction| {
+                    instruction.location.is_in_main_file() && instruction.location.line >= line
+                })
+                .ok_or(DebuggerError::NoInstructionAfterLine(line))?;
+
+            (instruction as u32, None)
+        } else {
+            let &instruction = (program.labels.get(target))
+                .ok_or_else(|| DebuggerError::NoSuchLabel(target.to_owned()))?;
+
+            (instruction, Some(target.to_owned()))
+        };
+
+        let Some(location) = program
+            .instructions
+            .get(instruction as usize)
+            .map(|instruction| program.describe_location(instruction.location))
+        else {
+            return Err(DebuggerError::LabelAtEnd(target.to_owned()));
+        };
+
+        let condition = match arguments.next() {
+            Some("if") => Some(parse_condition(arguments, computer, program)?),
+            Some(other) => return Err(DebuggerError::UnexpectedArgument(other.to_owned())),
+            None => None,
+        };
+
+        self.push(Stop::Breakpoint {
+            instruction,
+            location,
+            label,
+            condition,
+        });
+
+        Ok(())
+    }
+
+    fn add_watchpoint<'a>(
+        &mut self,
+        arguments: &mut Peekable<impl Iterator<Item = &'a str>>,
+        computer: &Computer,
+    ) -> Result<(), DebuggerError> {
+        let target = arguments.next().ok_or(DebuggerError::MissingArgument)?;
+
+        let (name, element) = match target.split_once('[') {
+            Some((name, element)) => {
+                let element = (element.strip_suffix(']'))
+                    .and_then(|element| element.parse::<Integer>().ok())
+                    .ok_or_else(|| DebuggerError::InvalidRegister(target.to_owned()))?;
+
+                (name, Some(element))
+            }
+            None => (target, None),
+        };
+
+        let mut characters = name.chars();
+
+        let register = match (characters.next(), characters.next()) {
+            (Some(name), None) => computer::register_with_name(name),
+            _ => None,
+        }
+        .filter(|&register| computer.registers.get(register).is_some())
+        .ok_or_else(|| DebuggerError::InvalidRegister(target.to_owned()))?;
+
+        self.push(Stop::Watchpoint {
+            register,
+            element,
+            previous_value: watched_value(&computer.registers, register, element),
+        });
+
+        Ok(())
+    }
+
+    fn delete<'a>(
+        &mut self,
+        arguments: &mut Peekable<impl Iterator<Item = &'a str>>,
+    ) -> Result<(), DebuggerError> {
+        let number = arguments.next().ok_or(DebuggerError::MissingArgument)?;
+
+        let stop = (number.parse::<usize>().ok())
+            .and_then(|number| self.stops.get_mut(number.checked_sub(1)?))
+            .filter(|stop| stop.is_some())
+            .ok_or_else(|| DebuggerError::NoSuchStop(number.to_owned()))?;
+
+        *stop = None;
+
+        Ok(())
+    }
+
+    fn step_back<'a>(
+        &mut self,
+        arguments: &mut Peekable<impl Iterator<Item = &'a str>>,
+        computer: &mut Computer,
+        history: &mut History,
+        program: &Program,
+    ) -> Result<(), DebuggerError> {
+        let unit = match arguments.peek() {
+            Some(&("c" | "cycle" | "cycles")) => Some(StepUnit::Cycle),
+            Some(&("i" | "instruction" | "instructions")) => Some(StepUnit::Instruction),
+            Some(&("t" | "tick" | "ticks")) => Some(StepUnit::Tick),
+            _ => None,
+        };
+
+        if unit.is_some() {
+            arguments.next();
+        }
+
+        let count = match arguments.next() {
+            Some(count) => count
+                .parse::<u64>()
+                .map_err(|_| DebuggerError::UnexpectedArgument(count.to_owned()))?,
+            None => 1,
+        };
+
+        for _ in 0..count {
+            let undone = match unit.unwrap_or(StepUnit::Tick) {
+                StepUnit::Cycle => history.undo_cycle(computer, program).is_some(),
+                StepUnit::Instruction => history.undo_instruction(computer, program),
+                StepUnit::Tick => history.undo_tick(computer, program),
+            };
+
+            if !undone {
+                if history.earliest_cycle() == 0 {
+                    println!("Reached the start of the program");
+                } else {
+                    println!(
+                        "Reached runtime {}, the earliest which is still recorded",
+                        history.earliest_runtime(),
+                    );
+                }
+                break;
+            }
+        }
+
+        self.print_moved("Rewound to", computer, program);
+
+        Ok(())
+    }
+
+    fn rewind<'a>(
+        &mut self,
+        arguments: &mut Peekable<impl Iterator<Item = &'a str>>,
+        computer: &mut Computer,
+        history: &mut History,
+        program: &Program,
+    ) -> Result<(), DebuggerError> {
+        let runtime = arguments.next().ok_or(DebuggerError::MissingArgument)?;
+        let runtime = runtime
+            .parse::<u64>()
+            .map_err(|_| DebuggerError::UnexpectedArgument(runtime.to_owned()))?;
+
+        if runtime > computer.runtime {
+            return Err(DebuggerError::RuntimeInFuture(runtime));
+        }
+
+        if !history.rewind_to_runtime(runtime, computer, program) {
+            return Err(DebuggerError::RuntimeNotRecorded(
+                history.earliest_runtime(),
+            ));
+        }
+
+        self.print_moved("Rewound to", computer, program);
+
+        Ok(())
+    }
+
+    fn save_state<'a>(
+        arguments: &mut Peekable<impl Iterator<Item = &'a str>>,
+        computer: &Computer,
+    ) -> Result<(), DebuggerError> {
+        let path = arguments.next().ok_or(DebuggerError::MissingArgument)?;
+
+        fs::write(path, computer.save_state())
+            .map_err(|error| DebuggerError::File(format!("{path}: {error}")))?;
+
+        println!("Saved the state to {path}");
+
+        Ok(())
+    }
+
+    fn load_state<'a>(
+        &mut self,
+        arguments: &mut Peekable<impl Iterator<Item = &'a str>>,
+        computer: &mut Computer,
+        history: &mut History,
+        program: &Program,
+    ) -> Result<(), DebuggerError> {
+        let path = arguments.next().ok_or(DebuggerError::MissingArgument)?;
+
+        let source = fs::read_to_string(path)
+            .map_err(|error| DebuggerError::File(format!("{path}: {error}")))?;
+
+        computer
+            .load_state(&source, program)
+            .map_err(DebuggerError::InvalidState)?;
+
+        // The history can't step back past a state which came from elsewhere
+        *history = History::new(computer);
+
+        self.print_moved("Loaded a state at", computer, program);
+
+        Ok(())
+    }
+
+    /// Prints the state after stepping backwards or loading a state, and makes sure that
+    /// watchpoints don't see the change as a write.
+    fn print_moved(&mut self, action: &str, computer: &Computer, program: &Program) {
+        for stop in self.stops.iter_mut().flatten() {
+            if let Stop::Watchpoint {
+                register,
+                element,
+                previous_value,
+            } = stop
+            {
+                *previous_value = watched_value(&computer.registers, *register, *element);
+            }
+        }
+
+        match program.instructions.get(computer.instruction as usize) {
+            Some(instruction) => print!(
+                "{action} {location} ({instruction})",
+                location = program.describe_location(instruction.location),
+            ),
+            None => print!("{action} instruction {}", computer.instruction),
+        }
+
+        println!(
+            " at runtime {runtime}:\n{registers}",
+            runtime = computer.runtime,
+            registers = computer.registers,
+        );
+
+        if let Some(stack) = call_stack(computer, program) {
+            println!("{stack}");
+        }
+    }
+
+    fn push(&mut self, stop: Stop) {
+        println!("{}: {stop}", self.stops.len() + 1);
+
+        self.stops.push(Some(stop));
+    }
+
+    pub fn list(&self) {
+        let mut empty = true;
+
+        for (i, stop) in self.stops.iter().enumerate() {
+            if let Some(stop) = stop {
+                println!("{}: {stop}", i + 1);
+                empty = false;
+            }
+        }
+
+        if empty {
+            println!("No breakpoints or watchpoints");
+        }
+    }
+
+    /// Should be called after each step of the computer. Prints and returns whether or not any
+    /// stops were hit. `executed` is whether or not the computer ran an instruction in the step.
+    pub fn check(&mut self, computer: &Computer, executed: bool) -> bool {
+        let mut hit = false;
+
+        for (i, stop) in self.stops.iter_mut().enumerate() {
+            let Some(stop) = stop else {
+                continue;
+            };
+
+            match stop {
+                Stop::Breakpoint {
+                    instruction,
+                    condition,
+                    ..
+                } => {
+                    if executed
+                        && computer.block_time == 0
+                        && computer.interrupt.is_none()
+                        && computer.instruction == *instruction
+                        && condition.is_none_or(|condition| {
+                            condition_holds(&condition, &computer.registers)
+                        })
+                    {
+                        println!("Hit {}: {stop}", i + 1);
+                        hit = true;
+                    }
+                }
+                Stop::Watchpoint {
+                    register,
+                    element,
+                    previous_value,
+                } => {
+                    let value = watched_value(&computer.registers, *register, *element);
+
+                    if value != *previous_value {
+                        println!(
+                            "Hit {}: {} changed from {} to {}",
+                            i + 1,
+                            WatchTarget(*register, *element),
+                            OptionalValue(*previous_value),
+                            OptionalValue(value),
+                        );
+
+                        *previous_value = value;
+                        hit = true;
+                    }
+                }
+            }
+        }
+
+        hit
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum StepUnit {
+    Cycle,
+    Instruction,
+    Tick,
+}
+
+/// Lists where each call which hasn't returned yet was made from, with the most recent first.
+/// Returns `None` if there aren't any.
+#[must_use]
+pub fn call_stack(computer: &Computer, program: &Program) -> Option<String> {
+    if computer.return_stack.addresses.is_empty() {
+        return None;
+    }
+
+    let mut stack = String::from("Call stack:");
+
+    for &address in computer.return_stack.addresses.iter().rev() {
+        let call = address.checked_sub(1);
+
+        match call.and_then(|call| program.instructions.get(call as usize)) {
+            Some(instruction) => write!(
+                stack,
+                "\n    {location} ({instruction})",
+                location = program.describe_location(instruction.location),
+            ),
+            None => write!(stack, "\n    return address {address}"),
+        }
+        .unwrap();
+    }
+
+    Some(stack)
+}
+
+fn parse_condition<'a>(
+    arguments: &mut Peekable<impl Iterator<Item = &'a str>>,
+    computer: &Computer,
+    program: &Program,
+) -> Result<Argument, DebuggerError> {
+    let condition = ArgumentIntermediate::pop_from_tokens(arguments)
+        .and_then(|condition| condition.as_value(computer.maximum_digits, &program.constants))
+        .map_err(DebuggerError::InvalidCondition)?;
+
+    for source in condition.number_sources() {
+        if let Some(register) = source.as_register()
+            && computer.registers.get(register).is_none()
+        {
+            return Err(DebuggerError::InvalidRegister(source.to_string()));
+        }
+    }
+
+    Ok(condition)
+}
+
+fn condition_holds(condition: &Argument, registers: &RegisterSet) -> bool {
+    let value = match condition {
+        Argument::Number(source) => source.value(registers).map(|(value, _)| value),
+        Argument::Comparison(comparison) => comparison.evaluate(registers).map(|(value, _)| value),
+        Argument::Instruction(_) | Argument::Empty => Ok(1),
+    };
+
+    // Conditions which can't be evaluated (e.g. reading out of bounds) never stop the program
+    value.is_ok_and(|value| value != 0)
+}
+
+fn watched_value(
+    registers: &RegisterSet,
+    register: u32,
+    element: Option<Integer>,
+) -> Option<Integer> {
+    let register = registers.get(register)?;
+
+    match element {
+        Some(element) => {
+            let index = usize::try_from(element.checked_sub(register.offset())?).ok()?;
+
+            register.all_values().get(index).map(|value| value.get())
+        }
+        None => register.value().ok().map(|value| value.get()),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum DebuggerError {
+    MissingArgument,
+    UnexpectedArgument(String),
+    NoInstructionAfterLine(u32),
+    NoSuchLabel(String),
+    LabelAtEnd(String),
+    InvalidRegister(String),
+    InvalidCondition(ParseArgumentError),
+    NoSuchStop(String),
+    RuntimeInFuture(u64),
+    RuntimeNotRecorded(u64),
+    File(String),
+    InvalidState(Vec<DefinitionError>),
+}
+
+impl Display for DebuggerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DebuggerError::MissingArgument => write!(f, "Missing argument (see \"help\")"),
+            DebuggerError::UnexpectedArgument(argument) => {
+                write!(f, "Unexpected argument \"{argument}\" (see \"help\")")
+            }
+            DebuggerError::NoInstructionAfterLine(line) => {
+                write!(f, "No instructions on or after line {line}")
+            }
+            DebuggerError::NoSuchLabel(label) => write!(f, "No such label \"{label}\""),
+            DebuggerError::LabelAtEnd(label) => {
+                write!(f, "\"{label}\" is at the end of the program")
+            }
+            DebuggerError::InvalidRegister(register) => {
+                write!(f, "\"{register}\" is not a register on this machine")
+            }
+            DebuggerError::InvalidCondition(ParseArgumentError::OutOfTokens) => {
+                write!(f, "Missing condition after \"if\"")
+            }
+            DebuggerError::InvalidCondition(_) => {
+                write!(
+                    f,
+                    "Invalid condition, expected a register, constant, or comparison"
+                )
+            }
+            DebuggerError::NoSuchStop(number) => {
+                write!(f, "No breakpoint or watchpoint numbered \"{number}\"")
+            }
+            DebuggerError::RuntimeInFuture(runtime) => {
+                write!(f, "Runtime {runtime} hasn't been reached yet")
+            }
+            DebuggerError::RuntimeNotRecorded(earliest) => {
+                write!(f, "Runtimes before {earliest} are no longer recorded")
+            }
+            DebuggerError::File(error) => write!(f, "{error}"),
+            DebuggerError::InvalidState(errors) => {
+                write!(f, "Invalid state:")?;
+
+                for error in errors {
+                    write!(f, "\n    {error}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for Stop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stop::Breakpoint {
+                location,
+                label,
+                condition,
+                ..
+            } => {
+                write!(f, "break at {location}")?;
+
+                if let Some(label) = label {
+                    write!(f, " ({label})")?;
+                }
+
+                if let Some(condition) = condition {
+                    write!(f, " if {condition}")?;
+                }
+
+                Ok(())
+            }
+            Stop::Watchpoint {
+                register,
+                element,
+                previous_value,
+            } => write!(
+                f,
+                "watch {} (currently {})",
+                WatchTarget(*register, *element),
+                OptionalValue(*previous_value),
+            ),
+        }
+    }
+}
+
+struct WatchTarget(u32, Option<Integer>);
+
+impl Display for WatchTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", computer::name_of_register(self.0).unwrap())?;
+
+        if let Some(element) = self.1 {
+            write!(f, "[{element}]")?;
+        }
+
+        Ok(())
+    }
+}
+
+struct OptionalValue(Option<Integer>);
+
+impl Display for OptionalValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(value) => write!(f, "{value}"),
+            None => write!(f, "out of bounds"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_MACHINE_WITH_H;
+
+    const SOURCE: &str = "\
+SET X 1
+LBL LOOP
+ADD X 1 X
+LJP X < 5 LOOP
+";
+
+    fn setup() -> (Debugger, Computer, Program) {
+        let computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        let program = Program::assemble_from("test".into(), SOURCE, &computer).unwrap();
+
+        (Debugger::default(), computer, program)
+    }
+
+    fn arguments(text: &str) -> Peekable<std::str::SplitWhitespace<'_>> {
+        text.split_whitespace().peekable()
+    }
+
+    fn set(computer: &mut Computer, name: char, element: Option<usize>, value: Integer) {
+        let register = computer::register_with_name(name).unwrap();
+        let register = computer.registers.get_mut(register).unwrap();
+
+        match element {
+            Some(element) => register.all_values_mut()[element].try_set(value),
+            None => register.value_mut().unwrap().try_set(value),
+        }
+        .unwrap();
+    }
+
+    fn value_of(computer: &Computer, name: char) -> Integer {
+        watched_value(
+            &computer.registers,
+            computer::register_with_name(name).unwrap(),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn breakpoints_are_added_at_lines_and_labels() {
+        let (mut debugger, computer, program) = setup();
+        let add = program.labels["LOOP"];
+
+        // Lines are counted from 0, as in assembly errors
+        debugger
+            .add_breakpoint(&mut arguments("2"), &computer, &program)
+            .unwrap();
+        debugger
+            .add_breakpoint(&mut arguments("1"), &computer, &program)
+            .unwrap();
+        debugger
+            .add_breakpoint(&mut arguments("LOOP if X > 3"), &computer, &program)
+            .unwrap();
+
+        assert!(matches!(
+            debugger.stops[0],
+            Some(Stop::Breakpoint { instruction, label: None, condition: None, .. }) if instruction == add
+        ));
+        // A line without an instruction stops at the next one
+        assert!(matches!(
+            debugger.stops[1],
+            Some(Stop::Breakpoint { instruction, label: None, .. }) if instruction == add
+        ));
+        assert!(matches!(
+            &debugger.stops[2],
+            Some(Stop::Breakpoint { instruction, label: Some(label), condition: Some(_), .. })
+                if *instruction == add && label == "LOOP"
+        ));
+    }
+
+    #[test]
+    fn invalid_breakpoints_are_rejected() {
+        let (mut debugger, computer, program) = setup();
+
+        let mut add = |text| debugger.add_breakpoint(&mut arguments(text), &computer, &program);
+
+        assert!(matches!(add(""), Err(DebuggerError::MissingArgument)));
+        assert!(matches!(
+            add("99"),
+            Err(DebuggerError::NoInstructionAfterLine(99))
+        ));
+        assert!(matches!(
+            add("END"),
+            Err(DebuggerError::NoSuchLabel(label)) if label == "END"
+        ));
+        assert!(matches!(
+            add("LOOP unless X"),
+            Err(DebuggerError::UnexpectedArgument(argument)) if argument == "unless"
+        ));
+        assert!(matches!(
+            add("LOOP if W > 1"),
+            Err(DebuggerError::InvalidRegister(register)) if register == "W"
+        ));
+        assert!(debugger.stops.is_empty());
+    }
+
+    #[test]
+    fn conditions_hold_when_they_are_not_zero() {
+        let (_, mut computer, program) = setup();
+
+        let holds = |text, computer: &Computer| {
+            let condition = parse_condition(&mut arguments(text), computer, &program).unwrap();
+            condition_holds(&condition, &computer.registers)
+        };
+
+        assert!(!holds("X", &computer));
+        assert!(holds("1", &computer));
+        assert!(!holds("0", &computer));
+        assert!(!holds("X > 3", &computer));
+
+        set(&mut computer, 'X', None, 4);
+
+        assert!(holds("X", &computer));
+        assert!(holds("X > 3", &computer));
+        assert!(!holds("X = 3", &computer));
+    }
+
+    #[test]
+    fn breakpoints_are_hit_when_their_condition_holds() {
+        let (mut debugger, mut computer, program) = setup();
+
+        debugger
+            .add_breakpoint(&mut arguments("LOOP if X > 3"), &computer, &program)
+            .unwrap();
+
+        let mut hits = Vec::new();
+
+        while computer.interrupt.is_none() {
+            let executed = computer.step_cycle(&program);
+
+            if debugger.check(&computer, executed) {
+                hits.push(value_of(&computer, 'X'));
+            }
+        }
+
+        assert_eq!(hits, [4]);
+    }
+
+    #[test]
+    fn watchpoints_track_elements_of_vectors() {
+        let (mut debugger, mut computer, _) = setup();
+
+        debugger
+            .add_watchpoint(&mut arguments("D[42]"), &computer)
+            .unwrap();
+
+        assert!(!debugger.check(&computer, true));
+
+        set(&mut computer, 'D', Some(41), 7);
+        assert!(!debugger.check(&computer, true));
+
+        set(&mut computer, 'D', Some(42), 7);
+        assert!(debugger.check(&computer, true));
+        assert!(matches!(
+            debugger.stops[0],
+            Some(Stop::Watchpoint {
+                element: Some(42),
+                previous_value: Some(7),
+                ..
+            })
+        ));
+
+        // Only changes are hits
+        assert!(!debugger.check(&computer, true));
+
+        for target in ["D[x]", "D[42", "W", "XY"] {
+            assert!(
+                matches!(
+                    debugger.add_watchpoint(&mut arguments(target), &computer),
+                    Err(DebuggerError::InvalidRegister(register)) if register == target
+                ),
+                "{target}"
+            );
+        }
+    }
+
+    #[test]
+    fn deleting_keeps_the_numbers_of_other_stops() {
+        let (mut debugger, computer, program) = setup();
+
+        debugger
+            .add_breakpoint(&mut arguments("LOOP"), &computer, &program)
+            .unwrap();
+        debugger
+            .add_watchpoint(&mut arguments("X"), &computer)
+            .unwrap();
+
+        debugger.delete(&mut arguments("1")).unwrap();
+
+        assert!(debugger.stops[0].is_none());
+        assert!(matches!(debugger.stops[1], Some(Stop::Watchpoint { .. })));
+
+        for number in ["1", "0", "3", "one"] {
+            assert!(
+                matches!(
+                    debugger.delete(&mut arguments(number)),
+                    Err(DebuggerError::NoSuchStop(stop)) if stop == number
+                ),
+                "{number}"
+            );
+        }
+    }
+}
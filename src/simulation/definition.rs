@@ -0,0 +1,449 @@
+use std::fmt::Display;
+
+use toml::{Table, Value};
+
+use super::{computer, snapshot::BLOCK_REASONS};
+
+/// Parses a TOML definition file (e.g. a machine or level).
+pub fn parse(source_code: &str) -> Result<Table, Vec<DefinitionError>> {
+    toml::from_str::<Table>(source_code).map_err(|error| {
+        vec![DefinitionError {
+            entry: String::new(),
+            kind: DefinitionErrorKind::Syntax(error.to_string()),
+        }]
+    })
+}
+
+/// A table in a definition file, which keeps track of the path to it for error reporting.
+pub struct Entry<'a, 'b> {
+    pub path: String,
+    pub table: &'a Table,
+    errors: &'b mut Vec<DefinitionError>,
+}
+
+impl<'a, 'b> Entry<'a, 'b> {
+    pub fn new(path: String, table: &'a Table, errors: &'b mut Vec<DefinitionError>) -> Self {
+        Self {
+            path,
+            table,
+            errors,
+        }
+    }
+
+    pub fn child<'c>(&'c mut self, key: &str, table: &'a Table) -> Entry<'a, 'c> {
+        Entry::new(self.path_of(key), table, self.errors)
+    }
+
+    pub fn path_of(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{key}", self.path)
+        }
+    }
+
+    pub fn error(&mut self, key: &str, kind: DefinitionErrorKind) {
+        self.errors.push(DefinitionError {
+            entry: self.path_of(key),
+            kind,
+        });
+    }
+
+    /// Reports every key which isn't in `keys`.
+    pub fn finish(self, keys: &[&str]) {
+        let mut unexpected = self
+            .table
+            .keys()
+            .filter(|key| !keys.contains(&key.as_str()))
+            .collect::<Vec<_>>();
+        unexpected.sort_unstable();
+
+        for key in unexpected {
+            self.errors.push(DefinitionError {
+                entry: self.path_of(key),
+                kind: DefinitionErrorKind::UnexpectedEntry,
+            });
+        }
+    }
+
+    pub fn required(&mut self, key: &str) -> Option<&'a Value> {
+        let value = self.table.get(key);
+
+        if value.is_none() {
+            self.error(key, DefinitionErrorKind::MissingEntry);
+        }
+
+        value
+    }
+
+    pub fn table(&mut self, key: &str) -> Option<&'a Table> {
+        let value = self.required(key)?;
+        self.as_table(key, value)
+    }
+
+    pub fn optional_table(&mut self, key: &str) -> Option<&'a Table> {
+        let value = self.table.get(key)?;
+        self.as_table(key, value)
+    }
+
+    pub fn as_table(&mut self, key: &str, value: &'a Value) -> Option<&'a Table> {
+        let table = value.as_table();
+
+        if table.is_none() {
+            self.error(
+                key,
+                DefinitionErrorKind::IncorrectType {
+                    expected: "a table",
+                },
+            );
+        }
+
+        table
+    }
+
+    pub fn integer(&mut self, key: &str, minimum: i64, maximum: i64) -> Option<i64> {
+        let value = self.required(key)?;
+        self.as_integer(key, value, minimum, maximum)
+    }
+
+    /// Returns `None` if the entry is invalid, or `Some(None)` if it isn't present.
+    pub fn optional_integer(
+        &mut self,
+        key: &str,
+        minimum: i64,
+        maximum: i64,
+    ) -> Option<Option<i64>> {
+        match self.table.get(key) {
+            Some(value) => self.as_integer(key, value, minimum, maximum).map(Some),
+            None => Some(None),
+        }
+    }
+
+    pub fn as_integer(
+        &mut self,
+        key: &str,
+        value: &Value,
+        minimum: i64,
+        maximum: i64,
+    ) -> Option<i64> {
+        let Some(integer) = value.as_integer() else {
+            self.error(
+                key,
+                DefinitionErrorKind::IncorrectType {
+                    expected: "an integer",
+                },
+            );
+            return None;
+        };
+
+        if integer < minimum || integer > maximum {
+            self.error(
+                key,
+                DefinitionErrorKind::OutOfRange {
+                    got: integer,
+                    minimum,
+                    maximum,
+                },
+            );
+            return None;
+        }
+
+        Some(integer)
+    }
+
+    /// Returns `None` if the entry is invalid, or `Some(None)` if it isn't present.
+    pub fn optional_boolean(&mut self, key: &str) -> Option<Option<bool>> {
+        let Some(value) = self.table.get(key) else {
+            return Some(None);
+        };
+
+        let boolean = value.as_bool();
+
+        if boolean.is_none() {
+            self.error(
+                key,
+                DefinitionErrorKind::IncorrectType {
+                    expected: "true or false",
+                },
+            );
+        }
+
+        boolean.map(Some)
+    }
+
+    pub fn string(&mut self, key: &str) -> Option<&'a str> {
+        let value = self.required(key)?;
+        let string = value.as_str();
+
+        if string.is_none() {
+            self.error(
+                key,
+                DefinitionErrorKind::IncorrectType {
+                    expected: "a string",
+                },
+            );
+        }
+
+        string
+    }
+
+    /// Returns `None` if the entry is invalid, or `Some(None)` if it isn't present.
+    pub fn optional_string(&mut self, key: &str) -> Option<Option<&'a str>> {
+        match self.table.get(key) {
+            Some(_) => self.string(key).map(Some),
+            None => Some(None),
+        }
+    }
+
+    pub fn integer_array(&mut self, key: &str, minimum: i64, maximum: i64) -> Option<Vec<i64>> {
+        let value = self.required(key)?;
+
+        let Some(array) = value.as_array() else {
+            self.error(
+                key,
+                DefinitionErrorKind::IncorrectType {
+                    expected: "an array of integers",
+                },
+            );
+            return None;
+        };
+
+        array
+            .iter()
+            .map(|value| self.as_integer(key, value, minimum, maximum))
+            .collect()
+    }
+
+    /// Returns `None` if the entry is invalid, or `Some(None)` if it isn't present.
+    pub fn optional_integer_array(
+        &mut self,
+        key: &str,
+        minimum: i64,
+        maximum: i64,
+    ) -> Option<Option<Vec<i64>>> {
+        match self.table.get(key) {
+            Some(_) => self.integer_array(key, minimum, maximum).map(Some),
+            None => Some(None),
+        }
+    }
+
+    pub fn register(&mut self, key: &str) -> Option<u32> {
+        let value = self.required(key)?;
+        self.as_register(key, value)
+    }
+
+    /// Returns `None` if the entry is invalid, or `Some(None)` if it isn't present.
+    pub fn optional_register(&mut self, key: &str) -> Option<Option<u32>> {
+        match self.table.get(key) {
+            Some(value) => self.as_register(key, value).map(Some),
+            None => Some(None),
+        }
+    }
+
+    pub fn as_register(&mut self, key: &str, value: &Value) -> Option<u32> {
+        let Some(name) = value.as_str() else {
+            self.error(
+                key,
+                DefinitionErrorKind::IncorrectType {
+                    expected: "a register name",
+                },
+            );
+            return None;
+        };
+
+        let register = register_index(name);
+
+        if register.is_none() {
+            self.error(key, DefinitionErrorKind::NoSuchRegister(name.to_owned()));
+        }
+
+        register
+    }
+}
+
+pub fn register_index(name: &str) -> Option<u32> {
+    let mut characters = name.chars();
+
+    match (characters.next(), characters.next()) {
+        (Some(name), None) => computer::register_with_name(name),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DefinitionError {
+    /// The dotted path to the entry, e.g. `registers.D.length`. Empty for the whole file.
+    pub entry: String,
+    pub kind: DefinitionErrorKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum DefinitionErrorKind {
+    Syntax(String),
+    MissingEntry,
+    UnexpectedEntry,
+    IncorrectType {
+        expected: &'static str,
+    },
+    OutOfRange {
+        got: i64,
+        minimum: i64,
+        maximum: i64,
+    },
+    NoSuchRegister(String),
+    NoSuchInstruction(String),
+    NoSuchBlockCondition(String),
+    NoSuchOverflowMode(String),
+    NoConditionalTime,
+    UndefinedRegister(u32),
+    NotAVector(u32),
+    UnlinkedIndex {
+        array: u32,
+        index: u32,
+    },
+    VectorChannel,
+    VectorSeeking,
+    SeekingChannel,
+    VectorFaultRegister,
+    NoSuchMachine(String),
+    NoSuchSequence(String),
+    NoSuchBlockReason(String),
+    ExpectedOneOf(&'static [&'static str]),
+    TooManyValues {
+        got: usize,
+        length: usize,
+    },
+    WrongNumberOfValues {
+        got: usize,
+        length: usize,
+    },
+    ReturnStackTooDeep {
+        got: usize,
+        depth: u32,
+    },
+    UnsupportedVersion {
+        got: i64,
+        expected: i64,
+    },
+    DifferentMachine {
+        saved: String,
+        current: String,
+    },
+}
+
+impl Display for DefinitionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.entry.is_empty() {
+            write!(f, "Error: {error}", error = self.kind)
+        } else {
+            write!(f, "{entry}: {error}", entry = self.entry, error = self.kind)
+        }
+    }
+}
+
+impl Display for DefinitionErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefinitionErrorKind::Syntax(message) => write!(f, "Invalid syntax: {message}"),
+            DefinitionErrorKind::MissingEntry => write!(f, "Missing entry"),
+            DefinitionErrorKind::UnexpectedEntry => write!(f, "Unexpected entry"),
+            DefinitionErrorKind::IncorrectType { expected } => {
+                write!(f, "Expected {expected}")
+            }
+            DefinitionErrorKind::OutOfRange {
+                got,
+                minimum,
+                maximum,
+            } => write!(
+                f,
+                "{got} is out of range (minimum: {minimum}, maximum: {maximum})"
+            ),
+            DefinitionErrorKind::NoSuchRegister(name) => {
+                write!(
+                    f,
+                    "No such register \"{name}\", must be a letter from A to Z"
+                )
+            }
+            DefinitionErrorKind::NoSuchInstruction(name) => {
+                write!(f, "No such instruction \"{name}\"")
+            }
+            DefinitionErrorKind::NoSuchBlockCondition(kind) => {
+                write!(f, "No such block condition \"{kind}\"")
+            }
+            DefinitionErrorKind::NoSuchOverflowMode(mode) => write!(
+                f,
+                "No such overflow mode \"{mode}\", must be \"trap\", \"wrap\" or \"saturate\""
+            ),
+            DefinitionErrorKind::NoConditionalTime => {
+                write!(f, "This instruction has no conditional time")
+            }
+            DefinitionErrorKind::UndefinedRegister(register) => write!(
+                f,
+                "\"{name}\" register is not defined on this machine",
+                name = computer::name_of_register(*register).unwrap(),
+            ),
+            DefinitionErrorKind::NotAVector(register) => write!(
+                f,
+                "\"{name}\" register must have a length to be indexed",
+                name = computer::name_of_register(*register).unwrap(),
+            ),
+            DefinitionErrorKind::UnlinkedIndex { array, index } => write!(
+                f,
+                "\"{index}\" register must also set indexes_array = \"{array}\"",
+                array = computer::name_of_register(*array).unwrap(),
+                index = computer::name_of_register(*index).unwrap(),
+            ),
+            DefinitionErrorKind::VectorFaultRegister => {
+                write!(f, "The fault register can't have a length")
+            }
+            DefinitionErrorKind::VectorChannel => {
+                write!(f, "Channel registers can't have a length")
+            }
+            DefinitionErrorKind::VectorSeeking => {
+                write!(f, "Seeking registers can't have a length")
+            }
+            DefinitionErrorKind::SeekingChannel => {
+                write!(f, "Channel registers can't be seeking")
+            }
+            DefinitionErrorKind::NoSuchMachine(name) => write!(
+                f,
+                "No such machine \"{name}\", must be \"default\", \"default_with_h\" or a table"
+            ),
+            DefinitionErrorKind::NoSuchSequence(name) => write!(f, "No such sequence \"{name}\""),
+            DefinitionErrorKind::NoSuchBlockReason(name) => {
+                let reasons = BLOCK_REASONS
+                    .iter()
+                    .map(|(reason, _)| format!("\"{reason}\""))
+                    .collect::<Vec<_>>();
+
+                write!(
+                    f,
+                    "No such block reason \"{name}\", must be one of {}",
+                    reasons.join(", ")
+                )
+            }
+            DefinitionErrorKind::ExpectedOneOf(keys) => {
+                write!(f, "Expected exactly one of {}", keys.join(", "))
+            }
+            DefinitionErrorKind::TooManyValues { got, length } => {
+                write!(f, "Got {got} values, but the register only has {length}")
+            }
+            DefinitionErrorKind::WrongNumberOfValues { got, length } => {
+                write!(f, "Got {got} values, but the register has {length}")
+            }
+            DefinitionErrorKind::ReturnStackTooDeep { got, depth } => {
+                write!(
+                    f,
+                    "Got {got} calls, but the return stack only holds {depth}"
+                )
+            }
+            DefinitionErrorKind::UnsupportedVersion { got, expected } => {
+                write!(f, "Unsupported version {got}, expected {expected}")
+            }
+            DefinitionErrorKind::DifferentMachine { saved, current } => write!(
+                f,
+                "Saved from a machine where this is {saved}, but it's {current} on this machine"
+            ),
+        }
+    }
+}
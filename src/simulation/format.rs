@@ -0,0 +1,306 @@
+use std::{collections::HashMap, fmt::Write};
+
+use strum::IntoEnumIterator;
+
+use super::{
+    argument::Argument,
+    instruction::{Instruction, InstructionKind},
+    program::{
+        self, COMMENT_SEPARATOR, FILE_LABEL_SEPARATOR, INCLUDE_PSEUDO_INSTRUCTION,
+        LABEL_PSEUDO_INSTRUCTION, MACRO_PSEUDO_INSTRUCTION, PSEUDO_INSTRUCTIONS, Program,
+    },
+};
+
+/// Comparison operators which are written with more than one character, and the character they
+/// are replaced with.
+pub const COMPARISON_CORRECTIONS: [(&str, &str); 4] =
+    [(">=", "≥"), ("<=", "≤"), ("!=", "≠"), ("/=", "≠")];
+
+/// Turns a program back into source code which assembles to the same instructions. Labels are kept
+/// where possible, and made up for any other instruction which is jumped to.
+#[must_use]
+pub fn disassemble(program: &Program) -> String {
+    let labels = labels_of(program);
+
+    let mut source = String::new();
+
+    for index in 0..=program.instructions.len() as u32 {
+        for label in labels.get(&index).into_iter().flatten() {
+            writeln!(source, "{LABEL_PSEUDO_INSTRUCTION} {label}").unwrap();
+        }
+
+        let Some(instruction) = program.instructions.get(index as usize) else {
+            break;
+        };
+
+        writeln!(source, "{}", instruction_source(instruction, &labels)).unwrap();
+    }
+
+    source
+}
+
+/// Writes an instruction as source code, with jumps to the first label of their target from
+/// [`labels_of`].
+#[must_use]
+pub fn instruction_source(instruction: &Instruction, labels: &HashMap<u32, Vec<String>>) -> String {
+    let mut source = instruction.kind.get_default_properties().name.to_owned();
+
+    for argument in instruction.arguments {
+        match argument {
+            Argument::Empty => (),
+            Argument::Instruction(target) => write!(source, " {}", labels[&target][0]).unwrap(),
+            _ => write!(source, " {argument}").unwrap(),
+        }
+    }
+
+    source
+}
+
+/// The names of the labels to put before each instruction, the first of which is jumped to.
+#[must_use]
+pub fn labels_of(program: &Program) -> HashMap<u32, Vec<String>> {
+    let mut names = (program.labels.iter())
+        .map(|(label, &index)| {
+            // Labels which aren't exported from an included file are named after their file
+            let label = label
+                .rsplit_once(FILE_LABEL_SEPARATOR)
+                .map_or(label.as_str(), |(_, label)| label);
+
+            (label.to_owned(), index)
+        })
+        .filter(|(label, _)| program::is_label_valid(label))
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+
+    let mut labels = HashMap::<u32, Vec<String>>::new();
+    let mut used = Vec::new();
+
+    for (label, index) in names {
+        if !used.contains(&label) {
+            used.push(label.clone());
+            labels.entry(index).or_default().push(label);
+        }
+    }
+
+    let targets = (program.instructions.iter())
+        .flat_map(|instruction| instruction.arguments)
+        .filter_map(|argument| match argument {
+            Argument::Instruction(target) => Some(target),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    for target in targets {
+        if labels.contains_key(&target) {
+            continue;
+        }
+
+        let mut label = format!("LABEL_{target}");
+
+        while used.contains(&label) {
+            label.push('_');
+        }
+
+        used.push(label.clone());
+        labels.insert(target, vec![label]);
+    }
+
+    labels
+}
+
+/// Formats source code in the canonical style, without changing what it assembles to. Operations
+/// are written in uppercase, comparisons use their single character forms, tokens are separated by
+/// single spaces, and comments after code on consecutive lines are lined up.
+#[must_use]
+pub fn format_source(source: &str) -> String {
+    // Macros can have lowercase names which look like operations
+    let macros = (source.lines())
+        .filter_map(|line| {
+            let mut tokens = line.split(COMMENT_SEPARATOR).next()?.split_whitespace();
+
+            tokens
+                .next()
+                .is_some_and(|token| token.eq_ignore_ascii_case(MACRO_PSEUDO_INSTRUCTION))
+                .then(|| tokens.next())
+                .flatten()
+        })
+        .collect::<Vec<_>>();
+
+    let mut lines = Vec::<(String, Option<&str>)>::new();
+
+    for line in source.lines() {
+        let (code, comment) = match line.split_once(COMMENT_SEPARATOR) {
+            Some((code, comment)) => (code, Some(comment.trim_end())),
+            None => (line, None),
+        };
+
+        let code = format_code(code, &macros);
+
+        // Only keep one blank line in a row, and none at the start
+        let is_blank = code.is_empty() && comment.is_none();
+
+        if is_blank
+            && lines
+                .last()
+                .is_none_or(|(code, comment)| code.is_empty() && comment.is_none())
+        {
+            continue;
+        }
+
+        lines.push((code, comment));
+    }
+
+    while lines.last() == Some(&(String::new(), None)) {
+        lines.pop();
+    }
+
+    let mut result = String::new();
+
+    let has_trailing_comment =
+        |(code, comment): &(String, Option<&str>)| !code.is_empty() && comment.is_some();
+
+    for group in lines.chunk_by(|a, b| has_trailing_comment(a) == has_trailing_comment(b)) {
+        let comment_column = (group.iter())
+            .map(|(code, _)| code.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        for (code, comment) in group {
+            result.push_str(code);
+
+            if let Some(comment) = comment {
+                if !code.is_empty() {
+                    let padding = comment_column - code.chars().count() + 1;
+                    result.push_str(&" ".repeat(padding));
+                }
+
+                write!(result, "{COMMENT_SEPARATOR}{comment}").unwrap();
+            }
+
+            result.push('\n');
+        }
+    }
+
+    result
+}
+
+/// Formats a line of code which has had its comment removed.
+fn format_code(code: &str, macros: &[&str]) -> String {
+    let mut tokens = code.split_whitespace();
+
+    let Some(first_token) = tokens.next() else {
+        return String::new();
+    };
+
+    let uppercase = first_token.to_uppercase();
+
+    let is_operation = !macros.contains(&first_token)
+        && (InstructionKind::iter().any(|kind| kind.get_default_properties().name == uppercase)
+            || PSEUDO_INSTRUCTIONS.contains(&uppercase.as_str()));
+
+    let operation = if is_operation {
+        uppercase
+    } else {
+        first_token.to_owned()
+    };
+
+    // The path of an include may contain spaces, so it's kept as it was
+    if operation == INCLUDE_PSEUDO_INSTRUCTION {
+        let path = code.trim()[first_token.len()..].trim();
+
+        return format!("{operation} {path}").trim_end().to_owned();
+    }
+
+    let mut result = operation;
+
+    for token in tokens {
+        let token = COMPARISON_CORRECTIONS
+            .iter()
+            .find(|&&(from, _)| from == token)
+            .map_or(token, |&(_, to)| to);
+
+        result.push(' ');
+        result.push_str(token);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use crate::{DEFAULT_MACHINE_WITH_H, simulation::computer::Computer};
+
+    const MESSY_SOURCE: &str = "
+
+def size 10
+mac store value ; stores a value
+set D value
+add I 1 I
+emc
+
+   set   I 0   ; start at the beginning
+lbl loop ; the loop
+store I
+
+
+ljp I <= size loop
+ljp I != 0 END
+LBL END
+
+";
+
+    #[test]
+    fn source_is_formatted_in_the_canonical_style() {
+        assert_eq!(
+            format_source(MESSY_SOURCE),
+            "DEF size 10
+MAC store value ; stores a value
+SET D value
+ADD I 1 I
+EMC
+
+SET I 0  ; start at the beginning
+LBL loop ; the loop
+store I
+
+LJP I ≤ size loop
+LJP I ≠ 0 END
+LBL END
+",
+        );
+    }
+
+    #[test]
+    fn formatting_doesnt_change_the_program() {
+        let computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        let assemble = |source: &str| {
+            disassemble(&Program::assemble_from("t".into(), source, &computer).unwrap())
+        };
+
+        // Lowercase operations don't assemble, so this only changes the spacing and comparisons
+        let source = "  SET   I 0   ; start\nLBL LOOP\nADD I 1 I\n\n\n\
+                      LJP I <= 10 LOOP ; loop\nLJP I != 0 END\nLBL END";
+
+        assert_eq!(assemble(&format_source(source)), assemble(source));
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/examples");
+
+        let sources = (fs::read_dir(examples).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "zρ"))
+            .map(|path| fs::read_to_string(path).unwrap())
+            .chain([MESSY_SOURCE.to_owned()]);
+
+        for source in sources {
+            let formatted = format_source(&source);
+
+            assert_eq!(format_source(&formatted), formatted);
+        }
+    }
+}
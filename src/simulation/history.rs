@@ -0,0 +1,416 @@
+use super::{
+    computer::{Computer, CycleState, OverwrittenValue, RegisterState},
+    program::Program,
+};
+
+/// Records the execution of a computer so that it can be stepped backwards.
+///
+/// A full copy of the computer is kept every `snapshot_interval` cycles, and only what each cycle
+/// since the latest one changed is kept so that it can be undone without copying the computer.
+/// Going back further than that restores a snapshot and runs the computer forward again. When
+/// there are too many snapshots, every other one is discarded, until the interval reaches
+/// [`Self::MAXIMUM_SNAPSHOT_INTERVAL`]. After that the oldest snapshot is discarded instead, and
+/// the cycles before it can no longer be rewound to.
+#[derive(Clone, Debug)]
+pub struct History {
+    snapshot_interval: u64,
+    maximum_snapshots: usize,
+    /// The computer after every `snapshot_interval`th cycle, starting with the initial state
+    snapshots: Vec<Snapshot>,
+    /// What is needed to undo each cycle since the latest snapshot
+    cycles: Vec<CycleRecord>,
+    /// The number of cycles run since the start
+    cycle: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Snapshot {
+    cycle: u64,
+    computer: Computer,
+    /// Whether or not the cycle before the snapshot started running an instruction
+    started_instruction: bool,
+}
+
+#[derive(Clone, Debug)]
+struct CycleRecord {
+    state: CycleState,
+    /// The earlier state of each register the cycle changed
+    registers: Vec<(u32, RegisterState)>,
+    overwritten: Vec<OverwrittenValue>,
+    started_instruction: bool,
+}
+
+impl History {
+    pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 256;
+    pub const DEFAULT_MAXIMUM_SNAPSHOTS: usize = 64;
+    pub const MAXIMUM_SNAPSHOT_INTERVAL: u64 = 1 << 12;
+
+    /// Starts recording from the computer's current state, which becomes the earliest point it
+    /// can be rewound to.
+    #[must_use]
+    pub fn new(computer: &Computer) -> Self {
+        Self::with_limits(
+            computer,
+            Self::DEFAULT_SNAPSHOT_INTERVAL,
+            Self::DEFAULT_MAXIMUM_SNAPSHOTS,
+        )
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if `snapshot_interval` is zero or `maximum_snapshots` is less than two.
+    #[must_use]
+    pub fn with_limits(
+        computer: &Computer,
+        snapshot_interval: u64,
+        maximum_snapshots: usize,
+    ) -> Self {
+        assert!(snapshot_interval > 0);
+        assert!(maximum_snapshots >= 2);
+
+        Self {
+            snapshot_interval,
+            maximum_snapshots,
+            snapshots: vec![Snapshot {
+                cycle: 0,
+                computer: computer.clone(),
+                started_instruction: false,
+            }],
+            cycles: Vec::new(),
+            cycle: 0,
+        }
+    }
+
+    /// The number of cycles run since recording started.
+    #[must_use]
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// The earliest cycle which can still be rewound to.
+    #[must_use]
+    pub fn earliest_cycle(&self) -> u64 {
+        self.snapshots[0].cycle
+    }
+
+    /// The earliest runtime which can still be rewound to.
+    #[must_use]
+    pub fn earliest_runtime(&self) -> u64 {
+        let earliest = &self.snapshots[0];
+
+        // Only the first snapshot is known to be taken at the start of a tick
+        if earliest.cycle == 0 {
+            earliest.computer.runtime
+        } else {
+            earliest.computer.runtime + 1
+        }
+    }
+
+    /// Equivalent to [`Computer::step_cycle`], but records the cycle.
+    pub fn step_cycle(&mut self, computer: &mut Computer, program: &Program) -> bool {
+        let state = computer.cycle_state();
+        let registers = computer.registers.states();
+        let block_time = computer.block_time;
+
+        computer.registers.start_journal();
+        let did_something = computer.step_cycle(program);
+        let overwritten = computer.registers.take_journal();
+
+        let started_instruction = did_something && block_time == 0;
+
+        self.cycle += 1;
+
+        if self.cycle.is_multiple_of(self.snapshot_interval) {
+            self.cycles.clear();
+            self.snapshots.push(Snapshot {
+                cycle: self.cycle,
+                computer: computer.clone(),
+                started_instruction,
+            });
+
+            if self.snapshots.len() > self.maximum_snapshots
+                && self.snapshot_interval >= Self::MAXIMUM_SNAPSHOT_INTERVAL
+            {
+                self.snapshots.remove(0);
+            } else if self.snapshots.len() > self.maximum_snapshots {
+                self.snapshot_interval *= 2;
+
+                // The latest snapshot is kept, as there are no records of the cycles before it
+                let interval = self.snapshot_interval;
+                let latest = self.cycle;
+                self.snapshots
+                    .retain(|snapshot| snapshot.cycle % interval == 0 || snapshot.cycle == latest);
+            }
+        } else {
+            self.cycles.push(CycleRecord {
+                state,
+                registers: computer.registers.changed_states(registers),
+                overwritten,
+                started_instruction,
+            });
+        }
+
+        did_something
+    }
+
+    /// Equivalent to [`Computer::step_tick`], but records each cycle.
+    pub fn step_tick(&mut self, computer: &mut Computer, program: &Program) {
+        while computer.interrupt.is_none() {
+            self.step_cycle(computer, program);
+
+            if computer.tick_complete {
+                break;
+            }
+        }
+    }
+
+    /// Equivalent to [`Computer::step_instruction`], but records each cycle.
+    pub fn step_instruction(&mut self, computer: &mut Computer, program: &Program) -> u64 {
+        let mut ticks = 0;
+
+        while computer.interrupt.is_none() {
+            let did_something = self.step_cycle(computer, program);
+
+            if computer.tick_complete {
+                ticks += 1;
+            }
+
+            if did_something && computer.block_time == 0 {
+                break;
+            }
+        }
+
+        ticks
+    }
+
+    /// Undoes the last cycle. Returns whether or not it started running an instruction, or `None`
+    /// if there is nothing to undo.
+    pub fn undo_cycle(&mut self, computer: &mut Computer, program: &Program) -> Option<bool> {
+        let Some(record) = self.cycles.pop() else {
+            if self.cycle == self.earliest_cycle() {
+                return None;
+            }
+
+            // Without any records, the computer must be in the state of the latest snapshot
+            let started_instruction = self.snapshots.last().unwrap().started_instruction;
+
+            self.rewind_to_cycle(self.cycle - 1, computer, program);
+
+            return Some(started_instruction);
+        };
+
+        computer.undo_cycle(record.state, &record.registers, &record.overwritten);
+        self.cycle -= 1;
+
+        Some(record.started_instruction)
+    }
+
+    /// Undoes cycles until the start of the last instruction run. Returns `false` if there was
+    /// nothing to undo.
+    pub fn undo_instruction(&mut self, computer: &mut Computer, program: &Program) -> bool {
+        let mut undone = false;
+
+        while let Some(started_instruction) = self.undo_cycle(computer, program) {
+            undone = true;
+
+            if started_instruction {
+                break;
+            }
+        }
+
+        undone
+    }
+
+    /// Undoes cycles until the start of the current tick, or of the previous tick if the current
+    /// one hasn't started yet. Returns `false` if there was nothing to undo, or that tick is no
+    /// longer recorded.
+    pub fn undo_tick(&mut self, computer: &mut Computer, program: &Program) -> bool {
+        if self.cycle == self.earliest_cycle() {
+            return false;
+        }
+
+        let runtime = if computer.tick_complete {
+            computer.runtime.saturating_sub(1)
+        } else {
+            computer.runtime
+        };
+
+        self.rewind_to_runtime(runtime, computer, program)
+    }
+
+    /// Returns to the state the computer was in after the given number of cycles. Returns `false`
+    /// and does nothing if that cycle is before [`Self::earliest_cycle`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `cycle` is after the current cycle.
+    pub fn rewind_to_cycle(
+        &mut self,
+        cycle: u64,
+        computer: &mut Computer,
+        program: &Program,
+    ) -> bool {
+        assert!(cycle <= self.cycle, "can only rewind to an earlier cycle");
+
+        if cycle < self.earliest_cycle() {
+            return false;
+        }
+
+        // Undo recorded cycles where possible to avoid running anything again
+        if cycle + self.cycles.len() as u64 >= self.cycle {
+            while self.cycle > cycle {
+                self.undo_cycle(computer, program);
+            }
+
+            return true;
+        }
+
+        let snapshot = self
+            .snapshots
+            .iter()
+            .rposition(|snapshot| snapshot.cycle <= cycle)
+            .unwrap();
+
+        self.restore_snapshot(snapshot, computer);
+
+        while self.cycle < cycle {
+            self.step_cycle(computer, program);
+        }
+
+        true
+    }
+
+    /// Returns to the first point at which the computer's runtime was `runtime`. Returns `false`
+    /// and does nothing if the computer hasn't reached that runtime yet, or it's before
+    /// [`Self::earliest_runtime`].
+    pub fn rewind_to_runtime(
+        &mut self,
+        runtime: u64,
+        computer: &mut Computer,
+        program: &Program,
+    ) -> bool {
+        if runtime > computer.runtime || runtime < self.earliest_runtime() {
+            return false;
+        }
+
+        // A snapshot may have been taken part way through the tick, so start from before it
+        let snapshot = self
+            .snapshots
+            .iter()
+            .rposition(|snapshot| snapshot.computer.runtime < runtime)
+            .unwrap_or(0);
+
+        self.restore_snapshot(snapshot, computer);
+
+        while computer.runtime < runtime && computer.interrupt.is_none() {
+            self.step_cycle(computer, program);
+        }
+
+        true
+    }
+
+    fn restore_snapshot(&mut self, snapshot: usize, computer: &mut Computer) {
+        self.snapshots.truncate(snapshot + 1);
+        self.cycles.clear();
+
+        let snapshot = &self.snapshots[snapshot];
+
+        *computer = snapshot.computer.clone();
+        self.cycle = snapshot.cycle;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_MACHINE_WITH_H;
+
+    fn run_forever(cycles: u64, history: &mut History, computer: &mut Computer) -> Program {
+        let program =
+            Program::assemble_from("t".into(), "LBL L\nADD X 1 X\nSUB X 1 X\nJMP L", computer)
+                .unwrap();
+
+        for _ in 0..cycles {
+            history.step_cycle(computer, &program);
+        }
+
+        program
+    }
+
+    #[test]
+    fn rewinding_restores_earlier_states() {
+        let mut computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        let mut history = History::with_limits(&computer, 8, 4);
+
+        let program = run_forever(100, &mut history, &mut computer);
+        let state = computer.save_state();
+        run_forever(200, &mut history, &mut computer);
+
+        assert!(history.rewind_to_cycle(100, &mut computer, &program));
+        assert_eq!(computer.save_state(), state);
+
+        assert!(history.rewind_to_cycle(0, &mut computer, &program));
+        assert_eq!(history.undo_cycle(&mut computer, &program), None);
+    }
+
+    #[test]
+    fn undoing_cycles_restores_every_change() {
+        let mut computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        let mut history = History::with_limits(&computer, 1 << 10, 2);
+
+        let program = Program::assemble_from(
+            "t".into(),
+            "LBL L\nCAL F\nSET D I\nADD I 3 I\nLJP I < 60 L\nEND\nLBL F\nADD X D X\nRET",
+            &computer,
+        )
+        .unwrap();
+
+        let mut states = vec![computer.save_state()];
+
+        while computer.interrupt.is_none() {
+            history.step_cycle(&mut computer, &program);
+            states.push(computer.save_state());
+        }
+
+        assert!(states.len() < 1 << 10);
+        assert!(
+            history
+                .cycles
+                .iter()
+                .all(|record| record.registers.len() <= 3)
+        );
+
+        states.pop();
+
+        while let Some(state) = states.pop() {
+            assert!(history.undo_cycle(&mut computer, &program).is_some());
+            assert_eq!(computer.save_state(), state);
+        }
+
+        assert_eq!(history.undo_cycle(&mut computer, &program), None);
+    }
+
+    #[test]
+    fn snapshot_interval_is_capped() {
+        let mut computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        let mut history = History::with_limits(&computer, 1, 2);
+
+        let cycles = History::MAXIMUM_SNAPSHOT_INTERVAL * 4;
+        let program = run_forever(cycles, &mut history, &mut computer);
+
+        assert_eq!(
+            history.snapshot_interval,
+            History::MAXIMUM_SNAPSHOT_INTERVAL
+        );
+        assert!(history.snapshots.len() <= 2);
+        assert!(history.earliest_cycle() > 0);
+
+        let earliest = history.earliest_cycle();
+        assert!(!history.rewind_to_cycle(earliest - 1, &mut computer, &program));
+        assert!(!history.rewind_to_runtime(0, &mut computer, &program));
+        assert_eq!(history.cycle(), cycles);
+
+        assert!(history.rewind_to_cycle(earliest, &mut computer, &program));
+        assert_eq!(history.undo_cycle(&mut computer, &program), None);
+    }
+}
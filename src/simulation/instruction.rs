@@ -5,12 +5,15 @@ use std::{
     ops::{Index, IndexMut},
 };
 
-use strum::{EnumCount, EnumIter, VariantArray};
+use strum::{EnumCount, EnumIter, IntoEnumIterator, VariantArray};
 
 use super::{
-    argument::Argument,
-    computer::{Register, RegisterAccessError, RegisterMap, RegisterSet},
-    integer::{AssignIntegerError, BiggerInteger, Integer},
+    argument::{Argument, NumberSource},
+    computer::{
+        FaultHandlers, Register, RegisterAccessError, RegisterMap, RegisterSet, ReturnStack,
+    },
+    integer::{AssignIntegerError, BiggerInteger, Integer, OverflowMode},
+    source::SourceLocation,
 };
 
 pub type ArgumentValues = [Option<Integer>; Instruction::NUM_ARGUMENTS];
@@ -18,7 +21,7 @@ pub type ArgumentValues = [Option<Integer>; Instruction::NUM_ARGUMENTS];
 #[derive(Clone, Copy, Debug)]
 pub struct Instruction {
     pub kind: InstructionKind,
-    pub line: u32,
+    pub location: SourceLocation,
     pub arguments: [Argument; Self::NUM_ARGUMENTS],
 }
 
@@ -27,12 +30,15 @@ impl Instruction {
 
     /// Returns the total time and energy taken by the instruction, the value of each input, and
     /// whether or not to update `previous_instruction`.
+    #[allow(clippy::too_many_arguments)]
     pub fn evaluate(
         &self,
         registers: &mut RegisterSet,
         instruction_properties: &InstructionKindMap<InstructionProperties>,
         previous_instruction: Option<(&Instruction, &ArgumentValues)>,
         next_instruction: &mut u32,
+        return_stack: &mut ReturnStack,
+        fault_handlers: &mut FaultHandlers,
         runtime: u64,
     ) -> Result<(u32, u32, ArgumentValues, bool), InstructionEvaluationInterrupt> {
         let properties = instruction_properties[self.kind];
@@ -98,7 +104,7 @@ impl Instruction {
 
         match self.kind {
             InstructionKind::Set => {
-                self.write_to_argument(registers, 0, argument_values[1].unwrap())?
+                self.write_to_argument(registers, 0, argument_values[1].unwrap().into())?
                     .set_time_to_write(&mut write_time, &mut write_block_time);
             }
             InstructionKind::Add => {
@@ -122,8 +128,12 @@ impl Instruction {
                 .set_time_to_write(&mut write_time, &mut write_block_time);
             }
             InstructionKind::Negate => {
-                self.write_to_argument(registers, 0, -argument_values[0].unwrap())?
-                    .set_time_to_write(&mut write_time, &mut write_block_time);
+                self.write_to_argument(
+                    registers,
+                    0,
+                    -BiggerInteger::from(argument_values[0].unwrap()),
+                )?
+                .set_time_to_write(&mut write_time, &mut write_block_time);
             }
             InstructionKind::Multiply => {
                 self.apply_operation(
@@ -162,18 +172,22 @@ impl Instruction {
                 .set_time_to_write(&mut write_time, &mut write_block_time);
             }
             InstructionKind::IsOdd => {
-                self.write_to_argument(registers, 0, argument_values[0].unwrap().rem_euclid(2))?
-                    .set_time_to_write(&mut write_time, &mut write_block_time);
+                self.write_to_argument(
+                    registers,
+                    0,
+                    argument_values[0].unwrap().rem_euclid(2).into(),
+                )?
+                .set_time_to_write(&mut write_time, &mut write_block_time);
             }
             InstructionKind::Compare => {
-                self.write_to_argument(registers, 1, argument_values[0].unwrap())?
+                self.write_to_argument(registers, 1, argument_values[0].unwrap().into())?
                     .set_time_to_write(&mut write_time, &mut write_block_time);
             }
             InstructionKind::CompareSetIfTrue => {
                 let result = argument_values[0].unwrap();
 
                 if result == 1 {
-                    self.write_to_argument(registers, 1, result)?
+                    self.write_to_argument(registers, 1, result.into())?
                         .set_time_to_write(&mut write_time, &mut write_block_time);
                 }
             }
@@ -181,7 +195,7 @@ impl Instruction {
                 let result = argument_values[0].unwrap();
 
                 if result == 0 {
-                    self.write_to_argument(registers, 1, result)?
+                    self.write_to_argument(registers, 1, result.into())?
                         .set_time_to_write(&mut write_time, &mut write_block_time);
                 }
             }
@@ -192,6 +206,23 @@ impl Instruction {
                     jump = Some(*self.arguments[1].as_instruction().unwrap());
                 }
             }
+            InstructionKind::Call => {
+                return_stack.push(*next_instruction + 1)?;
+                jump = Some(*self.arguments[0].as_instruction().unwrap());
+            }
+            InstructionKind::Return => jump = Some(return_stack.pop()?),
+            InstructionKind::Handle => {
+                // Code 0 handles every fault
+                let class =
+                    match argument_values[0].unwrap() {
+                        0 => None,
+                        code => Some(FaultClass::from_code(code).ok_or(
+                            InstructionEvaluationInterrupt::NoSuchFaultClass { got: code },
+                        )?),
+                    };
+
+                fault_handlers.set(class, *self.arguments[1].as_instruction().unwrap());
+            }
             InstructionKind::Sleep => {
                 instruction_time += argument_values[0].unwrap().max(0) as u32;
             }
@@ -236,21 +267,32 @@ impl Instruction {
 
                 let digits = argument_values[1].unwrap_or(0).max(0) as u32;
 
-                let clock = {
-                    if let Some(divisor) = 10u64.checked_pow(digits) {
-                        (runtime / divisor % bound) as Integer
-                    } else {
-                        0
-                    }
+                let shifted = 10u64
+                    .checked_pow(digits)
+                    .map_or(0, |divisor| runtime / divisor);
+
+                // The clock counts up from zero again once it's too big, rather than stopping the
+                // program or becoming negative, unless the register saturates
+                let clock = match register.overflow {
+                    OverflowMode::Trap | OverflowMode::Wrap => shifted % bound,
+                    OverflowMode::Saturate => shifted.min(bound - 1),
                 };
 
-                self.write_to_argument(registers, 0, clock)?
+                self.write_to_argument(registers, 0, clock as BiggerInteger)?
                     .set_time_to_write(&mut write_time, &mut write_block_time);
             }
         }
 
         let total_time = (read_time + instruction_time).max(write_block_time) + write_time;
 
+        // Values sent over channels are only used up once the instruction can no longer fault, so
+        // a fault handler doesn't lose them
+        for (register, num_reads) in registers_read.into_iter().enumerate() {
+            if num_reads > 0 {
+                registers.take_incoming(register as u32);
+            }
+        }
+
         if let Some(jump) = jump {
             *next_instruction = jump;
         } else {
@@ -265,6 +307,100 @@ impl Instruction {
         ))
     }
 
+    /// Returns the index of the argument the instruction writes its result to, if any.
+    #[must_use]
+    pub fn written_argument(&self) -> Option<usize> {
+        match self.kind {
+            InstructionKind::Set
+            | InstructionKind::Negate
+            | InstructionKind::IsOdd
+            | InstructionKind::TryWrite
+            | InstructionKind::Clock => Some(0),
+            InstructionKind::Compare
+            | InstructionKind::CompareSetIfTrue
+            | InstructionKind::CompareSetIfFalse => Some(1),
+            InstructionKind::Add
+            | InstructionKind::Subtract
+            | InstructionKind::Multiply
+            | InstructionKind::Divide
+            | InstructionKind::Modulus => Some(2),
+            InstructionKind::Jump
+            | InstructionKind::JumpCondLikely
+            | InstructionKind::JumpCondUnlikely
+            | InstructionKind::Call
+            | InstructionKind::Return
+            | InstructionKind::Handle
+            | InstructionKind::Sleep
+            | InstructionKind::End
+            | InstructionKind::TryRead => None,
+        }
+    }
+
+    /// Returns every register the instruction reads from.
+    #[must_use]
+    pub fn registers_read(
+        &self,
+        instruction_properties: &InstructionKindMap<InstructionProperties>,
+    ) -> Vec<u32> {
+        let properties = instruction_properties[self.kind];
+
+        let mut registers = self
+            .arguments
+            .iter()
+            .zip(properties.arguments)
+            .filter(|&(_, requirement)| requirement != ArgumentRequirement::RegisterWriteOnly)
+            .flat_map(|(argument, _)| argument.number_sources())
+            .filter_map(NumberSource::as_register)
+            .collect::<Vec<_>>();
+
+        registers.sort_unstable();
+        registers.dedup();
+
+        registers
+    }
+
+    /// Returns the longest a register used by the instruction will be blocked for.
+    #[must_use]
+    pub fn register_block_time(
+        &self,
+        registers: &RegisterSet,
+        instruction_properties: &InstructionKindMap<InstructionProperties>,
+    ) -> u32 {
+        let written = self
+            .written_argument()
+            .and_then(|argument| self.arguments[argument].as_register());
+
+        (self.registers_read(instruction_properties).into_iter())
+            .chain(written)
+            .filter_map(|register| registers.get(register))
+            .map(|register| register.block_time)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns a channel register read by the instruction which hasn't been sent a value yet.
+    #[must_use]
+    pub fn empty_channel(
+        &self,
+        registers: &RegisterSet,
+        instruction_properties: &InstructionKindMap<InstructionProperties>,
+    ) -> Option<u32> {
+        let properties = instruction_properties[self.kind];
+
+        self.arguments
+            .iter()
+            .zip(properties.arguments)
+            .filter(|&(_, requirement)| requirement != ArgumentRequirement::RegisterWriteOnly)
+            .flat_map(|(argument, _)| argument.number_sources())
+            .filter_map(NumberSource::as_register)
+            .find(|&register| {
+                registers
+                    .get(register)
+                    .and_then(|register| register.channel)
+                    .is_some_and(|channel| channel.incoming.is_none())
+            })
+    }
+
     /// Returns the time to evaluate the instruction and whether or not to update the
     /// `previous_instruction`.
     #[must_use]
@@ -276,14 +412,14 @@ impl Instruction {
     ) -> (u32, u32, bool) {
         let properties = instruction_properties[self.kind];
 
-        if let &Some((time, ref condition)) = &properties.conditional_time {
-            if condition.matches_context(previous_instruction, self.arguments, argument_values) {
-                let energy = properties
-                    .conditional_energy
-                    .unwrap_or(properties.base_energy);
+        if let &Some((time, ref condition)) = &properties.conditional_time
+            && condition.matches_context(previous_instruction, self.arguments, argument_values)
+        {
+            let energy = properties
+                .conditional_energy
+                .unwrap_or(properties.base_energy);
 
-                return (time, energy, !condition.allows_cascade());
-            }
+            return (time, energy, !condition.allows_cascade());
         }
 
         (properties.base_time, properties.base_energy, true)
@@ -297,14 +433,14 @@ impl Instruction {
     ) -> InstructionKind {
         let properties = instruction_properties[self.kind];
 
-        if let &Some((group, ref condition)) = &properties.group {
-            if condition.matches_context(
+        if let &Some((group, ref condition)) = &properties.group
+            && condition.matches_context(
                 previous_instruction,
                 self.arguments,
                 &ArgumentValues::default(),
-            ) {
-                return group;
-            }
+            )
+        {
+            return group;
         }
 
         self.kind
@@ -319,7 +455,7 @@ impl Instruction {
         &self,
         registers: &'a mut RegisterSet,
         destination: usize,
-        value: Integer,
+        value: BiggerInteger,
     ) -> Result<&'a Register, InstructionEvaluationInterrupt> {
         let register = self.register_of_argument(destination);
 
@@ -356,34 +492,15 @@ impl Instruction {
         let lhs = argument_values[argument_sources[0]].unwrap();
         let rhs = argument_values[argument_sources[1]].unwrap();
 
-        if let Some(result) = fast_function(lhs, rhs) {
-            Ok(self.write_to_argument(registers, argument_sources[2], result)?)
-        } else {
-            let result =
-                debug_function(lhs as BiggerInteger, rhs as BiggerInteger).into_debug_result()?;
-
-            let register = self.register_of_argument(argument_sources[2]);
-            let value = registers.get(register).unwrap().value().map_err(|error| {
-                InstructionEvaluationInterrupt::RegisterError { register, error }
-            })?;
-
-            Err(InstructionEvaluationInterrupt::RegisterError {
-                register,
-                error: RegisterAccessError::InvalidAssignment {
-                    error: if result > 0 {
-                        AssignIntegerError::ValueMuchTooBig {
-                            got: result,
-                            maximum: value.maximum(),
-                        }
-                    } else {
-                        AssignIntegerError::ValueMuchTooSmall {
-                            got: result,
-                            minimum: value.minimum(),
-                        }
-                    },
-                },
-            })
-        }
+        // Results which don't fit are brought into range when they're written
+        let result = match fast_function(lhs, rhs) {
+            Some(result) => result.into(),
+            None => {
+                debug_function(lhs as BiggerInteger, rhs as BiggerInteger).into_debug_result()?
+            }
+        };
+
+        self.write_to_argument(registers, argument_sources[2], result)
     }
 }
 
@@ -435,6 +552,9 @@ pub enum InstructionKind {
     Jump,
     JumpCondLikely,
     JumpCondUnlikely,
+    Call,
+    Return,
+    Handle,
     Sleep,
     End,
     TryRead,
@@ -485,13 +605,10 @@ impl InstructionKindMap<InstructionProperties> {
         self
     }
 
+    /// Finds the enabled instruction with the given name.
     #[must_use]
     pub fn instruction_with_name(&self, name: &str) -> Option<&InstructionProperties> {
-        if name.is_empty() {
-            return None;
-        }
-
-        self.0.iter().find(|&properties| properties.name == name)
+        (self.0.iter()).find(|&properties| properties.enabled && properties.name == name)
     }
 }
 
@@ -509,7 +626,7 @@ impl<T> IndexMut<InstructionKind> for InstructionKindMap<T> {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InstructionEvaluationInterrupt {
     RegisterError {
         register: u32,
@@ -519,6 +636,14 @@ pub enum InstructionEvaluationInterrupt {
         error: ArithmaticError,
     },
     ProgramComplete,
+    /// A `CAL` was run with the return stack already full
+    ReturnStackOverflow,
+    /// A `RET` was run without a `CAL` to return to
+    ReturnStackUnderflow,
+    /// A `HDL` was given a code which isn't the code of any [`FaultClass`] (or 0)
+    NoSuchFaultClass {
+        got: Integer,
+    },
     RuntimeCounterOverflow,
     EnergyCounterOverflow,
 }
@@ -529,11 +654,82 @@ impl From<ArithmaticError> for InstructionEvaluationInterrupt {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ArithmaticError {
     DivideByZero,
 }
 
+/// The interrupts which a program can recover from by jumping to a handler set with `HDL`, in the
+/// order of their fault codes (starting from 1).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, EnumCount)]
+pub enum FaultClass {
+    IndexOutOfBounds,
+    ValueTooBig,
+    ValueTooSmall,
+    DivideByZero,
+}
+
+impl FaultClass {
+    /// The names of each fault class in a saved state.
+    pub const NAMES: [(&str, Self); Self::COUNT] = [
+        ("index_out_of_bounds", Self::IndexOutOfBounds),
+        ("value_too_big", Self::ValueTooBig),
+        ("value_too_small", Self::ValueTooSmall),
+        ("divide_by_zero", Self::DivideByZero),
+    ];
+
+    /// Returns the class of fault an interrupt is, if it can be handled.
+    #[must_use]
+    pub fn of(interrupt: InstructionEvaluationInterrupt) -> Option<Self> {
+        match interrupt {
+            InstructionEvaluationInterrupt::RegisterError { error, .. } => match error {
+                RegisterAccessError::IndexTooBig { .. }
+                | RegisterAccessError::IndexTooSmall { .. } => Some(Self::IndexOutOfBounds),
+                RegisterAccessError::InvalidAssignment {
+                    error:
+                        AssignIntegerError::ValueTooBig { .. }
+                        | AssignIntegerError::ValueMuchTooBig { .. },
+                } => Some(Self::ValueTooBig),
+                RegisterAccessError::InvalidAssignment {
+                    error:
+                        AssignIntegerError::ValueTooSmall { .. }
+                        | AssignIntegerError::ValueMuchTooSmall { .. },
+                } => Some(Self::ValueTooSmall),
+                _ => None,
+            },
+            InstructionEvaluationInterrupt::ArithmaticError {
+                error: ArithmaticError::DivideByZero,
+            } => Some(Self::DivideByZero),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn code(self) -> Integer {
+        self as Integer + 1
+    }
+
+    #[must_use]
+    pub fn from_code(code: Integer) -> Option<Self> {
+        Self::iter().find(|class| class.code() == code)
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        (Self::NAMES.iter())
+            .find(|&&(other, _)| other == name)
+            .map(|&(_, class)| class)
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        (Self::NAMES.iter())
+            .find(|&&(_, other)| other == self)
+            .map(|&(name, _)| name)
+            .unwrap()
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ArgumentRequirement {
     Constant,
@@ -587,6 +783,8 @@ impl Display for ArgumentRequirement {
 pub struct InstructionProperties {
     pub kind: InstructionKind,
     pub name: &'static str,
+    /// Disabled instructions can't be assembled on the machine.
+    pub enabled: bool,
     pub arguments: [ArgumentRequirement; Instruction::NUM_ARGUMENTS],
     pub base_time: u32,
     pub conditional_time: Option<(u32, PropertyCondition)>,
@@ -600,6 +798,7 @@ impl InstructionProperties {
     pub const DEFAULT: Self = Self {
         kind: InstructionKind::Set,
         name: "",
+        enabled: true,
         arguments: [ArgumentRequirement::Empty; Instruction::NUM_ARGUMENTS],
         base_time: 0,
         conditional_time: None,
@@ -691,6 +890,26 @@ impl PropertyCondition {
     }
 }
 
+impl Display for PropertyCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropertyCondition::SameAsPrevious { kind, .. } => write!(
+                f,
+                "after {} with the same arguments",
+                kind.get_default_properties().name,
+            ),
+            PropertyCondition::ArgumentMatches { argument, value } => {
+                write!(f, "when argument {} is {value}", argument + 1)
+            }
+            PropertyCondition::ArgumentTypeMatches {
+                argument,
+                requirement,
+            } => write!(f, "when argument {} is {requirement}", argument + 1),
+            PropertyCondition::Always => write!(f, "always"),
+        }
+    }
+}
+
 const fn arguments<const N: usize>(
     arguments: [ArgumentRequirement; N],
 ) -> [ArgumentRequirement; Instruction::NUM_ARGUMENTS] {
@@ -922,6 +1141,33 @@ pub static DEFAULT_INSTRUCTIONS: InstructionKindMap<InstructionProperties> = Ins
         group: Some((InstructionKind::Jump, PropertyCondition::Always)),
         ..InstructionProperties::DEFAULT
     },
+    InstructionProperties {
+        kind: InstructionKind::Call,
+        name: "CAL",
+        arguments: arguments([ArgumentRequirement::Instruction]),
+        base_time: 1,
+        base_energy: 2,
+        ..InstructionProperties::DEFAULT
+    },
+    InstructionProperties {
+        kind: InstructionKind::Return,
+        name: "RET",
+        arguments: arguments([]),
+        base_time: 1,
+        base_energy: 2,
+        ..InstructionProperties::DEFAULT
+    },
+    InstructionProperties {
+        kind: InstructionKind::Handle,
+        name: "HDL",
+        arguments: arguments([
+            ArgumentRequirement::Constant,
+            ArgumentRequirement::Instruction,
+        ]),
+        base_time: 1,
+        base_energy: 1,
+        ..InstructionProperties::DEFAULT
+    },
     InstructionProperties {
         kind: InstructionKind::Sleep,
         name: "SLP",
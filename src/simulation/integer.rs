@@ -57,6 +57,39 @@ impl DigitInteger {
         self.value
     }
 
+    /// Brings a value into the range of the integer as `mode` says to, or returns the error for
+    /// [`OverflowMode::Trap`] if it's out of range.
+    pub fn fit(
+        &self,
+        value: BiggerInteger,
+        mode: OverflowMode,
+    ) -> Result<Integer, AssignIntegerError> {
+        let maximum = BiggerInteger::from(self.maximum());
+
+        let value = match mode {
+            OverflowMode::Trap => value,
+            // There are 2 * maximum + 1 values in the range, from -maximum to maximum. The value is
+            // brought into the range before the offset is added, so adding it can't overflow
+            OverflowMode::Wrap => {
+                let range = 2 * maximum + 1;
+                (value.rem_euclid(range) + maximum).rem_euclid(range) - maximum
+            }
+            OverflowMode::Saturate => value.clamp(-maximum, maximum),
+        };
+
+        match Integer::try_from(value) {
+            Ok(value) => Self::check_value(value, self.digits),
+            Err(_) if value > 0 => Err(AssignIntegerError::ValueMuchTooBig {
+                got: value,
+                maximum: self.maximum(),
+            }),
+            Err(_) => Err(AssignIntegerError::ValueMuchTooSmall {
+                got: value,
+                minimum: self.minimum(),
+            }),
+        }
+    }
+
     #[must_use]
     pub const fn get_bigger(&self) -> BiggerInteger {
         self.value as BiggerInteger
@@ -119,6 +152,42 @@ impl DigitInteger {
     }
 }
 
+/// What happens when a value written to a register is out of its range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowMode {
+    /// The program is interrupted
+    #[default]
+    Trap,
+    /// The value wraps around to the other end of the range, so one more than the maximum is the
+    /// minimum
+    Wrap,
+    /// The value is clamped to the maximum or minimum
+    Saturate,
+}
+
+impl OverflowMode {
+    pub const NAMES: [(&str, Self); 3] = [
+        ("trap", Self::Trap),
+        ("wrap", Self::Wrap),
+        ("saturate", Self::Saturate),
+    ];
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        (Self::NAMES.iter())
+            .find(|&&(other, _)| other == name)
+            .map(|&(_, mode)| mode)
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        (Self::NAMES.iter())
+            .find(|&&(_, other)| other == self)
+            .map(|&(name, _)| name)
+            .unwrap()
+    }
+}
+
 impl Display for DigitInteger {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.get())
@@ -141,7 +210,7 @@ impl Debug for DigitInteger {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AssignIntegerError {
     ValueTooBig {
         got: Integer,
@@ -206,3 +275,74 @@ pub fn format_underflow_error(
         "\"{value}\" is too small for this machine (minimum: {minimum})"
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THREE_DIGITS: DigitInteger = DigitInteger {
+        value: 0,
+        digits: 2,
+    };
+
+    #[test]
+    fn values_in_range_are_unchanged() {
+        for mode in OverflowMode::NAMES.map(|(_, mode)| mode) {
+            assert_eq!(THREE_DIGITS.fit(999, mode), Ok(999));
+            assert_eq!(THREE_DIGITS.fit(-999, mode), Ok(-999));
+            assert_eq!(THREE_DIGITS.fit(0, mode), Ok(0));
+        }
+    }
+
+    #[test]
+    fn trap_rejects_values_out_of_range() {
+        assert_eq!(
+            THREE_DIGITS.fit(1000, OverflowMode::Trap),
+            Err(AssignIntegerError::ValueTooBig {
+                got: 1000,
+                maximum: 999,
+            }),
+        );
+        assert_eq!(
+            THREE_DIGITS.fit(-1000, OverflowMode::Trap),
+            Err(AssignIntegerError::ValueTooSmall {
+                got: -1000,
+                minimum: -999,
+            }),
+        );
+        assert_eq!(
+            THREE_DIGITS.fit(BiggerInteger::MAX, OverflowMode::Trap),
+            Err(AssignIntegerError::ValueMuchTooBig {
+                got: BiggerInteger::MAX,
+                maximum: 999,
+            }),
+        );
+    }
+
+    #[test]
+    fn wrap_goes_around_to_the_other_end() {
+        assert_eq!(THREE_DIGITS.fit(1000, OverflowMode::Wrap), Ok(-999));
+        assert_eq!(THREE_DIGITS.fit(-1000, OverflowMode::Wrap), Ok(999));
+        assert_eq!(THREE_DIGITS.fit(1999, OverflowMode::Wrap), Ok(0));
+        assert_eq!(THREE_DIGITS.fit(999 * 999, OverflowMode::Wrap), Ok(500));
+        assert!(
+            THREE_DIGITS
+                .fit(BiggerInteger::MIN, OverflowMode::Wrap)
+                .is_ok()
+        );
+        assert_eq!(
+            THREE_DIGITS.fit(BiggerInteger::MAX, OverflowMode::Wrap),
+            Ok(662),
+        );
+    }
+
+    #[test]
+    fn saturate_clamps_to_the_range() {
+        assert_eq!(THREE_DIGITS.fit(1000, OverflowMode::Saturate), Ok(999));
+        assert_eq!(THREE_DIGITS.fit(-1000, OverflowMode::Saturate), Ok(-999));
+        assert_eq!(
+            THREE_DIGITS.fit(BiggerInteger::MAX, OverflowMode::Saturate),
+            Ok(999),
+        );
+    }
+}
@@ -0,0 +1,912 @@
+use std::fmt::Display;
+
+use simplerand::Rng;
+use toml::{Table, Value};
+
+use crate::{DEFAULT_MACHINE, DEFAULT_MACHINE_WITH_H};
+
+use super::{
+    computer::{self, Computer},
+    definition::{self, DefinitionError, DefinitionErrorKind, Entry},
+    instruction::InstructionEvaluationInterrupt,
+    integer::{BiggerInteger, Integer},
+    program::Program,
+};
+
+/// A puzzle for a program to solve: a machine, what its registers start with, and what they
+/// should end with. See `resources/levels` for examples.
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub name: String,
+    pub description: String,
+    pub computer: Computer,
+    pub inputs: Vec<(u32, Input)>,
+    pub outputs: Vec<(u32, Output)>,
+    /// The number of times to run the program, with a different seed for random inputs each time
+    pub cases: u32,
+    pub seed: u64,
+    pub tick_limit: u64,
+}
+
+/// The initial contents of a register, starting from its first element.
+#[derive(Clone, Debug)]
+pub enum Input {
+    Values(Vec<Integer>),
+    Random {
+        minimum: Integer,
+        maximum: Integer,
+        /// Defaults to the length of the register
+        length: Option<usize>,
+    },
+}
+
+/// The expected final contents of a register, starting from its first element.
+#[derive(Clone, Debug)]
+pub enum Output {
+    Values(Vec<Integer>),
+    Sequence {
+        sequence: Sequence,
+        /// Defaults to the length of the register
+        length: Option<usize>,
+    },
+    /// The initial values of a register, in ascending order
+    Sorted(u32),
+    /// The sum of the initial values of a register
+    Sum(u32),
+    /// Any values which never decrease
+    Ascending {
+        /// Defaults to the length of the register
+        length: Option<usize>,
+    },
+    /// Any values between a minimum and maximum, inclusive
+    Range {
+        minimum: Integer,
+        maximum: Integer,
+        /// Defaults to the length of the register
+        length: Option<usize>,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Sequence {
+    Fibonacci,
+    Kolakoski,
+    Primes,
+}
+
+#[derive(Clone, Debug)]
+pub struct CaseResult {
+    pub case: u32,
+    pub seed: u64,
+    pub runtime: u64,
+    pub energy_used: u64,
+    pub failure: Option<CaseFailure>,
+}
+
+#[derive(Clone, Debug)]
+pub enum CaseFailure {
+    Interrupted {
+        /// A description of where the program halted
+        location: Option<String>,
+        interrupt: InstructionEvaluationInterrupt,
+    },
+    TickLimit(u64),
+    IncorrectValue {
+        register: u32,
+        index: Integer,
+        expected: Integer,
+        got: Integer,
+    },
+    /// A value less than the one before it, for an ascending output
+    Descending {
+        register: u32,
+        index: Integer,
+        previous: Integer,
+        got: Integer,
+    },
+    OutOfRange {
+        register: u32,
+        index: Integer,
+        minimum: Integer,
+        maximum: Integer,
+        got: Integer,
+    },
+}
+
+/// The scores of a program which passed every case of a level. Lower is better.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scores {
+    /// The longest runtime of any case
+    pub runtime: u64,
+    /// The most energy used by any case
+    pub energy: u64,
+    /// The number of instructions in the program
+    pub size: usize,
+}
+
+impl Level {
+    pub const DEFAULT_TICK_LIMIT: u64 = 1_000_000;
+
+    pub fn load_from(source_code: &str) -> Result<Self, Vec<DefinitionError>> {
+        let table = definition::parse(source_code)?;
+
+        let mut errors = Vec::new();
+        let mut root = Entry::new(String::new(), &table, &mut errors);
+
+        let name = root.string("name");
+        let description = root.optional_string("description");
+
+        let computer = match root.required("machine") {
+            Some(Value::String(machine)) => match machine.as_str() {
+                "default" => Some(Computer::load_from(DEFAULT_MACHINE).unwrap()),
+                "default_with_h" => Some(Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap()),
+                _ => {
+                    root.error(
+                        "machine",
+                        DefinitionErrorKind::NoSuchMachine(machine.to_owned()),
+                    );
+                    None
+                }
+            },
+            Some(Value::Table(machine)) => {
+                Computer::load_from_entry(root.child("machine", machine))
+            }
+            Some(_) => {
+                root.error(
+                    "machine",
+                    DefinitionErrorKind::IncorrectType {
+                        expected: "a machine name or table",
+                    },
+                );
+                None
+            }
+            None => None,
+        };
+
+        let cases = root.optional_integer("cases", 1, u32::MAX.into());
+        let seed = root.optional_integer("seed", 0, i64::MAX);
+        let tick_limit = root.optional_integer("tick_limit", 1, i64::MAX);
+        let inputs = root.optional_table("inputs");
+        let outputs = root.table("outputs");
+
+        let (inputs, outputs) = match &computer {
+            Some(computer) => (
+                inputs.map_or_else(Vec::new, |inputs| {
+                    load_inputs(root.child("inputs", inputs), computer)
+                }),
+                outputs.map_or_else(Vec::new, |outputs| {
+                    load_outputs(root.child("outputs", outputs), computer)
+                }),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+
+        root.finish(&[
+            "name",
+            "description",
+            "machine",
+            "cases",
+            "seed",
+            "tick_limit",
+            "inputs",
+            "outputs",
+        ]);
+
+        let (
+            Some(name),
+            Some(description),
+            Some(computer),
+            Some(cases),
+            Some(seed),
+            Some(tick_limit),
+        ) = (name, description, computer, cases, seed, tick_limit)
+        else {
+            return Err(errors);
+        };
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            description: description.unwrap_or_default().to_owned(),
+            computer,
+            inputs,
+            outputs,
+            cases: cases.unwrap_or(1) as u32,
+            seed: seed.unwrap_or(0) as u64,
+            tick_limit: tick_limit.map_or(Self::DEFAULT_TICK_LIMIT, |limit| limit as u64),
+        })
+    }
+
+    /// Returns the computer with the inputs for a case written to its registers.
+    #[must_use]
+    pub fn setup_case(&self, case: u32) -> Computer {
+        let mut computer = self.computer.clone();
+
+        let mut rng = Rng::new();
+        rng.set_seed(self.seed_of_case(case).into());
+
+        for (register, input) in &self.inputs {
+            let values = computer
+                .registers
+                .registers
+                .get_mut(*register as usize)
+                .and_then(Option::as_mut)
+                .unwrap()
+                .all_values_mut();
+
+            match input {
+                Input::Values(inputs) => {
+                    for (value, &input) in values.iter_mut().zip(inputs) {
+                        value.try_set(input).unwrap();
+                    }
+                }
+                &Input::Random {
+                    minimum,
+                    maximum,
+                    length,
+                } => {
+                    let length = length.unwrap_or(values.len());
+
+                    for value in values.iter_mut().take(length) {
+                        let range = (maximum - minimum) as u128 + 1;
+                        value
+                            .try_set(minimum + rng.randn(range) as Integer)
+                            .unwrap();
+                    }
+                }
+            }
+        }
+
+        computer
+    }
+
+    #[must_use]
+    pub fn seed_of_case(&self, case: u32) -> u64 {
+        self.seed.wrapping_add(case.into())
+    }
+
+    #[must_use]
+    pub fn run_case(&self, program: &Program, case: u32) -> CaseResult {
+        let initial = self.setup_case(case);
+        let mut computer = initial.clone();
+
+        while computer.interrupt.is_none() && computer.runtime < self.tick_limit {
+            computer.step_tick(program);
+        }
+
+        let failure = match computer.interrupt {
+            Some(InstructionEvaluationInterrupt::ProgramComplete) => {
+                self.check(&initial, &computer).err()
+            }
+            Some(interrupt) => Some(CaseFailure::Interrupted {
+                location: program
+                    .instructions
+                    .get(computer.instruction as usize)
+                    .map(|instruction| program.describe_location(instruction.location)),
+                interrupt,
+            }),
+            None => Some(CaseFailure::TickLimit(self.tick_limit)),
+        };
+
+        CaseResult {
+            case,
+            seed: self.seed_of_case(case),
+            runtime: computer.runtime,
+            energy_used: computer.energy_used,
+            failure,
+        }
+    }
+
+    #[must_use]
+    pub fn run(&self, program: &Program) -> Vec<CaseResult> {
+        (0..self.cases)
+            .map(|case| self.run_case(program, case))
+            .collect()
+    }
+
+    /// Returns the scores of a program, or `None` if it failed any case.
+    #[must_use]
+    pub fn scores(results: &[CaseResult], program: &Program) -> Option<Scores> {
+        if results.iter().any(|result| result.failure.is_some()) {
+            return None;
+        }
+
+        Some(Scores {
+            runtime: results.iter().map(|result| result.runtime).max()?,
+            energy: results.iter().map(|result| result.energy_used).max()?,
+            size: program.instructions.len(),
+        })
+    }
+
+    /// Checks the registers of a computer which started as `initial` against the outputs.
+    pub fn check(&self, initial: &Computer, computer: &Computer) -> Result<(), CaseFailure> {
+        for (register, output) in &self.outputs {
+            let register_data = computer.registers.get(*register).unwrap();
+            let values = register_data.all_values();
+
+            let initial_values = |input| {
+                initial
+                    .registers
+                    .get(input)
+                    .unwrap()
+                    .all_values()
+                    .iter()
+                    .map(|value| value.get())
+            };
+
+            let index = |i: usize| i as Integer + register_data.offset();
+
+            let expected = match *output {
+                Output::Values(ref expected) => expected.clone(),
+                Output::Sequence { sequence, length } => {
+                    sequence.first(length.unwrap_or(values.len()))
+                }
+                Output::Sorted(input) => {
+                    let mut sorted = initial_values(input).collect::<Vec<_>>();
+                    sorted.sort_unstable();
+                    sorted
+                }
+                Output::Sum(input) => {
+                    // Added up in a wider type so a sum no register could hold can't overflow,
+                    // and saturated like the sequences are
+                    let sum = initial_values(input)
+                        .map(BiggerInteger::from)
+                        .sum::<BiggerInteger>();
+                    vec![sum.clamp(Integer::MIN.into(), Integer::MAX.into()) as Integer]
+                }
+                Output::Ascending { length } => {
+                    let length = length.unwrap_or(values.len());
+
+                    for (i, pair) in values[..length].windows(2).enumerate() {
+                        let (previous, got) = (pair[0].get(), pair[1].get());
+
+                        if got < previous {
+                            return Err(CaseFailure::Descending {
+                                register: *register,
+                                index: index(i + 1),
+                                previous,
+                                got,
+                            });
+                        }
+                    }
+
+                    continue;
+                }
+                Output::Range {
+                    minimum,
+                    maximum,
+                    length,
+                } => {
+                    let length = length.unwrap_or(values.len());
+
+                    for (i, got) in values[..length].iter().enumerate() {
+                        if !(minimum..=maximum).contains(&got.get()) {
+                            return Err(CaseFailure::OutOfRange {
+                                register: *register,
+                                index: index(i),
+                                minimum,
+                                maximum,
+                                got: got.get(),
+                            });
+                        }
+                    }
+
+                    continue;
+                }
+            };
+
+            for (i, (got, expected)) in values.iter().zip(expected).enumerate() {
+                if got.get() != expected {
+                    return Err(CaseFailure::IncorrectValue {
+                        register: *register,
+                        index: index(i),
+                        expected,
+                        got: got.get(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Sequence {
+    /// Returns the first `length` elements of the sequence.
+    #[must_use]
+    pub fn first(self, length: usize) -> Vec<Integer> {
+        let mut sequence = Vec::with_capacity(length);
+
+        match self {
+            Sequence::Fibonacci => {
+                let (mut a, mut b): (Integer, Integer) = (1, 1);
+
+                while sequence.len() < length {
+                    sequence.push(a);
+                    (a, b) = (b, a.saturating_add(b));
+                }
+            }
+            Sequence::Kolakoski => {
+                sequence.extend([1, 2, 2]);
+                let mut i = 2;
+
+                while sequence.len() < length {
+                    for _ in 0..sequence[i] {
+                        sequence.push((i % 2 + 1) as Integer);
+                    }
+
+                    i += 1;
+                }
+            }
+            Sequence::Primes => {
+                let mut candidate = 2;
+
+                while sequence.len() < length {
+                    if sequence.iter().all(|prime| candidate % prime != 0) {
+                        sequence.push(candidate);
+                    }
+
+                    candidate += 1;
+                }
+            }
+        }
+
+        // There may be excess elements
+        sequence.truncate(length);
+
+        sequence
+    }
+}
+
+fn load_inputs(mut inputs: Entry, computer: &Computer) -> Vec<(u32, Input)> {
+    let mut loaded = Vec::new();
+
+    let table = inputs.table;
+
+    let mut names = table.keys().collect::<Vec<_>>();
+    names.sort_unstable();
+
+    for name in names {
+        let Some((register, table)) = register_entry(&mut inputs, name, computer) else {
+            continue;
+        };
+
+        let values = computer.registers.get(register).unwrap().all_values();
+        let (register_minimum, register_maximum) =
+            (values[0].minimum().into(), values[0].maximum().into());
+
+        if table.contains_key("values") == table.contains_key("random") {
+            inputs.error(
+                name,
+                DefinitionErrorKind::ExpectedOneOf(&["values", "random"]),
+            );
+            continue;
+        }
+
+        let mut entry = inputs.child(name, table);
+
+        let input = if table.contains_key("values") {
+            let inputs = entry.integer_array("values", register_minimum, register_maximum);
+
+            if let Some(inputs) = &inputs {
+                check_length(&mut entry, "values", inputs.len(), values.len());
+            }
+
+            inputs.map(|inputs| Input::Values(inputs.into_iter().map(|x| x as Integer).collect()))
+        } else if let Some(random) = entry.table("random") {
+            let mut random = entry.child("random", random);
+
+            let minimum = random.integer("minimum", register_minimum, register_maximum);
+            let maximum = random.integer(
+                "maximum",
+                minimum.unwrap_or(register_minimum),
+                register_maximum,
+            );
+            let length = random.optional_integer("length", 1, values.len() as i64);
+
+            random.finish(&["minimum", "maximum", "length"]);
+
+            match (minimum, maximum, length) {
+                (Some(minimum), Some(maximum), Some(length)) => Some(Input::Random {
+                    minimum: minimum as Integer,
+                    maximum: maximum as Integer,
+                    length: length.map(|length| length as usize),
+                }),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        entry.finish(&["values", "random"]);
+
+        if let Some(input) = input {
+            loaded.push((register, input));
+        }
+    }
+
+    loaded
+}
+
+fn load_outputs(mut outputs: Entry, computer: &Computer) -> Vec<(u32, Output)> {
+    const KINDS: &[&str] = &["values", "sequence", "sorted", "sum", "ascending", "range"];
+
+    let mut loaded = Vec::new();
+
+    let table = outputs.table;
+
+    let mut names = table.keys().collect::<Vec<_>>();
+    names.sort_unstable();
+
+    for name in names {
+        let Some((register, table)) = register_entry(&mut outputs, name, computer) else {
+            continue;
+        };
+
+        let values = computer.registers.get(register).unwrap().all_values();
+        let (minimum, maximum) = (values[0].minimum().into(), values[0].maximum().into());
+
+        let kinds = KINDS
+            .iter()
+            .filter(|kind| table.contains_key(**kind))
+            .collect::<Vec<_>>();
+
+        let [&kind] = kinds[..] else {
+            outputs.error(name, DefinitionErrorKind::ExpectedOneOf(KINDS));
+            continue;
+        };
+
+        let mut entry = outputs.child(name, table);
+
+        let output = match kind {
+            "values" => {
+                let outputs = entry.integer_array("values", minimum, maximum);
+
+                if let Some(outputs) = &outputs {
+                    check_length(&mut entry, "values", outputs.len(), values.len());
+                }
+
+                entry.finish(&["values"]);
+
+                outputs.map(|outputs| {
+                    Output::Values(outputs.into_iter().map(|x| x as Integer).collect())
+                })
+            }
+            "sequence" => {
+                let sequence = entry.string("sequence").and_then(|sequence| {
+                    let kind = match sequence {
+                        "fibonacci" => Sequence::Fibonacci,
+                        "kolakoski" => Sequence::Kolakoski,
+                        "primes" => Sequence::Primes,
+                        _ => {
+                            entry.error(
+                                "sequence",
+                                DefinitionErrorKind::NoSuchSequence(sequence.to_owned()),
+                            );
+                            return None;
+                        }
+                    };
+
+                    Some(kind)
+                });
+                let length = entry.optional_integer("length", 1, values.len() as i64);
+
+                entry.finish(&["sequence", "length"]);
+
+                sequence
+                    .zip(length)
+                    .map(|(sequence, length)| Output::Sequence {
+                        sequence,
+                        length: length.map(|length| length as usize),
+                    })
+            }
+            "sorted" | "sum" => {
+                let input = entry.register(kind).filter(|&input| {
+                    let defined = computer.registers.get(input).is_some();
+
+                    if !defined {
+                        entry.error(kind, DefinitionErrorKind::UndefinedRegister(input));
+                    }
+
+                    defined
+                });
+
+                entry.finish(&[kind]);
+
+                input.map(|input| match kind {
+                    "sorted" => Output::Sorted(input),
+                    _ => Output::Sum(input),
+                })
+            }
+            "ascending" => {
+                let ascending = match entry.optional_boolean("ascending") {
+                    Some(Some(true)) => Some(()),
+                    Some(_) => {
+                        entry.error(
+                            "ascending",
+                            DefinitionErrorKind::IncorrectType { expected: "true" },
+                        );
+                        None
+                    }
+                    None => None,
+                };
+                let length = entry.optional_integer("length", 1, values.len() as i64);
+
+                entry.finish(&["ascending", "length"]);
+
+                ascending.and(length).map(|length| Output::Ascending {
+                    length: length.map(|length| length as usize),
+                })
+            }
+            "range" => {
+                let range = entry.table("range").and_then(|range| {
+                    let mut range = entry.child("range", range);
+
+                    let range_minimum = range.integer("minimum", minimum, maximum);
+                    let range_maximum =
+                        range.integer("maximum", range_minimum.unwrap_or(minimum), maximum);
+                    let length = range.optional_integer("length", 1, values.len() as i64);
+
+                    range.finish(&["minimum", "maximum", "length"]);
+
+                    match (range_minimum, range_maximum, length) {
+                        (Some(range_minimum), Some(range_maximum), Some(length)) => {
+                            Some(Output::Range {
+                                minimum: range_minimum as Integer,
+                                maximum: range_maximum as Integer,
+                                length: length.map(|length| length as usize),
+                            })
+                        }
+                        _ => None,
+                    }
+                });
+
+                entry.finish(&["range"]);
+
+                range
+            }
+            _ => unreachable!(),
+        };
+
+        if let Some(output) = output {
+            loaded.push((register, output));
+        }
+    }
+
+    loaded
+}
+
+/// Returns the register named by a key of the inputs or outputs, if it's defined on the machine,
+/// along with the key's table.
+fn register_entry<'a>(
+    entry: &mut Entry<'a, '_>,
+    name: &str,
+    computer: &Computer,
+) -> Option<(u32, &'a Table)> {
+    let Some(register) = definition::register_index(name) else {
+        entry.error(name, DefinitionErrorKind::NoSuchRegister(name.to_owned()));
+        return None;
+    };
+
+    if computer.registers.get(register).is_none() {
+        entry.error(name, DefinitionErrorKind::UndefinedRegister(register));
+        return None;
+    }
+
+    let table = entry.table;
+
+    entry
+        .as_table(name, &table[name])
+        .map(|table| (register, table))
+}
+
+fn check_length(entry: &mut Entry, key: &str, got: usize, length: usize) {
+    if got > length {
+        entry.error(key, DefinitionErrorKind::TooManyValues { got, length });
+    }
+}
+
+impl Display for CaseFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaseFailure::Interrupted {
+                location: Some(location),
+                interrupt,
+            } => write!(f, "Halted on {location}: {interrupt:?}"),
+            CaseFailure::Interrupted {
+                location: None,
+                interrupt,
+            } => write!(f, "Halted: {interrupt:?}"),
+            CaseFailure::TickLimit(limit) => write!(f, "Didn't finish within {limit} ticks"),
+            CaseFailure::IncorrectValue {
+                register,
+                index,
+                expected,
+                got,
+            } => {
+                let name = computer::name_of_register(*register).unwrap();
+
+                write!(f, "Expected {expected} at {name}[{index}], got {got}")
+            }
+            CaseFailure::Descending {
+                register,
+                index,
+                previous,
+                got,
+            } => {
+                let name = computer::name_of_register(*register).unwrap();
+
+                write!(
+                    f,
+                    "Expected at least {previous} (the value before) at {name}[{index}], got {got}"
+                )
+            }
+            CaseFailure::OutOfRange {
+                register,
+                index,
+                minimum,
+                maximum,
+                got,
+            } => {
+                let name = computer::name_of_register(*register).unwrap();
+
+                write!(
+                    f,
+                    "Expected {minimum} to {maximum} at {name}[{index}], got {got}"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARRAY_SUM: &str = include_str!("../../resources/levels/array_sum.toml");
+    const ARRAY_SUM_SOLUTION: &str = include_str!("../../resources/examples/array_sum.zρ");
+
+    fn assemble(level: &Level, source_code: &str) -> Program {
+        Program::assemble_from("t".into(), source_code, &level.computer).unwrap()
+    }
+
+    #[test]
+    fn solutions_pass_every_case() {
+        let level = Level::load_from(ARRAY_SUM).unwrap();
+        let program = assemble(&level, ARRAY_SUM_SOLUTION);
+
+        let results = level.run(&program);
+        assert_eq!(results.len(), level.cases as usize);
+
+        let scores = Level::scores(&results, &program).unwrap();
+        assert_eq!(scores.size, 3);
+        assert!(scores.runtime > 20);
+    }
+
+    #[test]
+    fn wrong_programs_fail() {
+        let level = Level::load_from(ARRAY_SUM).unwrap();
+
+        let program = assemble(&level, "SET X 1");
+        let results = level.run(&program);
+
+        assert!(matches!(
+            results[0].failure,
+            Some(CaseFailure::IncorrectValue { got: 1, .. })
+        ));
+        assert_eq!(Level::scores(&results, &program), None);
+
+        let program = assemble(&level, "LBL L\nJMP L");
+        assert!(matches!(
+            level.run_case(&program, 0).failure,
+            Some(CaseFailure::TickLimit(10000))
+        ));
+    }
+
+    #[test]
+    fn random_inputs_depend_on_the_case() {
+        let level = Level::load_from(ARRAY_SUM).unwrap();
+        let d = computer::register_with_name('D').unwrap();
+
+        let inputs = |case| {
+            let computer = level.setup_case(case);
+            let values = computer.registers.get(d).unwrap().all_values();
+
+            values.iter().map(|value| value.get()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(inputs(0), inputs(0));
+        assert_ne!(inputs(0), inputs(1));
+        assert!(inputs(0)[..20].iter().all(|value| (0..=99).contains(value)));
+        assert!(inputs(0)[20..].iter().all(|&value| value == 0));
+    }
+
+    #[test]
+    fn sums_too_big_for_a_register_do_not_overflow() {
+        // 22 of the biggest values an 8 digit register holds add up to more than `Integer::MAX`
+        let values = vec!["99999999"; 22].join(", ");
+        let level = Level::load_from(&format!(
+            "name = \"Big Sum\"
+            machine = {{ digits = 8, registers = {{ D = {{ length = 22 }}, X = {{}} }} }}
+            inputs.D.values = [{values}]
+            outputs.X.sum = \"D\""
+        ))
+        .unwrap();
+
+        let program = assemble(&level, "SET X 99999999");
+        assert!(matches!(
+            level.run_case(&program, 0).failure,
+            Some(CaseFailure::IncorrectValue {
+                expected: Integer::MAX,
+                got: 99_999_999,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn predicate_outputs_check_every_value() {
+        let level = |d: &str| {
+            Level::load_from(&format!(
+                "name = \"Predicates\"
+                machine = {{ digits = 3, registers = {{ D = {{ length = 4 }}, X = {{}} }} }}
+                inputs.D.values = [{d}]
+                outputs.D = {{ ascending = true, length = 3 }}
+                outputs.X.range = {{ minimum = 10, maximum = 20 }}"
+            ))
+            .unwrap()
+        };
+
+        let run = |d, source_code| {
+            let level = level(d);
+            level.run_case(&assemble(&level, source_code), 0).failure
+        };
+
+        // Only the values up to the length have to be ascending
+        assert!(run("1, 1, 2, -1", "SET X 10").is_none());
+        assert!(run("1, 1, 2", "SET X 20").is_none());
+
+        assert!(matches!(
+            run("2, 1", "SET X 10"),
+            Some(CaseFailure::Descending {
+                index: 1,
+                previous: 2,
+                got: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            run("1, 2, 3", "SET X 21"),
+            Some(CaseFailure::OutOfRange { got: 21, .. })
+        ));
+    }
+
+    #[test]
+    fn predicate_outputs_must_be_valid() {
+        let errors = Level::load_from(
+            "name = \"Predicates\"
+            machine = { digits = 3, registers = { D = { length = 4 }, X = {} } }
+            outputs.D.ascending = false
+            outputs.X.range = { minimum = 10, maximum = 5 }",
+        )
+        .unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].entry, "outputs.D.ascending");
+        assert_eq!(errors[1].entry, "outputs.X.range.maximum");
+    }
+
+    #[test]
+    fn sequences_start_correctly() {
+        assert_eq!(Sequence::Fibonacci.first(7), [1, 1, 2, 3, 5, 8, 13]);
+        assert_eq!(
+            Sequence::Kolakoski.first(10),
+            [1, 2, 2, 1, 1, 2, 1, 2, 2, 1]
+        );
+        assert_eq!(Sequence::Primes.first(6), [2, 3, 5, 7, 11, 13]);
+        assert_eq!(Sequence::Kolakoski.first(2), [1, 2]);
+    }
+}
@@ -0,0 +1,565 @@
+use std::num::NonZeroU8;
+
+use super::{
+    computer::{
+        self, BlockCondition, Channel, Computer, Register, RegisterSet, RegisterValues, ReturnStack,
+    },
+    definition::{self, DefinitionError, DefinitionErrorKind, Entry},
+    instruction::{self, InstructionKindMap, InstructionProperties},
+    integer::{DigitInteger, Integer, OverflowMode},
+};
+
+impl Computer {
+    /// Builds a computer from a machine definition file (see `resources/machines` for examples).
+    pub fn load_from(source_code: &str) -> Result<Self, Vec<DefinitionError>> {
+        let table = definition::parse(source_code)?;
+
+        let mut errors = Vec::new();
+
+        let computer = Self::load_from_entry(Entry::new(String::new(), &table, &mut errors));
+
+        match computer {
+            Some(computer) if errors.is_empty() => Ok(computer),
+            _ => Err(errors),
+        }
+    }
+
+    /// Builds a computer from a machine definition within another definition file. Any errors are
+    /// added to the entry's list, and the computer should only be used if there were none.
+    pub fn load_from_entry(mut root: Entry) -> Option<Self> {
+        let maximum_digits = root
+            .integer("digits", 0, DigitInteger::MAXIMUM_DIGITS as i64)
+            .map(|digits| digits as u8);
+
+        let overflow = optional_overflow_mode(&mut root);
+        let registers = root.table("registers");
+        let instructions = root.optional_table("instructions");
+
+        let registers = registers
+            .zip(maximum_digits)
+            .map(|(registers, maximum_digits)| {
+                load_registers(
+                    root.child("registers", registers),
+                    maximum_digits,
+                    overflow.flatten().unwrap_or_default(),
+                )
+            })
+            .unwrap_or_default();
+
+        let instruction_properties = instructions
+            .map(|instructions| load_instructions(root.child("instructions", instructions)))
+            .unwrap_or(instruction::DEFAULT_INSTRUCTIONS);
+
+        let return_stack_depth = root.optional_integer("return_stack_depth", 0, u16::MAX.into());
+        let fault_register = root.optional_register("fault_register");
+
+        if let Some(Some(register)) = fault_register {
+            match registers.get(register) {
+                Some(register_data) if register_data.is_vector() => {
+                    root.error("fault_register", DefinitionErrorKind::VectorFaultRegister);
+                }
+                Some(_) => (),
+                None => root.error(
+                    "fault_register",
+                    DefinitionErrorKind::UndefinedRegister(register),
+                ),
+            }
+        }
+
+        root.finish(&[
+            "digits",
+            "overflow",
+            "return_stack_depth",
+            "fault_register",
+            "registers",
+            "instructions",
+        ]);
+
+        let mut computer = Computer::new(maximum_digits?, registers, instruction_properties);
+
+        if let Some(depth) = return_stack_depth? {
+            computer.return_stack = ReturnStack::new(depth as u32);
+        }
+
+        computer.fault_handlers.code_register = fault_register?;
+
+        Some(computer)
+    }
+}
+
+fn load_registers(mut entry: Entry, maximum_digits: u8, overflow: OverflowMode) -> RegisterSet {
+    let mut registers = RegisterSet::new_empty();
+
+    let table = entry.table;
+
+    let mut names = table.keys().collect::<Vec<_>>();
+    names.sort_unstable();
+
+    for name in &names {
+        let Some(index) = definition::register_index(name) else {
+            entry.error(name, DefinitionErrorKind::NoSuchRegister(name.to_string()));
+            continue;
+        };
+
+        let Some(register_table) = entry.as_table(name, &table[*name]) else {
+            continue;
+        };
+
+        if let Some(register) =
+            load_register(entry.child(name, register_table), maximum_digits, overflow)
+        {
+            registers.add_register(index, register).unwrap();
+        }
+    }
+
+    // Links can only be checked once every register is known
+    for (i, register) in registers.registers.iter().enumerate() {
+        let Some(register) = register else {
+            continue;
+        };
+
+        let name = computer::name_of_register(i as u32).unwrap();
+
+        if let Some(array) = register.indexes_array {
+            match registers.get(array) {
+                Some(indexed_register) if indexed_register.is_vector() => (),
+                Some(_) => entry.error(
+                    &format!("{name}.indexes_array"),
+                    DefinitionErrorKind::NotAVector(array),
+                ),
+                None => entry.error(
+                    &format!("{name}.indexes_array"),
+                    DefinitionErrorKind::UndefinedRegister(array),
+                ),
+            }
+        }
+
+        if let Some(index_register) = register.indexed_by {
+            if !register.is_vector() {
+                entry.error(
+                    &format!("{name}.indexed_by"),
+                    DefinitionErrorKind::NotAVector(i as u32),
+                );
+            }
+
+            match registers.get(index_register) {
+                Some(index_register_data) => {
+                    if index_register_data.indexes_array != Some(i as u32) {
+                        entry.error(
+                            &format!("{name}.indexed_by"),
+                            DefinitionErrorKind::UnlinkedIndex {
+                                array: i as u32,
+                                index: index_register,
+                            },
+                        );
+                    }
+                }
+                None => entry.error(
+                    &format!("{name}.indexed_by"),
+                    DefinitionErrorKind::UndefinedRegister(index_register),
+                ),
+            }
+        }
+    }
+
+    registers
+}
+
+/// `overflow` is used unless the register has its own overflow mode.
+fn load_register(mut entry: Entry, maximum_digits: u8, overflow: OverflowMode) -> Option<Register> {
+    let digits = entry.optional_integer("digits", 0, maximum_digits.into());
+    let length = entry.optional_integer("length", 1, Register::MAXIMUM_LENGTH as i64);
+    let offset = entry.optional_integer("offset", Integer::MIN.into(), Integer::MAX.into());
+    let indexed_by = entry.optional_register("indexed_by");
+    let indexes_array = entry.optional_register("indexes_array");
+    let read_time = entry.optional_integer("read_time", 0, u32::MAX.into());
+    let write_time = entry.optional_integer("write_time", 0, u32::MAX.into());
+    let channel = entry.optional_boolean("channel");
+    let seeking = entry.optional_boolean("seeking");
+    let register_overflow = optional_overflow_mode(&mut entry);
+    let block_condition = match entry.optional_table("block_condition") {
+        Some(table) => load_block_condition(entry.child("block_condition", table)).map(Some),
+        None => Some(None),
+    };
+
+    if let (Some(Some(true)), Some(Some(_))) = (channel, length) {
+        entry.error("channel", DefinitionErrorKind::VectorChannel);
+    }
+
+    if let Some(Some(true)) = seeking {
+        if let Some(Some(_)) = length {
+            entry.error("seeking", DefinitionErrorKind::VectorSeeking);
+        }
+
+        if let Some(Some(true)) = channel {
+            entry.error("seeking", DefinitionErrorKind::SeekingChannel);
+        }
+    }
+
+    entry.finish(&[
+        "digits",
+        "length",
+        "offset",
+        "indexed_by",
+        "indexes_array",
+        "read_time",
+        "write_time",
+        "channel",
+        "seeking",
+        "overflow",
+        "block_condition",
+    ]);
+
+    let digits = digits?.map_or(maximum_digits, |digits| digits as u8);
+    let value = DigitInteger::new(0, digits).unwrap();
+
+    let values = match length? {
+        Some(length) => RegisterValues::Vector {
+            values: vec![value; length as usize].into_boxed_slice(),
+            index: 0,
+            offset: offset?.unwrap_or(0) as Integer,
+        },
+        None => RegisterValues::Scalar(value),
+    };
+
+    Some(Register {
+        values,
+        block_condition: block_condition?,
+        indexes_array: indexes_array?,
+        indexed_by: indexed_by?,
+        read_time: read_time?.unwrap_or(0) as u32,
+        write_time: write_time?.unwrap_or(0) as u32,
+        channel: channel?.unwrap_or(false).then(Channel::default),
+        seeking: seeking?.unwrap_or(false),
+        overflow: register_overflow?.unwrap_or(overflow),
+        ..Register::DEFAULT
+    })
+}
+
+/// Reads the `overflow` entry, which is `"trap"`, `"wrap"` or `"saturate"`.
+fn optional_overflow_mode(entry: &mut Entry) -> Option<Option<OverflowMode>> {
+    match entry.optional_string("overflow")? {
+        Some(name) => match OverflowMode::from_name(name) {
+            Some(mode) => Some(Some(mode)),
+            None => {
+                entry.error(
+                    "overflow",
+                    DefinitionErrorKind::NoSuchOverflowMode(name.to_owned()),
+                );
+                None
+            }
+        },
+        None => Some(None),
+    }
+}
+
+fn load_instructions(mut instructions: Entry) -> InstructionKindMap<InstructionProperties> {
+    let mut instruction_properties = instruction::DEFAULT_INSTRUCTIONS;
+
+    let table = instructions.table;
+
+    let mut names = table.keys().collect::<Vec<_>>();
+    names.sort_unstable();
+
+    for name in names {
+        let Some(&default_properties) =
+            instruction::DEFAULT_INSTRUCTIONS.instruction_with_name(name)
+        else {
+            instructions.error(
+                name,
+                DefinitionErrorKind::NoSuchInstruction(name.to_owned()),
+            );
+            continue;
+        };
+
+        let Some(instruction_table) = instructions.as_table(name, &table[name]) else {
+            continue;
+        };
+
+        let mut entry = instructions.child(name, instruction_table);
+
+        let enabled = entry.optional_boolean("enabled");
+        let base_time = entry.optional_integer("base_time", 0, u32::MAX.into());
+        let conditional_time = entry.optional_integer("conditional_time", 0, u32::MAX.into());
+        let base_energy = entry.optional_integer("base_energy", 0, u32::MAX.into());
+        let conditional_energy = entry.optional_integer("conditional_energy", 0, u32::MAX.into());
+        let calls_per_tick_limit =
+            entry.optional_integer("calls_per_tick_limit", 0, u8::MAX.into());
+
+        if let Some(Some(_)) = conditional_time
+            && default_properties.conditional_time.is_none()
+        {
+            entry.error("conditional_time", DefinitionErrorKind::NoConditionalTime);
+        }
+
+        if let Some(Some(_)) = conditional_energy
+            && default_properties.conditional_time.is_none()
+        {
+            entry.error("conditional_energy", DefinitionErrorKind::NoConditionalTime);
+        }
+
+        entry.finish(&[
+            "enabled",
+            "base_time",
+            "conditional_time",
+            "base_energy",
+            "conditional_energy",
+            "calls_per_tick_limit",
+        ]);
+
+        let properties = &mut instruction_properties[default_properties.kind];
+
+        if let Some(Some(enabled)) = enabled {
+            properties.enabled = enabled;
+        }
+
+        if let Some(Some(base_time)) = base_time {
+            properties.base_time = base_time as u32;
+        }
+
+        if let (Some(Some(time)), Some((_, condition))) =
+            (conditional_time, properties.conditional_time)
+        {
+            properties.conditional_time = Some((time as u32, condition));
+        }
+
+        if let Some(Some(base_energy)) = base_energy {
+            properties.base_energy = base_energy as u32;
+        }
+
+        if let Some(Some(conditional_energy)) = conditional_energy {
+            properties.conditional_energy = Some(conditional_energy as u32);
+        }
+
+        if let Some(Some(limit)) = calls_per_tick_limit {
+            properties.calls_per_tick_limit = NonZeroU8::new(limit as u8);
+        }
+    }
+
+    instruction_properties
+}
+
+fn load_block_condition(mut entry: Entry) -> Option<BlockCondition> {
+    let kind = entry.string("kind");
+
+    let block_condition = match kind? {
+        "index_change" => {
+            let minimum_change = entry.integer("minimum_change", 0, u32::MAX.into());
+            let block_time = entry.integer("block_time", 0, u32::MAX.into());
+
+            entry.finish(&["kind", "minimum_change", "block_time"]);
+
+            BlockCondition::IndexChange {
+                minimum_change: minimum_change? as u32,
+                block_time: block_time? as u32,
+            }
+        }
+        "sequential" => {
+            let block_time = entry.integer("block_time", 0, u32::MAX.into());
+
+            entry.finish(&["kind", "block_time"]);
+
+            BlockCondition::Sequential {
+                block_time: block_time? as u32,
+            }
+        }
+        "cache" => {
+            let line_length = entry.integer("line_length", 1, Register::MAXIMUM_LENGTH as i64);
+            let lines = entry.integer("lines", 1, BlockCondition::MAXIMUM_CACHE_LINES as i64);
+            let miss_time = entry.integer("miss_time", 0, u32::MAX.into());
+
+            entry.finish(&["kind", "line_length", "lines", "miss_time"]);
+
+            BlockCondition::Cache {
+                line_length: line_length? as u32,
+                lines: lines? as u32,
+                miss_time: miss_time? as u32,
+            }
+        }
+        "write_back" => {
+            let latency = entry.integer("latency", 0, u32::MAX.into());
+
+            entry.finish(&["kind", "latency"]);
+
+            BlockCondition::WriteBack {
+                latency: latency? as u32,
+            }
+        }
+        "contention" => {
+            let accesses = entry.integer("accesses", 1, u32::MAX.into());
+            let cooldown = entry.integer("cooldown", 0, u32::MAX.into());
+
+            entry.finish(&["kind", "accesses", "cooldown"]);
+
+            BlockCondition::Contention {
+                accesses: accesses? as u32,
+                cooldown: cooldown? as u32,
+            }
+        }
+        kind => {
+            entry.error(
+                "kind",
+                DefinitionErrorKind::NoSuchBlockCondition(kind.to_owned()),
+            );
+            return None;
+        }
+    };
+
+    Some(block_condition)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DEFAULT_MACHINE, DEFAULT_MACHINE_WITH_H};
+
+    /// Loads the default machine with `extra` added to it, and returns the entry and kind of each
+    /// error.
+    fn errors(extra: &str) -> Vec<(String, DefinitionErrorKind)> {
+        Computer::load_from(&format!("{DEFAULT_MACHINE}\n{extra}"))
+            .expect_err("the machine should be invalid")
+            .into_iter()
+            .map(|error| (error.entry, error.kind))
+            .collect()
+    }
+
+    #[test]
+    fn machines_load_from_definitions() {
+        let computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+
+        let d = computer
+            .registers
+            .get(computer::register_with_name('D').unwrap());
+        assert!(
+            matches!(&d.unwrap().values, RegisterValues::Vector { values, .. } if values.len() == 100)
+        );
+        assert!(
+            computer
+                .registers
+                .get(computer::register_with_name('H').unwrap())
+                .is_some()
+        );
+        assert!(
+            computer
+                .registers
+                .get(computer::register_with_name('W').unwrap())
+                .is_none()
+        );
+
+        let computer = Computer::load_from(&format!(
+            "{DEFAULT_MACHINE}\n[instructions.MUL]\nbase_time = 7\nenabled = false"
+        ))
+        .unwrap();
+
+        let mul = instruction::DEFAULT_INSTRUCTIONS
+            .instruction_with_name("MUL")
+            .unwrap()
+            .kind;
+        assert_eq!(computer.instruction_properties[mul].base_time, 7);
+        assert!(!computer.instruction_properties[mul].enabled);
+        assert!(
+            (computer.instruction_properties)
+                .instruction_with_name("MUL")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn invalid_registers_are_reported_with_their_entry() {
+        let errors = errors("[registers.AB]\n[registers.Q]\ndigits = 4\ncolour = \"red\"");
+
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert!(matches!(
+            &errors[0],
+            (entry, DefinitionErrorKind::NoSuchRegister(name)) if entry == "registers.AB" && name == "AB"
+        ));
+        assert!(matches!(
+            &errors[1],
+            (entry, DefinitionErrorKind::OutOfRange { got: 4, minimum: 0, maximum: 3 })
+                if entry == "registers.Q.digits"
+        ));
+        assert!(matches!(
+            &errors[2],
+            (entry, DefinitionErrorKind::UnexpectedEntry) if entry == "registers.Q.colour"
+        ));
+    }
+
+    #[test]
+    fn oversized_registers_are_rejected() {
+        let errors = errors("[registers.Q]\nlength = 4294967295");
+
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(matches!(
+            &errors[0],
+            (entry, DefinitionErrorKind::OutOfRange { got: 4294967295, minimum: 1, maximum })
+                if entry == "registers.Q.length" && *maximum == Register::MAXIMUM_LENGTH as i64
+        ));
+    }
+
+    #[test]
+    fn oversized_cache_lines_are_rejected() {
+        let errors = errors(
+            "[registers.Q]\nlength = 10\n[registers.Q.block_condition]\nkind = \"cache\"\n\
+             line_length = 4294967295\nlines = 1\nmiss_time = 1",
+        );
+
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(matches!(
+            &errors[0],
+            (entry, DefinitionErrorKind::OutOfRange { got: 4294967295, minimum: 1, maximum })
+                if entry == "registers.Q.block_condition.line_length"
+                    && *maximum == Register::MAXIMUM_LENGTH as i64
+        ));
+    }
+
+    #[test]
+    fn invalid_machines_are_reported_with_their_entry() {
+        let errors = Computer::load_from("digits = 12\nspeed = 1\n[registers.X]").unwrap_err();
+
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert_eq!(errors[0].entry, "digits");
+        assert!(matches!(
+            errors[0].kind,
+            DefinitionErrorKind::OutOfRange { got: 12, minimum: 0, maximum } if maximum == DigitInteger::MAXIMUM_DIGITS as i64
+        ));
+        assert_eq!(errors[1].entry, "speed");
+        assert!(matches!(
+            errors[1].kind,
+            DefinitionErrorKind::UnexpectedEntry
+        ));
+
+        let errors = Computer::load_from("digits = 3\n[registers").unwrap_err();
+        assert_eq!(errors[0].entry, "");
+        assert!(matches!(errors[0].kind, DefinitionErrorKind::Syntax(_)));
+    }
+
+    #[test]
+    fn invalid_instructions_are_reported_with_their_entry() {
+        let errors = errors(
+            "[instructions.FOO]\n\
+             [instructions.ADD]\n\
+             conditional_time = 1\n\
+             conditional_energy = 1\n\
+             [instructions.DIV]\n\
+             base_time = -1",
+        );
+
+        assert_eq!(errors.len(), 4, "{errors:?}");
+        assert!(matches!(
+            &errors[0],
+            (entry, DefinitionErrorKind::NoConditionalTime) if entry == "instructions.ADD.conditional_time"
+        ));
+        assert!(matches!(
+            &errors[1],
+            (entry, DefinitionErrorKind::NoConditionalTime) if entry == "instructions.ADD.conditional_energy"
+        ));
+        assert!(matches!(
+            &errors[2],
+            (entry, DefinitionErrorKind::OutOfRange { got: -1, .. }) if entry == "instructions.DIV.base_time"
+        ));
+        assert!(matches!(
+            &errors[3],
+            (entry, DefinitionErrorKind::NoSuchInstruction(name)) if entry == "instructions.FOO" && name == "FOO"
+        ));
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt::Display;
+
+use super::{
+    computer::{self, Channel, Computer},
+    program::Program,
+};
+
+/// Computers which run in lockstep, passing values to each other through linked channel
+/// registers.
+///
+/// Every computer runs one tick before any values are passed, so the order of the nodes never
+/// affects the timing.
+#[derive(Clone, Debug, Default)]
+pub struct Network {
+    pub nodes: Vec<Node>,
+    links: Vec<[Endpoint; 2]>,
+    pub runtime: u64,
+    deadlocked: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub computer: Computer,
+    pub program: Program,
+}
+
+/// A channel register on a node of a network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    pub node: usize,
+    pub register: u32,
+}
+
+impl Network {
+    /// Returns the index of the new node.
+    pub fn add_node(&mut self, computer: Computer, program: Program) -> usize {
+        self.nodes.push(Node { computer, program });
+        self.nodes.len() - 1
+    }
+
+    /// Connects two channel registers, so that values written to one can be read from the other.
+    pub fn link(&mut self, a: Endpoint, b: Endpoint) -> Result<(), LinkError> {
+        for endpoint in [a, b] {
+            let node = self
+                .nodes
+                .get(endpoint.node)
+                .ok_or(LinkError::NoSuchNode(endpoint.node))?;
+
+            if node
+                .computer
+                .registers
+                .get(endpoint.register)
+                .and_then(|register| register.channel)
+                .is_none()
+            {
+                return Err(LinkError::NotAChannel(endpoint));
+            }
+
+            if a == b
+                || self
+                    .links
+                    .iter()
+                    .flatten()
+                    .any(|&linked| linked == endpoint)
+            {
+                return Err(LinkError::AlreadyLinked(endpoint));
+            }
+        }
+
+        self.links.push([a, b]);
+
+        Ok(())
+    }
+
+    /// Returns `true` once every computer has been interrupted.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.nodes
+            .iter()
+            .all(|node| node.computer.interrupt.is_some())
+    }
+
+    /// Returns `true` if every running computer is waiting on a channel, and no values were
+    /// passed during the last tick.
+    #[must_use]
+    pub fn is_deadlocked(&self) -> bool {
+        self.deadlocked
+    }
+
+    pub fn step_tick(&mut self) {
+        for node in &mut self.nodes {
+            if node.computer.interrupt.is_none() {
+                node.computer.step_tick(&node.program);
+            }
+        }
+
+        let mut passed_values = false;
+
+        for i in 0..self.links.len() {
+            let [a, b] = self.links[i];
+
+            passed_values |= self.pass_value(a, b);
+            passed_values |= self.pass_value(b, a);
+        }
+
+        self.deadlocked = !passed_values
+            && !self.is_finished()
+            && self
+                .nodes
+                .iter()
+                .filter(|node| node.computer.interrupt.is_none())
+                .all(|node| node.computer.waiting_for_channel.is_some());
+
+        self.runtime += 1;
+    }
+
+    /// Runs until every computer is interrupted, the network is deadlocked, or the runtime
+    /// reaches `tick_limit`.
+    pub fn run(&mut self, tick_limit: Option<u64>) {
+        while !self.is_finished() && !self.deadlocked {
+            if tick_limit.is_some_and(|limit| self.runtime >= limit) {
+                break;
+            }
+
+            self.step_tick();
+        }
+    }
+
+    /// Moves a value one step from the sender to the receiver. Returns whether or not anything
+    /// changed.
+    fn pass_value(&mut self, from: Endpoint, to: Endpoint) -> bool {
+        let mut sender = self.channel(from);
+        let mut receiver = self.channel(to);
+
+        match sender.outgoing {
+            // Pass the value on once the receiver has read the last one
+            Some(value) if !sender.delivered && receiver.incoming.is_none() => {
+                receiver.incoming = Some(value);
+                sender.delivered = true;
+            }
+            // The receiver has read the value, so the sender can continue
+            Some(_) if sender.delivered && receiver.incoming.is_none() => {
+                sender.outgoing = None;
+                sender.delivered = false;
+            }
+            _ => return false,
+        }
+
+        *self.channel_mut(from) = sender;
+        *self.channel_mut(to) = receiver;
+
+        true
+    }
+
+    fn channel(&self, endpoint: Endpoint) -> Channel {
+        self.nodes[endpoint.node]
+            .computer
+            .registers
+            .get(endpoint.register)
+            .and_then(|register| register.channel)
+            .unwrap()
+    }
+
+    fn channel_mut(&mut self, endpoint: Endpoint) -> &mut Channel {
+        self.nodes[endpoint.node].computer.registers.registers[endpoint.register as usize]
+            .as_mut()
+            .and_then(|register| register.channel.as_mut())
+            .unwrap()
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{node}:{register}",
+            node = self.node + 1,
+            register = computer::name_of_register(self.register).unwrap_or('?'),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LinkError {
+    NoSuchNode(usize),
+    NotAChannel(Endpoint),
+    AlreadyLinked(Endpoint),
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::NoSuchNode(node) => write!(f, "There is no computer {}", node + 1),
+            LinkError::NotAChannel(endpoint) => write!(f, "{endpoint} is not a channel register"),
+            LinkError::AlreadyLinked(endpoint) => write!(f, "{endpoint} is already linked"),
+        }
+    }
+}
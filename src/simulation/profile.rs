@@ -0,0 +1,234 @@
+use std::{cmp::Reverse, collections::BTreeMap, fmt::Write, ops::AddAssign};
+
+use super::{
+    program::Program,
+    source::{SourceLocation, SourceSet},
+};
+
+/// What each instruction of a program cost while it ran, recorded by a
+/// [`Computer`](super::computer::Computer) with profiling enabled.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    /// Indexed by the instruction
+    pub instructions: Vec<InstructionProfile>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InstructionProfile {
+    pub runs: u64,
+    /// Includes the ticks spent waiting for registers, channels and `calls_per_tick_limit`
+    pub ticks: u64,
+    pub energy: u64,
+    /// The ticks spent waiting for a blocked register or a channel
+    pub blocked_ticks: u64,
+}
+
+impl AddAssign for InstructionProfile {
+    fn add_assign(&mut self, rhs: Self) {
+        self.runs += rhs.runs;
+        self.ticks += rhs.ticks;
+        self.energy += rhs.energy;
+        self.blocked_ticks += rhs.blocked_ticks;
+    }
+}
+
+impl Profile {
+    /// The number of lines listed as the hottest in a report.
+    pub const HOT_LINES: usize = 10;
+
+    pub fn instruction_mut(&mut self, instruction: u32) -> &mut InstructionProfile {
+        let index = instruction as usize;
+
+        if index >= self.instructions.len() {
+            self.instructions
+                .resize(index + 1, InstructionProfile::default());
+        }
+
+        &mut self.instructions[index]
+    }
+
+    /// Adds up the cost of the instructions on each line, since a line using a macro can have more
+    /// than one.
+    #[must_use]
+    pub fn lines(&self, program: &Program) -> BTreeMap<SourceLocation, InstructionProfile> {
+        let mut lines = BTreeMap::<_, InstructionProfile>::new();
+
+        for (instruction, &profile) in program.instructions.iter().zip(&self.instructions) {
+            *lines.entry(instruction.location).or_default() += profile;
+        }
+
+        lines
+    }
+
+    /// Lists the lines which took the most ticks, followed by the source of each file with the
+    /// cost of each line alongside.
+    #[must_use]
+    pub fn report(&self, program: &Program, sources: &SourceSet) -> String {
+        let lines = self.lines(program);
+
+        let total = lines
+            .values()
+            .fold(InstructionProfile::default(), |mut total, &line| {
+                total += line;
+                total
+            });
+
+        let mut hot_lines = (lines.iter())
+            .filter(|(_, line)| line.runs > 0)
+            .collect::<Vec<_>>();
+        hot_lines.sort_by_key(|&(location, line)| {
+            (Reverse(line.ticks), Reverse(line.energy), *location)
+        });
+
+        let mut report = String::from("Hottest lines:\n");
+
+        for (rank, &(&location, line)) in hot_lines.iter().take(Self::HOT_LINES).enumerate() {
+            let share = if total.ticks > 0 {
+                line.ticks as f64 / total.ticks as f64 * 100.0
+            } else {
+                0.0
+            };
+
+            writeln!(
+                report,
+                "{rank:>3}. {location:<16} {ticks:>8} ticks ({share:>5.1}%) {energy:>8} energy \
+                 {blocked:>8} blocked   {source}",
+                rank = rank + 1,
+                location = program.describe_location(location),
+                ticks = line.ticks,
+                energy = line.energy,
+                blocked = line.blocked_ticks,
+                source = sources.line(location).unwrap_or("").trim(),
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            report,
+            "\nTotal: {runs} instructions run, {ticks} ticks, {energy} energy, {blocked} ticks \
+             blocked",
+            runs = total.runs,
+            ticks = total.ticks,
+            energy = total.energy,
+            blocked = total.blocked_ticks,
+        )
+        .unwrap();
+
+        let width = [total.runs, total.ticks, total.energy, total.blocked_ticks]
+            .into_iter()
+            .map(|value| value.to_string().len())
+            .chain(["blocked".len()])
+            .max()
+            .unwrap();
+
+        for (file, source) in sources.files.iter().enumerate() {
+            writeln!(
+                report,
+                "\n{name}:\n{runs:>width$} {ticks:>width$} {energy:>width$} {blocked:>width$} |",
+                name = source.name,
+                runs = "runs",
+                ticks = "ticks",
+                energy = "energy",
+                blocked = "blocked",
+            )
+            .unwrap();
+
+            for (line, text) in source.text.lines().enumerate() {
+                let location = SourceLocation {
+                    file: file as u32,
+                    line: line as u32,
+                };
+
+                match lines.get(&location) {
+                    Some(line) => write!(
+                        report,
+                        "{runs:>width$} {ticks:>width$} {energy:>width$} {blocked:>width$} |",
+                        runs = line.runs,
+                        ticks = line.ticks,
+                        energy = line.energy,
+                        blocked = line.blocked_ticks,
+                    )
+                    .unwrap(),
+                    None => write!(report, "{:>1$} |", "", width * 4 + 3).unwrap(),
+                }
+
+                writeln!(report, " {text}").unwrap();
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DEFAULT_MACHINE_WITH_H, simulation::computer::Computer};
+
+    const SOURCE_CODE: &str = "MAC STEP
+ADD X 1 X
+ADD M 2 M
+EMC
+LBL LOOP
+STEP
+SET H X
+LJP X < 5 LOOP";
+
+    fn profiled() -> (Computer, Program) {
+        let mut computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        let program = Program::assemble_from("t".into(), SOURCE_CODE, &computer).unwrap();
+        computer.profile = Some(Box::default());
+
+        while computer.interrupt.is_none() {
+            computer.step_tick(&program);
+        }
+
+        (computer, program)
+    }
+
+    fn line(number: u32) -> SourceLocation {
+        SourceLocation {
+            file: 0,
+            line: number,
+        }
+    }
+
+    #[test]
+    fn costs_are_attributed_to_lines() {
+        let (computer, program) = profiled();
+        let lines = computer.profile.as_ref().unwrap().lines(&program);
+
+        // Both instructions of the macro are counted on the line using it
+        assert_eq!(lines[&line(5)].runs, 10);
+        assert_eq!(lines[&line(6)].runs, 5);
+
+        // Moving M by 2 blocks H, which is written on the next line
+        assert!(lines[&line(6)].blocked_ticks > 0);
+        assert_eq!(lines[&line(5)].blocked_ticks, 0);
+
+        let total = lines
+            .values()
+            .fold(InstructionProfile::default(), |mut total, &line| {
+                total += line;
+                total
+            });
+
+        assert_eq!(total.energy, computer.energy_used);
+        // The last tick is spent stopping, rather than on an instruction
+        assert_eq!(total.ticks + 1, computer.runtime);
+    }
+
+    #[test]
+    fn reports_list_the_hottest_lines_first() {
+        let (computer, program) = profiled();
+        let sources = SourceSet::single("t".into(), SOURCE_CODE.into());
+        let report = computer
+            .profile
+            .as_ref()
+            .unwrap()
+            .report(&program, &sources);
+
+        let hottest = report.lines().nth(1).unwrap();
+        assert!(hottest.ends_with("SET H X"), "{report}");
+    }
+}
@@ -1,21 +1,54 @@
 use std::{
-    array, cmp::Ordering, collections::HashMap, fmt::Display, iter::Peekable, num::ParseIntError,
+    array,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    iter::Peekable,
+    num::ParseIntError,
 };
 
 use super::{
     argument::{Argument, Comparison, NumberSource},
     computer::{self, Computer},
-    instruction::{ArgumentRequirement, Instruction, InstructionKind},
+    instruction::{ArgumentRequirement, FaultClass, Instruction, InstructionKind},
     integer::{self, AssignIntegerError, DigitInteger, Integer},
+    source::{self, SourceLocation, SourceSet},
 };
 
+use strum::EnumCount;
+
 pub const COMMENT_SEPARATOR: char = ';';
 pub const LABEL_PSEUDO_INSTRUCTION: &str = "LBL";
+pub const CONSTANT_PSEUDO_INSTRUCTION: &str = "DEF";
+pub const MACRO_PSEUDO_INSTRUCTION: &str = "MAC";
+pub const MACRO_END_PSEUDO_INSTRUCTION: &str = "EMC";
+pub const INCLUDE_PSEUDO_INSTRUCTION: &str = "INC";
+pub const EXPORT_PSEUDO_INSTRUCTION: &str = "EXP";
+
+pub const PSEUDO_INSTRUCTIONS: [&str; 6] = [
+    LABEL_PSEUDO_INSTRUCTION,
+    CONSTANT_PSEUDO_INSTRUCTION,
+    MACRO_PSEUDO_INSTRUCTION,
+    MACRO_END_PSEUDO_INSTRUCTION,
+    INCLUDE_PSEUDO_INSTRUCTION,
+    EXPORT_PSEUDO_INSTRUCTION,
+];
+
+/// Separates the name of a file from a label in it, for labels of included files which aren't
+/// exported.
+pub const FILE_LABEL_SEPARATOR: char = ':';
 
 #[derive(Clone, Debug)]
 pub struct Program {
     pub name: String,
     pub instructions: Vec<Instruction>,
+    /// The index of the instruction each label points to. Labels of included files which aren't
+    /// exported are prefixed by the name of their file.
+    pub labels: HashMap<String, u32>,
+    /// The value of each constant defined with `DEF`
+    pub constants: HashMap<String, Integer>,
+    /// The names of the files the program was assembled from, starting with the main file
+    pub files: Vec<String>,
 }
 
 impl Program {
@@ -24,6 +57,9 @@ impl Program {
         Self {
             name,
             instructions: Vec::new(),
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            files: Vec::new(),
         }
     }
 
@@ -33,107 +69,697 @@ impl Program {
         self
     }
 
+    /// Assembles a program from a single file, which can't include others.
     pub fn assemble_from(
         name: String,
         source_code: &str,
         target_computer: &Computer,
     ) -> Result<Self, Vec<ProgramAssemblyError>> {
-        let mut errors = Vec::new();
+        let sources = SourceSet::single(name.clone(), source_code.to_owned());
+
+        Self::assemble_from_sources(name, &sources, target_computer)
+    }
+
+    /// Assembles a program starting from the first file of `sources`, which may include the
+    /// others.
+    pub fn assemble_from_sources(
+        name: String,
+        sources: &SourceSet,
+        target_computer: &Computer,
+    ) -> Result<Self, Vec<ProgramAssemblyError>> {
+        let mut assembler = Assembler::new(sources, target_computer);
+
+        if !sources.files.is_empty() {
+            assembler.assemble_file(SourceLocation::MAIN_FILE);
+        }
+
+        assembler.finish(name)
+    }
+
+    /// Describes where a location is, naming the file if the program has more than one.
+    #[must_use]
+    pub fn describe_location(&self, location: SourceLocation) -> String {
+        match self.files.get(location.file as usize) {
+            Some(file) if self.files.len() > 1 => format!("line {} of {file}", location.line),
+            _ => format!("line {}", location.line),
+        }
+    }
+}
+
+/// The state of a program while it's being assembled, one line at a time.
+struct Assembler<'a, 'b> {
+    sources: &'a SourceSet,
+    target_computer: &'b Computer,
+    errors: Vec<ProgramAssemblyError>,
+
+    /// Files which have been included, so that each is only included once
+    included: HashSet<u32>,
+    instructions: Vec<InstructionIntermediate<'a>>,
+    labels: HashMap<LabelName<'a>, LabelIndex>,
+    duplicate_labels: HashMap<LabelName<'a>, Vec<LabelIndex>>,
+    exports: Vec<(LabelName<'a>, Span)>,
+    constants: HashMap<String, Integer>,
+    /// Where each constant was defined
+    constant_spans: HashMap<&'a str, Span>,
+    macros: HashMap<&'a str, Macro<'a>>,
+}
+
+/// A label within the namespace of its file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LabelName<'a> {
+    pub file: u32,
+    pub label: &'a str,
+}
+
+/// The labels which can be used from one file, which are its own and those exported by any file.
+#[derive(Clone, Copy, Debug)]
+pub struct LabelScope<'s, 'a> {
+    pub file: u32,
+    pub labels: &'s HashMap<LabelName<'a>, LabelIndex>,
+    /// The file of each exported label
+    pub exported: &'s HashMap<&'a str, u32>,
+}
+
+impl<'a> LabelScope<'_, 'a> {
+    #[must_use]
+    pub fn get(&self, label: &'a str) -> Option<&LabelIndex> {
+        let file = self.file;
 
-        let mut instructions = Vec::new();
-        let mut labels = HashMap::new();
+        self.labels.get(&LabelName { file, label }).or_else(|| {
+            let &file = self.exported.get(label)?;
+
+            self.labels.get(&LabelName { file, label })
+        })
+    }
 
-        let mut duplicate_labels = HashMap::<&str, Vec<LabelIndex>>::new();
+    pub fn visible_labels(&self) -> impl Iterator<Item = &'a str> {
+        let file = self.file;
+
+        (self.labels.keys())
+            .filter(move |name| name.file == file)
+            .map(|name| name.label)
+            .chain(self.exported.keys().copied())
+    }
+}
+
+impl<'a, 'b> Assembler<'a, 'b> {
+    fn new(sources: &'a SourceSet, target_computer: &'b Computer) -> Self {
+        Self {
+            sources,
+            target_computer,
+            errors: Vec::new(),
+
+            included: HashSet::new(),
+            instructions: Vec::new(),
+            labels: HashMap::new(),
+            duplicate_labels: HashMap::new(),
+            exports: Vec::new(),
+            constants: HashMap::new(),
+            constant_spans: HashMap::new(),
+            macros: HashMap::new(),
+        }
+    }
+
+    fn assemble_file(&mut self, file: u32) {
+        self.included.insert(file);
+
+        let source_code = &self.sources.files[file as usize].text;
+
+        // The macro whose body is being read
+        let mut macro_definition: Option<Macro> = None;
 
         for (i, line) in source_code.lines().enumerate() {
-            let i = i.try_into().unwrap();
+            let line = SourceLine {
+                file,
+                index: i.try_into().unwrap(),
+                text: line,
+                sources: self.sources,
+            };
+
+            let tokens = line.tokens();
 
-            match InstructionIntermediate::from_line(line, i, target_computer) {
-                Ok(instruction_result) => match instruction_result {
-                    ParseInstructionResult::Instruction(instruction) => {
-                        instructions.push(instruction);
+            let Some(definition) = &mut macro_definition else {
+                macro_definition = self.assemble_line(line, tokens, line.location(), &[]);
+                continue;
+            };
+
+            match tokens.first().copied() {
+                Some(MACRO_END_PSEUDO_INSTRUCTION) => {
+                    let arguments = tokens[1..].iter().copied().map(ArgumentIntermediate::Token);
+
+                    if let Err(error) = InstructionIntermediate::check_argument_length(
+                        &arguments.collect::<Vec<_>>(),
+                        0,
+                        0,
+                        line,
+                        tokens[0],
+                    ) {
+                        self.errors.push(error);
                     }
-                    ParseInstructionResult::Label(label) => {
-                        let label_position = LabelIndex {
-                            index: instructions.len().try_into().unwrap(),
-                            line: i,
-                        };
 
-                        if let Some(duplicate_label) = labels.insert(label, label_position) {
-                            if let Some(indecies) = duplicate_labels.get_mut(label) {
-                                // It has already been checked
-                                indecies.push(label_position);
-                            } else {
-                                duplicate_labels
-                                    .insert(label, vec![duplicate_label, label_position]);
-                            }
-                        }
+                    self.define_macro(macro_definition.take().unwrap());
+                }
+                Some(MACRO_PSEUDO_INSTRUCTION) => {
+                    self.errors.push(ProgramAssemblyError::new(
+                        line.span_of(tokens[0]),
+                        ProgramAssemblyErrorKind::NestedMacroDefinition,
+                    ));
+                }
+                Some(LABEL_PSEUDO_INSTRUCTION) => {
+                    // Every use of the macro would define the label again
+                    self.errors.push(ProgramAssemblyError::new(
+                        line.span_of(tokens[0]),
+                        ProgramAssemblyErrorKind::LabelInMacro,
+                    ));
+                }
+                _ => definition.body.push(line),
+            }
+        }
+
+        if let Some(definition) = macro_definition {
+            self.errors.push(ProgramAssemblyError::new(
+                definition.span,
+                ProgramAssemblyErrorKind::UnterminatedMacro(definition.name.to_owned()),
+            ));
+        }
+    }
+
+    /// Adds the contents of a line, expanding it if it's a call to a macro. `location` is given to
+    /// any instructions, which is the line of the outermost call for lines of a macro. Returns the
+    /// start of a macro definition, whose body is the lines which follow.
+    fn assemble_line(
+        &mut self,
+        line: SourceLine<'a>,
+        tokens: Vec<&'a str>,
+        location: SourceLocation,
+        expanding: &[&'a str],
+    ) -> Option<Macro<'a>> {
+        let result = match InstructionIntermediate::from_tokens(
+            line,
+            tokens,
+            self.target_computer,
+            &self.macros,
+        ) {
+            Ok(result) => result,
+            Err(error) => {
+                self.errors.push(error);
+                return None;
+            }
+        };
+
+        match result {
+            ParseInstructionResult::Instruction(mut instruction) => {
+                instruction.location = location;
+                self.instructions.push(instruction);
+            }
+            ParseInstructionResult::Label(label) => {
+                let span = line.span_of(label);
+
+                let label_position = LabelIndex {
+                    index: self.instructions.len().try_into().unwrap(),
+                    span,
+                };
+
+                // Labels belong to the file they're written in, even when used by a macro
+                let name = LabelName {
+                    file: span.file,
+                    label,
+                };
+
+                if let Some(duplicate_label) = self.labels.insert(name, label_position) {
+                    if let Some(indecies) = self.duplicate_labels.get_mut(&name) {
+                        // It has already been checked
+                        indecies.push(label_position);
+                    } else {
+                        self.duplicate_labels
+                            .insert(name, vec![duplicate_label, label_position]);
                     }
-                    ParseInstructionResult::Empty => (),
-                },
-                Err(error) => errors.push(error),
+                }
             }
+            ParseInstructionResult::Export(label) => {
+                let span = line.span_of(label);
+
+                self.exports.push((
+                    LabelName {
+                        file: span.file,
+                        label,
+                    },
+                    span,
+                ));
+            }
+            ParseInstructionResult::Include(path) => self.include(line, path),
+            ParseInstructionResult::Constant { name, value } => {
+                self.define_constant(line, name, value);
+            }
+            ParseInstructionResult::MacroDefinition { definition, error } => {
+                if expanding.is_empty() {
+                    self.errors.extend(error);
+                    return Some(definition);
+                }
+
+                self.errors.push(ProgramAssemblyError::new(
+                    definition.span,
+                    ProgramAssemblyErrorKind::NestedMacroDefinition,
+                ));
+            }
+            ParseInstructionResult::MacroCall { name, arguments } => {
+                self.expand_macro(line, name, &arguments, location, expanding);
+            }
+            ParseInstructionResult::MacroEnd(token) => {
+                self.errors.push(ProgramAssemblyError::new(
+                    line.span_of(token),
+                    ProgramAssemblyErrorKind::UnexpectedMacroEnd,
+                ));
+            }
+            ParseInstructionResult::Empty => (),
         }
 
-        if !duplicate_labels.is_empty() {
-            let mut duplicate_labels = duplicate_labels.into_iter().collect::<Vec<_>>();
-            duplicate_labels.sort_unstable_by_key(|&(label, _)| label);
+        None
+    }
 
-            for (label, indecies) in duplicate_labels {
-                errors.push(ProgramAssemblyError {
-                    lines: indecies
-                        .into_iter()
-                        .map(|LabelIndex { line, .. }| line)
-                        .collect(),
-                    kind: ProgramAssemblyErrorKind::DuplicateLabel(label.to_owned()),
-                });
+    /// Assembles an included file in place of the line including it, unless it's already been
+    /// included.
+    fn include(&mut self, line: SourceLine<'a>, path: &'a str) {
+        let Some(name) = source::unquote(path) else {
+            self.errors.push(ProgramAssemblyError::new(
+                line.span_of(path),
+                ProgramAssemblyErrorKind::InvalidInclude(path.to_owned()),
+            ));
+            return;
+        };
+
+        let resolved = source::include_path(self.sources.name_of(line.file), name);
+
+        let Some(file) = self.sources.index_of(&resolved) else {
+            self.errors.push(ProgramAssemblyError::new(
+                line.span_of(path),
+                ProgramAssemblyErrorKind::NoSuchFile(resolved),
+            ));
+            return;
+        };
+
+        if !self.included.contains(&file) {
+            self.assemble_file(file);
+        }
+    }
+
+    fn define_constant(
+        &mut self,
+        line: SourceLine<'a>,
+        name: &'a str,
+        value: ArgumentIntermediate<'a>,
+    ) {
+        let span = line.span_of(name);
+
+        if !is_name_valid(name) {
+            self.errors.push(ProgramAssemblyError::new(
+                span,
+                ProgramAssemblyErrorKind::InvalidName(name.to_owned()),
+            ));
+            return;
+        }
+
+        let value = match value.as_constant(self.target_computer.maximum_digits, &self.constants) {
+            Ok(value) => value,
+            Err(ParseArgumentError::IncorrectType) => {
+                self.errors.push(ProgramAssemblyError::new(
+                    value.span(line),
+                    ProgramAssemblyErrorKind::UnexpectedArgument {
+                        got: value.to_owned(),
+                        expected: ArgumentRequirement::Constant,
+                    },
+                ));
+                return;
             }
+            Err(error) => {
+                self.errors.push(ProgramAssemblyError::new(
+                    value.span(line),
+                    ProgramAssemblyErrorKind::InvalidArgument(error),
+                ));
+                return;
+            }
+        };
+
+        if let Some(&first_span) = self.constant_spans.get(name) {
+            self.errors.push(ProgramAssemblyError {
+                spans: vec![first_span, span],
+                kind: ProgramAssemblyErrorKind::DuplicateConstant(name.to_owned()),
+                suggestion: None,
+            });
+            return;
         }
 
-        if !errors.is_empty() {
-            return Err(errors);
+        self.constant_spans.insert(name, span);
+        self.constants.insert(name.to_owned(), value);
+    }
+
+    fn define_macro(&mut self, definition: Macro<'a>) {
+        if !definition.valid {
+            return;
         }
 
-        let mut program = Self::new_empty(name);
+        if let Some(first) = self.macros.get(definition.name) {
+            self.errors.push(ProgramAssemblyError {
+                spans: vec![first.span, definition.span],
+                kind: ProgramAssemblyErrorKind::DuplicateMacro(definition.name.to_owned()),
+                suggestion: None,
+            });
+            return;
+        }
 
-        for instruction in instructions {
-            match instruction.parse(&labels, target_computer) {
-                Ok(instruction) => program.instructions.push(instruction),
-                Err(error) => errors.push(error),
+        self.macros.insert(definition.name, definition);
+    }
+
+    fn expand_macro(
+        &mut self,
+        line: SourceLine<'a>,
+        name: &'a str,
+        arguments: &[&'a str],
+        location: SourceLocation,
+        expanding: &[&'a str],
+    ) {
+        if expanding.contains(&name) {
+            self.errors.push(ProgramAssemblyError::new(
+                line.span_of(name),
+                ProgramAssemblyErrorKind::RecursiveMacro(name.to_owned()),
+            ));
+            return;
+        }
+
+        let definition = self.macros[name].clone();
+
+        let argument_intermediates = arguments
+            .iter()
+            .copied()
+            .map(ArgumentIntermediate::Token)
+            .collect::<Vec<_>>();
+
+        if let Err(error) = InstructionIntermediate::check_argument_length(
+            &argument_intermediates,
+            definition.parameters.len(),
+            definition.parameters.len(),
+            line,
+            name,
+        ) {
+            self.errors.push(error);
+            return;
+        }
+
+        let expanding = [expanding, &[name]].concat();
+
+        for body_line in definition.body {
+            let tokens = body_line
+                .tokens()
+                .into_iter()
+                .map(|token| {
+                    definition
+                        .parameters
+                        .iter()
+                        .position(|&parameter| parameter == token)
+                        .map_or(token, |i| arguments[i])
+                })
+                .collect();
+
+            self.assemble_line(body_line, tokens, location, &expanding);
+        }
+    }
+
+    fn finish(mut self, name: String) -> Result<Program, Vec<ProgramAssemblyError>> {
+        if !self.duplicate_labels.is_empty() {
+            let mut duplicate_labels = self.duplicate_labels.into_iter().collect::<Vec<_>>();
+            duplicate_labels.sort_unstable_by_key(|&(name, _)| name);
+
+            for (name, indecies) in duplicate_labels {
+                self.errors.push(ProgramAssemblyError {
+                    spans: indecies
+                        .into_iter()
+                        .map(|LabelIndex { span, .. }| span)
+                        .collect(),
+                    kind: ProgramAssemblyErrorKind::DuplicateLabel(name.label.to_owned()),
+                    suggestion: None,
+                });
             }
         }
 
-        if !errors.is_empty() {
-            return Err(errors);
+        let mut exported = HashMap::<&str, (u32, Span)>::new();
+
+        for &(name, span) in &self.exports {
+            if !self.labels.contains_key(&name) {
+                self.errors.push(ProgramAssemblyError::new(
+                    span,
+                    ProgramAssemblyErrorKind::InvalidArgument(ParseArgumentError::NoSuchLabel(
+                        name.label.to_owned(),
+                    )),
+                ));
+            } else if let Some(&(file, first_span)) = exported.get(name.label)
+                && file != name.file
+            {
+                self.errors.push(ProgramAssemblyError {
+                    spans: vec![first_span, span],
+                    kind: ProgramAssemblyErrorKind::DuplicateExport(name.label.to_owned()),
+                    suggestion: None,
+                });
+            } else {
+                exported.insert(name.label, (name.file, span));
+            }
         }
 
-        for instruction in &program.instructions {
-            for argument in &instruction.arguments {
-                for source in argument.number_sources() {
-                    if let Some(register) = source.as_register() {
-                        if target_computer.registers.get(register).is_none() {
-                            errors.push(ProgramAssemblyError {
-                                lines: vec![instruction.line],
-                                kind: ProgramAssemblyErrorKind::RegisterNotSupported(register),
-                            });
-                        }
-                    }
-                }
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+
+        let exported = (exported.into_iter())
+            .map(|(label, (file, _))| (label, file))
+            .collect::<HashMap<_, _>>();
+
+        let mut program = Program::new_empty(name);
+
+        program.labels = (self.labels.iter())
+            .map(|(name, label_index)| {
+                let label = if name.file == SourceLocation::MAIN_FILE
+                    || exported.get(name.label) == Some(&name.file)
+                {
+                    name.label.to_owned()
+                } else {
+                    format!(
+                        "{}{FILE_LABEL_SEPARATOR}{}",
+                        self.sources.name_of(name.file),
+                        name.label,
+                    )
+                };
+
+                (label, label_index.index)
+            })
+            .collect();
+
+        for instruction in self.instructions {
+            match instruction.parse(
+                &self.labels,
+                &exported,
+                &self.constants,
+                self.target_computer,
+            ) {
+                Ok(instruction) => program.instructions.push(instruction),
+                Err(error) => self.errors.push(error),
             }
         }
 
-        if errors.is_empty() {
+        program.constants = self.constants;
+        program.files = (self.sources.files.iter())
+            .map(|file| file.name.clone())
+            .collect();
+
+        if self.errors.is_empty() {
             Ok(program)
         } else {
-            Err(errors)
+            Err(self.errors)
         }
     }
 }
 
+/// A sequence of lines which is inserted in place of each line starting with its name, with the
+/// arguments of that line replacing the parameters.
+#[derive(Clone, Debug)]
+struct Macro<'a> {
+    name: &'a str,
+    span: Span,
+    parameters: Vec<&'a str>,
+    body: Vec<SourceLine<'a>>,
+    /// Whether the definition was free of errors, otherwise the macro is never defined
+    valid: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct ProgramAssemblyError {
-    pub lines: Vec<u32>,
+    /// The parts of the source code which caused the error, in order of appearance.
+    pub spans: Vec<Span>,
     pub kind: ProgramAssemblyErrorKind,
+    /// A replacement for the text of the first span which would likely fix the error.
+    pub suggestion: Option<String>,
+}
+
+impl ProgramAssemblyError {
+    #[must_use]
+    pub fn new(span: Span, kind: ProgramAssemblyErrorKind) -> Self {
+        Self {
+            spans: vec![span],
+            kind,
+            suggestion: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_suggestion(mut self, suggestion: Option<&str>) -> Self {
+        self.suggestion = suggestion.map(str::to_owned);
+        self
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = u32> {
+        self.spans.iter().map(|span| span.line)
+    }
+
+    pub fn locations(&self) -> impl Iterator<Item = SourceLocation> {
+        self.spans.iter().map(|span| span.location())
+    }
+
+    /// Returns a value which displays the error along with the lines of source code it refers to,
+    /// underlining the offending text.
+    #[must_use]
+    pub fn render<'a>(&'a self, sources: &'a SourceSet) -> RenderedAssemblyError<'a> {
+        RenderedAssemblyError {
+            error: self,
+            sources,
+        }
+    }
+}
+
+/// A range of bytes within a line of source code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// The index of the file in its [`SourceSet`]
+    pub file: u32,
+    pub line: u32,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    #[must_use]
+    pub fn location(&self) -> SourceLocation {
+        SourceLocation {
+            file: self.file,
+            line: self.line,
+        }
+    }
+
+    /// Returns the range of characters (rather than bytes) covered by the span.
+    #[must_use]
+    pub fn columns(&self, line: &str) -> (usize, usize) {
+        let column_of = |byte: usize| line.get(..byte).map_or(byte, |text| text.chars().count());
+
+        (column_of(self.start), column_of(self.end))
+    }
+
+    /// Extends the span to the end of `other`, unless it's on another line.
+    #[must_use]
+    pub fn to(self, other: Span) -> Span {
+        if other.location() != self.location() {
+            return self;
+        }
+
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+pub struct RenderedAssemblyError<'a> {
+    error: &'a ProgramAssemblyError,
+    sources: &'a SourceSet,
+}
+
+impl Display for RenderedAssemblyError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let spans = &self.error.spans;
+
+        if spans.is_empty() {
+            return writeln!(f, "Error: {}", self.error.kind);
+        }
+
+        // Name each file once, followed by the lines in it
+        for (i, group) in spans.chunk_by(|a, b| a.file == b.file).enumerate() {
+            if i != 0 {
+                write!(f, "; ")?;
+            }
+
+            write!(
+                f,
+                "{}: Line{} ",
+                self.sources.name_of(group[0].file),
+                if group.len() == 1 { "" } else { "s" },
+            )?;
+
+            for (i, span) in group.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ", ")?;
+                }
+
+                write!(f, "{}", span.line)?;
+            }
+        }
+
+        writeln!(f, ": {}", self.error.kind)?;
+
+        let gutter_width = self
+            .error
+            .lines()
+            .max()
+            .map_or(1, |line| line.to_string().len());
+
+        writeln!(f, "{:gutter_width$} |", "")?;
+
+        for (i, span) in spans.iter().enumerate() {
+            // Mark where the spans move between files
+            let previous_file = if i == 0 {
+                spans[0].file
+            } else {
+                spans[i - 1].file
+            };
+
+            if span.file != previous_file {
+                writeln!(
+                    f,
+                    "{:gutter_width$} --> {}",
+                    "",
+                    self.sources.name_of(span.file)
+                )?;
+            }
+
+            let line = self.sources.line(span.location()).unwrap_or("");
+            let (start, end) = span.columns(line);
+
+            // Keep tabs so that the underline lines up with the text above it
+            let padding = (line.chars().take(start))
+                .map(|character| if character == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+
+            writeln!(f, "{:>gutter_width$} | {line}", span.line)?;
+            write!(
+                f,
+                "{:gutter_width$} | {padding}{}",
+                "",
+                "^".repeat((end - start).max(1)),
+            )?;
+
+            match &self.error.suggestion {
+                Some(suggestion) if i == 0 => writeln!(f, " did you mean \"{suggestion}\"?")?,
+                _ => writeln!(f)?,
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -141,6 +767,19 @@ pub enum ProgramAssemblyErrorKind {
     RegisterNotSupported(u32),
     NoSuchOperation(String),
     DuplicateLabel(String),
+    DuplicateConstant(String),
+    DuplicateMacro(String),
+    InvalidName(String),
+    MacroShadowsOperation(String),
+    RecursiveMacro(String),
+    UnterminatedMacro(String),
+    UnexpectedMacroEnd,
+    NestedMacroDefinition,
+    LabelInMacro,
+    InvalidInclude(String),
+    NoSuchFile(String),
+    DuplicateExport(String),
+    NoSuchFaultCode(Integer),
     UnexpectedArgument {
         got: OwnedArgumentIntermediate,
         expected: ArgumentRequirement,
@@ -170,13 +809,118 @@ pub enum ParseArgumentError {
 #[derive(Clone, Debug)]
 struct InstructionIntermediate<'a> {
     kind: InstructionKind,
-    line: u32,
+    line: SourceLine<'a>,
+    /// The location given to the instruction, which differs from `line` for lines of a macro
+    location: SourceLocation,
+    code: &'a str,
     arguments: Vec<ArgumentIntermediate<'a>>,
 }
 
+#[derive(Clone, Copy, Debug)]
+struct SourceLine<'a> {
+    file: u32,
+    index: u32,
+    text: &'a str,
+    /// Every file of the program, which the tokens of expanded macros may come from
+    sources: &'a SourceSet,
+}
+
+impl<'a> SourceLine<'a> {
+    #[must_use]
+    fn location(&self) -> SourceLocation {
+        SourceLocation {
+            file: self.file,
+            line: self.index,
+        }
+    }
+
+    /// The tokens of the line, excluding comments.
+    #[must_use]
+    fn tokens(&self) -> Vec<&'a str> {
+        let line = self
+            .text
+            .split_once(COMMENT_SEPARATOR)
+            .map(|(line, _)| line)
+            .unwrap_or(self.text);
+
+        line.split_whitespace().collect()
+    }
+
+    /// Returns where `token` is in the source code, or the whole line if it isn't a slice of it.
+    #[must_use]
+    fn span_of(&self, token: &str) -> Span {
+        if let Some(start) = offset_within(self.text, token) {
+            return Span {
+                file: self.file,
+                line: self.index,
+                start,
+                end: start + token.len(),
+            };
+        }
+
+        // Tokens passed as arguments to a macro come from the line which called it, which may be
+        // in another file
+        let Some((file, source, offset)) = (self.sources.files.iter().enumerate())
+            .find_map(|(i, file)| Some((i, &file.text, offset_within(&file.text, token)?)))
+        else {
+            return Span {
+                file: self.file,
+                line: self.index,
+                start: 0,
+                end: self.text.len(),
+            };
+        };
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+
+        Span {
+            file: file.try_into().unwrap(),
+            line: source[..line_start].matches('\n').count() as u32,
+            start: offset - line_start,
+            end: offset - line_start + token.len(),
+        }
+    }
+
+    /// Returns an empty span just after `token`.
+    #[must_use]
+    fn span_after(&self, token: &str) -> Span {
+        let span = self.span_of(token);
+
+        Span {
+            start: span.end,
+            ..span
+        }
+    }
+}
+
+/// Returns the position of `token` in `text` if it's a slice of it.
+fn offset_within(text: &str, token: &str) -> Option<usize> {
+    (token.as_ptr() as usize)
+        .checked_sub(text.as_ptr() as usize)
+        .filter(|start| start + token.len() <= text.len())
+}
+
 enum ParseInstructionResult<'a> {
     Instruction(InstructionIntermediate<'a>),
     Label(&'a str),
+    Export(&'a str),
+    /// The quoted path of a file to include
+    Include(&'a str),
+    Constant {
+        name: &'a str,
+        value: ArgumentIntermediate<'a>,
+    },
+    /// The body of the macro is read even if the definition has an error, so that it isn't
+    /// mistaken for code outside of the macro
+    MacroDefinition {
+        definition: Macro<'a>,
+        error: Option<ProgramAssemblyError>,
+    },
+    MacroCall {
+        name: &'a str,
+        arguments: Vec<&'a str>,
+    },
+    /// The token ending a macro definition, given when there wasn't one to end
+    MacroEnd(&'a str),
     Empty,
 }
 
@@ -223,87 +967,233 @@ pub enum OwnedArgumentIntermediate {
 #[derive(Clone, Copy, Debug)]
 pub struct LabelIndex {
     index: u32,
-    line: u32,
+    span: Span,
 }
 
 impl<'a> InstructionIntermediate<'a> {
-    fn from_line(
-        source_line: &'a str,
-        line_index: u32,
+    fn from_tokens(
+        source_line: SourceLine<'a>,
+        tokens: Vec<&'a str>,
         target_computer: &Computer,
+        macros: &HashMap<&'a str, Macro<'a>>,
     ) -> Result<ParseInstructionResult<'a>, ProgramAssemblyError> {
-        let line = source_line
-            .split_once(COMMENT_SEPARATOR)
-            .map(|(line, _)| line)
-            .unwrap_or(source_line);
-
-        let mut tokens = line.split_whitespace().peekable();
-
-        let Some(instruction_code) = tokens.next() else {
+        let Some(&last_token) = tokens.last() else {
             return Ok(ParseInstructionResult::Empty);
         };
 
+        let mut tokens = tokens.into_iter().peekable();
+
+        let instruction_code = tokens.next().unwrap();
+
+        // Parameters and arguments of macros are single tokens, even if they look like comparisons
+        if macros.contains_key(instruction_code) {
+            return Ok(ParseInstructionResult::MacroCall {
+                name: instruction_code,
+                arguments: tokens.collect(),
+            });
+        }
+
+        match instruction_code {
+            MACRO_PSEUDO_INSTRUCTION => {
+                return Self::macro_definition(
+                    source_line,
+                    instruction_code,
+                    tokens.collect(),
+                    target_computer,
+                );
+            }
+            MACRO_END_PSEUDO_INSTRUCTION => {
+                return Ok(ParseInstructionResult::MacroEnd(instruction_code));
+            }
+            INCLUDE_PSEUDO_INSTRUCTION => {
+                let Some(&first_token) = tokens.peek() else {
+                    return Err(ProgramAssemblyError::new(
+                        source_line.span_after(instruction_code),
+                        ProgramAssemblyErrorKind::TooFewArguments { got: 0, minimum: 1 },
+                    ));
+                };
+
+                // The path may contain spaces, so it's everything up to the end of the line
+                let path = match offset_within(source_line.text, first_token)
+                    .zip(offset_within(source_line.text, last_token))
+                {
+                    Some((start, end)) => &source_line.text[start..end + last_token.len()],
+                    None => first_token,
+                };
+
+                return Ok(ParseInstructionResult::Include(path));
+            }
+            _ => (),
+        }
+
         let mut arguments = Vec::new();
 
         loop {
+            let first_token = tokens.peek().copied();
+
             match ArgumentIntermediate::pop_from_tokens(&mut tokens) {
                 Ok(argument) => arguments.push(argument),
                 Err(error) => match error {
                     ParseArgumentError::OutOfTokens => break,
                     _ => {
-                        return Err(ProgramAssemblyError {
-                            lines: vec![line_index],
-                            kind: ProgramAssemblyErrorKind::InvalidArgument(error),
-                        });
+                        let span = source_line
+                            .span_of(first_token.unwrap())
+                            .to(source_line.span_of(last_token));
+
+                        return Err(ProgramAssemblyError::new(
+                            span,
+                            ProgramAssemblyErrorKind::InvalidArgument(error),
+                        ));
                     }
                 },
             }
         }
 
         if instruction_code == LABEL_PSEUDO_INSTRUCTION {
-            Self::check_argument_length(arguments.len(), 1, 1, line_index)?;
+            Self::check_argument_length(&arguments, 1, 1, source_line, instruction_code)?;
 
             let argument = arguments[0];
 
             let ArgumentIntermediate::Token(label) = argument else {
-                return Err(ProgramAssemblyError {
-                    lines: vec![line_index],
-                    kind: ProgramAssemblyErrorKind::UnexpectedArgument {
+                return Err(ProgramAssemblyError::new(
+                    argument.span(source_line),
+                    ProgramAssemblyErrorKind::UnexpectedArgument {
                         got: argument.to_owned(),
                         expected: ArgumentRequirement::Instruction,
                     },
-                });
+                ));
             };
 
             return if is_label_valid(label) {
                 Ok(ParseInstructionResult::Label(label))
             } else {
-                Err(ProgramAssemblyError {
-                    lines: vec![line_index],
-                    kind: ProgramAssemblyErrorKind::InvalidArgument(
-                        ParseArgumentError::InvalidLabel(label.to_owned()),
-                    ),
-                })
+                Err(ProgramAssemblyError::new(
+                    source_line.span_of(label),
+                    ProgramAssemblyErrorKind::InvalidArgument(ParseArgumentError::InvalidLabel(
+                        label.to_owned(),
+                    )),
+                ))
             };
         }
 
+        if instruction_code == EXPORT_PSEUDO_INSTRUCTION {
+            Self::check_argument_length(&arguments, 1, 1, source_line, instruction_code)?;
+
+            let argument = arguments[0];
+
+            return match argument.as_label() {
+                Ok(label) => Ok(ParseInstructionResult::Export(label)),
+                Err(ParseArgumentError::IncorrectType) => Err(ProgramAssemblyError::new(
+                    argument.span(source_line),
+                    ProgramAssemblyErrorKind::UnexpectedArgument {
+                        got: argument.to_owned(),
+                        expected: ArgumentRequirement::Instruction,
+                    },
+                )),
+                Err(error) => Err(ProgramAssemblyError::new(
+                    argument.span(source_line),
+                    ProgramAssemblyErrorKind::InvalidArgument(error),
+                )),
+            };
+        }
+
+        if instruction_code == CONSTANT_PSEUDO_INSTRUCTION {
+            Self::check_argument_length(&arguments, 2, 2, source_line, instruction_code)?;
+
+            let name = arguments[0];
+
+            let ArgumentIntermediate::Token(name) = name else {
+                return Err(ProgramAssemblyError::new(
+                    name.span(source_line),
+                    ProgramAssemblyErrorKind::InvalidName(name.to_string()),
+                ));
+            };
+
+            return Ok(ParseInstructionResult::Constant {
+                name,
+                value: arguments[1],
+            });
+        }
+
         let instruction_properties = (target_computer.instruction_properties)
             .instruction_with_name(instruction_code)
-            .ok_or(ProgramAssemblyError {
-                lines: vec![line_index],
-                kind: ProgramAssemblyErrorKind::NoSuchOperation(instruction_code.to_owned()),
+            .ok_or_else(|| {
+                ProgramAssemblyError::new(
+                    source_line.span_of(instruction_code),
+                    ProgramAssemblyErrorKind::NoSuchOperation(instruction_code.to_owned()),
+                )
+                .with_suggestion(closest_match(
+                    instruction_code,
+                    (target_computer.instruction_properties.0.iter())
+                        .filter(|properties| properties.enabled)
+                        .map(|properties| properties.name)
+                        .chain(PSEUDO_INSTRUCTIONS)
+                        .chain(macros.keys().copied()),
+                ))
             })?;
 
         Ok(ParseInstructionResult::Instruction(Self {
             kind: instruction_properties.kind,
-            line: line_index,
+            line: source_line,
+            location: source_line.location(),
+            code: instruction_code,
             arguments,
         }))
     }
 
+    fn macro_definition(
+        source_line: SourceLine<'a>,
+        instruction_code: &'a str,
+        tokens: Vec<&'a str>,
+        target_computer: &Computer,
+    ) -> Result<ParseInstructionResult<'a>, ProgramAssemblyError> {
+        let Some((&name, parameters)) = tokens.split_first() else {
+            return Err(ProgramAssemblyError::new(
+                source_line.span_after(instruction_code),
+                ProgramAssemblyErrorKind::TooFewArguments { got: 0, minimum: 1 },
+            ));
+        };
+
+        let is_operation = target_computer
+            .instruction_properties
+            .instruction_with_name(name)
+            .is_some();
+
+        let error = if is_operation || PSEUDO_INSTRUCTIONS.contains(&name) {
+            Some(ProgramAssemblyError::new(
+                source_line.span_of(name),
+                ProgramAssemblyErrorKind::MacroShadowsOperation(name.to_owned()),
+            ))
+        } else {
+            [name]
+                .iter()
+                .chain(parameters)
+                .find(|token| !is_name_valid(token))
+                .map(|&token| {
+                    ProgramAssemblyError::new(
+                        source_line.span_of(token),
+                        ProgramAssemblyErrorKind::InvalidName(token.to_owned()),
+                    )
+                })
+        };
+
+        Ok(ParseInstructionResult::MacroDefinition {
+            definition: Macro {
+                name,
+                span: source_line.span_of(name),
+                parameters: parameters.to_vec(),
+                body: Vec::new(),
+                valid: error.is_none(),
+            },
+            error,
+        })
+    }
+
     pub fn parse(
         self,
-        labels: &HashMap<&str, LabelIndex>,
+        labels: &HashMap<LabelName<'a>, LabelIndex>,
+        exported: &HashMap<&'a str, u32>,
+        constants: &HashMap<String, Integer>,
         target_computer: &Computer,
     ) -> Result<Instruction, ProgramAssemblyError> {
         let properties = target_computer.instruction_properties[self.kind];
@@ -312,10 +1202,11 @@ impl<'a> InstructionIntermediate<'a> {
         let max_arguments = properties.maximum_arguments();
 
         Self::check_argument_length(
-            self.arguments.len(),
+            &self.arguments,
             min_arguments,
             max_arguments,
             self.line,
+            self.code,
         )?;
 
         let mut skipped = 0;
@@ -324,7 +1215,7 @@ impl<'a> InstructionIntermediate<'a> {
 
         let mut instruction = Instruction {
             kind: self.kind,
-            line: self.line,
+            location: self.location,
             arguments: array::from_fn(|_| Argument::Empty),
         };
 
@@ -338,29 +1229,70 @@ impl<'a> InstructionIntermediate<'a> {
 
             let argument_intermediate = *arguments.peek().unwrap();
 
+            // Labels are looked up from the file they're written in
+            let scope = LabelScope {
+                file: argument_intermediate.span(self.line).file,
+                labels,
+                exported,
+            };
+
             match argument_intermediate.as_requirement(
                 requirement,
-                labels,
+                &scope,
+                constants,
                 target_computer.maximum_digits,
             ) {
                 Ok(argument) => {
+                    for source in argument.number_sources() {
+                        if let Some(register) = source.as_register()
+                            && target_computer.registers.get(register).is_none()
+                        {
+                            return Err(ProgramAssemblyError::new(
+                                argument_intermediate.span_of_register(register, self.line),
+                                ProgramAssemblyErrorKind::RegisterNotSupported(register),
+                            ));
+                        }
+                    }
+
+                    // The code given to HDL is a constant, so it can be checked here
+                    if self.kind == InstructionKind::Handle
+                        && let Some(code) = argument.as_constant()
+                        && code != 0
+                        && FaultClass::from_code(code).is_none()
+                    {
+                        return Err(ProgramAssemblyError::new(
+                            argument_intermediate.span(self.line),
+                            ProgramAssemblyErrorKind::NoSuchFaultCode(code),
+                        ));
+                    }
+
                     instruction.arguments[i] = argument;
                     arguments.next();
                 }
                 Err(error) => {
+                    let span = argument_intermediate.span(self.line);
+
                     return if let ParseArgumentError::IncorrectType = error {
-                        Err(ProgramAssemblyError {
-                            lines: vec![self.line],
-                            kind: ProgramAssemblyErrorKind::UnexpectedArgument {
+                        Err(ProgramAssemblyError::new(
+                            span,
+                            ProgramAssemblyErrorKind::UnexpectedArgument {
                                 got: argument_intermediate.to_owned(),
                                 expected: requirement,
                             },
-                        })
+                        ))
                     } else {
-                        Err(ProgramAssemblyError {
-                            lines: vec![self.line],
-                            kind: ProgramAssemblyErrorKind::InvalidArgument(error),
-                        })
+                        let suggestion = match &error {
+                            ParseArgumentError::NoSuchLabel(label) => {
+                                closest_match(label, scope.visible_labels())
+                            }
+                            _ => None,
+                        };
+
+                        Err(ProgramAssemblyError::new(
+                            span,
+                            ProgramAssemblyErrorKind::InvalidArgument(error),
+                        )
+                        .with_suggestion(suggestion))
                     };
                 }
             }
@@ -370,27 +1302,46 @@ impl<'a> InstructionIntermediate<'a> {
     }
 
     fn check_argument_length(
-        length: usize,
+        arguments: &[ArgumentIntermediate<'a>],
         minimum: usize,
         maximum: usize,
-        line: u32,
+        line: SourceLine<'a>,
+        instruction_code: &'a str,
     ) -> Result<(), ProgramAssemblyError> {
+        let length = arguments.len();
+
         if length < minimum {
-            Err(ProgramAssemblyError {
-                lines: vec![line],
-                kind: ProgramAssemblyErrorKind::TooFewArguments {
+            let span = match arguments.last() {
+                Some(argument) => {
+                    let span = argument.span(line);
+
+                    Span {
+                        start: span.end,
+                        ..span
+                    }
+                }
+                None => line.span_after(instruction_code),
+            };
+
+            Err(ProgramAssemblyError::new(
+                span,
+                ProgramAssemblyErrorKind::TooFewArguments {
                     got: length,
                     minimum,
                 },
-            })
+            ))
         } else if length > maximum {
-            Err(ProgramAssemblyError {
-                lines: vec![line],
-                kind: ProgramAssemblyErrorKind::TooManyArguments {
+            let span = arguments[maximum]
+                .span(line)
+                .to(arguments.last().unwrap().span(line));
+
+            Err(ProgramAssemblyError::new(
+                span,
+                ProgramAssemblyErrorKind::TooManyArguments {
                     got: length,
                     maximum,
                 },
-            })
+            ))
         } else {
             Ok(())
         }
@@ -432,25 +1383,55 @@ impl<'a> ArgumentIntermediate<'a> {
         })
     }
 
+    #[must_use]
+    fn span(&self, line: SourceLine) -> Span {
+        match self {
+            ArgumentIntermediate::Token(token) => line.span_of(token),
+            ArgumentIntermediate::Comparison {
+                values: [lhs, rhs], ..
+            } => line.span_of(lhs).to(line.span_of(rhs)),
+        }
+    }
+
+    /// Returns the span of the token naming `register`, or of the whole argument if there isn't
+    /// one.
+    #[must_use]
+    fn span_of_register(&self, register: u32, line: SourceLine) -> Span {
+        match self {
+            ArgumentIntermediate::Comparison { values, .. } => values
+                .iter()
+                .find(|&&value| {
+                    ArgumentIntermediate::Token(value)
+                        .as_register()
+                        .is_ok_and(|value_register| value_register == register)
+                })
+                .map_or_else(|| self.span(line), |value| line.span_of(value)),
+            ArgumentIntermediate::Token(_) => self.span(line),
+        }
+    }
+
     pub fn as_requirement(
         &self,
         requirement: ArgumentRequirement,
-        labels: &HashMap<&str, LabelIndex>,
+        labels: &LabelScope<'_, 'a>,
+        constants: &HashMap<String, Integer>,
         maximum_digits: u8,
     ) -> Result<Argument, ParseArgumentError> {
         Ok(match requirement {
             ArgumentRequirement::Constant | ArgumentRequirement::ConstantOrEmpty => {
-                self.as_constant(maximum_digits)?.into()
+                self.as_constant(maximum_digits, constants)?.into()
             }
             ArgumentRequirement::RegisterWriteOnly | ArgumentRequirement::Register => {
                 self.as_register()?.into()
             }
             ArgumentRequirement::ConstantOrRegister => {
-                self.as_number_source(maximum_digits)?.into()
+                self.as_number_source(maximum_digits, constants)?.into()
+            }
+            ArgumentRequirement::Comparison => {
+                self.as_comparison(maximum_digits, constants)?.into()
             }
-            ArgumentRequirement::Comparison => self.as_comparison(maximum_digits)?.into(),
             ArgumentRequirement::AnyValue | ArgumentRequirement::AnyValueOrEmpty => {
-                self.as_value(maximum_digits)?
+                self.as_value(maximum_digits, constants)?
             }
             ArgumentRequirement::Instruction => {
                 let label = self.as_label()?;
@@ -479,9 +1460,18 @@ impl<'a> ArgumentIntermediate<'a> {
         }
     }
 
-    pub fn as_constant(&self, maximum_digits: u8) -> Result<Integer, ParseArgumentError> {
+    /// Parses a number, or the name of a constant.
+    pub fn as_constant(
+        &self,
+        maximum_digits: u8,
+        constants: &HashMap<String, Integer>,
+    ) -> Result<Integer, ParseArgumentError> {
         match *self {
             ArgumentIntermediate::Token(token) => {
+                if let Some(&value) = constants.get(token) {
+                    return Ok(value);
+                }
+
                 let value = token
                     .parse()
                     .map_err(|error: ParseIntError| match error.kind() {
@@ -540,8 +1530,12 @@ impl<'a> ArgumentIntermediate<'a> {
         }
     }
 
-    pub fn as_number_source(&self, maximum_digits: u8) -> Result<NumberSource, ParseArgumentError> {
-        let as_constant_error = match self.as_constant(maximum_digits) {
+    pub fn as_number_source(
+        &self,
+        maximum_digits: u8,
+        constants: &HashMap<String, Integer>,
+    ) -> Result<NumberSource, ParseArgumentError> {
+        let as_constant_error = match self.as_constant(maximum_digits, constants) {
             Ok(constant) => return Ok(NumberSource::Constant(constant)),
             Err(error) => error,
         };
@@ -560,7 +1554,11 @@ impl<'a> ArgumentIntermediate<'a> {
         )
     }
 
-    pub fn as_comparison(&self, maximum_digits: u8) -> Result<Comparison, ParseArgumentError> {
+    pub fn as_comparison(
+        &self,
+        maximum_digits: u8,
+        constants: &HashMap<String, Integer>,
+    ) -> Result<Comparison, ParseArgumentError> {
         match self {
             ArgumentIntermediate::Token(_) => Err(ParseArgumentError::IncorrectType),
             ArgumentIntermediate::Comparison {
@@ -571,20 +1569,24 @@ impl<'a> ArgumentIntermediate<'a> {
                 ordering: *ordering,
                 invert: *invert,
                 values: [
-                    ArgumentIntermediate::Token(lhs).as_number_source(maximum_digits)?,
-                    ArgumentIntermediate::Token(rhs).as_number_source(maximum_digits)?,
+                    ArgumentIntermediate::Token(lhs).as_number_source(maximum_digits, constants)?,
+                    ArgumentIntermediate::Token(rhs).as_number_source(maximum_digits, constants)?,
                 ],
             }),
         }
     }
 
-    pub fn as_value(&self, maximum_digits: u8) -> Result<Argument, ParseArgumentError> {
-        let as_source_error = match self.as_number_source(maximum_digits) {
+    pub fn as_value(
+        &self,
+        maximum_digits: u8,
+        constants: &HashMap<String, Integer>,
+    ) -> Result<Argument, ParseArgumentError> {
+        let as_source_error = match self.as_number_source(maximum_digits, constants) {
             Ok(source) => return Ok(source.into()),
             Err(error) => error,
         };
 
-        let as_comparison_error = match self.as_comparison(maximum_digits) {
+        let as_comparison_error = match self.as_comparison(maximum_digits, constants) {
             Ok(comparison) => return Ok(comparison.into()),
             Err(error) => error,
         };
@@ -647,31 +1649,6 @@ impl Display for OwnedArgumentIntermediate {
     }
 }
 
-impl Display for ProgramAssemblyError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.lines.len() {
-            0 => write!(f, "Error: {error}", error = self.kind),
-            1 => write!(
-                f,
-                "Line {line}: {error}",
-                line = self.lines[0],
-                error = self.kind,
-            ),
-            2.. => {
-                write!(f, "Lines ",)?;
-                for (i, line) in self.lines.iter().enumerate() {
-                    if i != 0 {
-                        write!(f, ", ")?;
-                    }
-
-                    write!(f, "{line}")?;
-                }
-                write!(f, ": {error}", error = self.kind)
-            }
-        }
-    }
-}
-
 impl Display for ProgramAssemblyErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -688,6 +1665,50 @@ impl Display for ProgramAssemblyErrorKind {
             ProgramAssemblyErrorKind::DuplicateLabel(label) => {
                 write!(f, "Duplicate label \"{label}\"")
             }
+            ProgramAssemblyErrorKind::DuplicateConstant(name) => {
+                write!(f, "Duplicate constant \"{name}\"")
+            }
+            ProgramAssemblyErrorKind::DuplicateMacro(name) => {
+                write!(f, "Duplicate macro \"{name}\"")
+            }
+            ProgramAssemblyErrorKind::InvalidName(name) => write!(
+                f,
+                "Invalid name \"{name}\", must contain only _, -, letters, and numbers, and not be \
+                a number or register"
+            ),
+            ProgramAssemblyErrorKind::MacroShadowsOperation(name) => {
+                write!(f, "Macro \"{name}\" has the same name as an operation")
+            }
+            ProgramAssemblyErrorKind::RecursiveMacro(name) => {
+                write!(f, "Macro \"{name}\" is used inside of itself")
+            }
+            ProgramAssemblyErrorKind::UnterminatedMacro(name) => write!(
+                f,
+                "Macro \"{name}\" has no {MACRO_END_PSEUDO_INSTRUCTION} to end it"
+            ),
+            ProgramAssemblyErrorKind::UnexpectedMacroEnd => {
+                write!(f, "{MACRO_END_PSEUDO_INSTRUCTION} without a macro to end")
+            }
+            ProgramAssemblyErrorKind::NestedMacroDefinition => {
+                write!(f, "Macros can't be defined inside of other macros")
+            }
+            ProgramAssemblyErrorKind::LabelInMacro => {
+                write!(f, "Labels can't be defined inside of macros")
+            }
+            ProgramAssemblyErrorKind::InvalidInclude(path) => {
+                write!(f, "Expected a path in double quotes, got \"{path}\"")
+            }
+            ProgramAssemblyErrorKind::NoSuchFile(path) => {
+                write!(f, "The file \"{path}\" could not be found")
+            }
+            ProgramAssemblyErrorKind::DuplicateExport(label) => {
+                write!(f, "The label \"{label}\" is exported by more than one file")
+            }
+            ProgramAssemblyErrorKind::NoSuchFaultCode(code) => write!(
+                f,
+                "No fault has the code {code} (expected 0 for every fault, or 1 to {count})",
+                count = FaultClass::COUNT,
+            ),
             ProgramAssemblyErrorKind::UnexpectedArgument { got, expected } => {
                 write!(f, "Got \"{got}\", expected {expected}")
             }
@@ -722,7 +1743,63 @@ impl Display for ProgramAssemblyErrorKind {
     }
 }
 
-fn is_label_valid(label: &str) -> bool {
+/// Returns the candidate most similar to `target`, ignoring case, if any are similar enough to
+/// likely be a typo.
+fn closest_match<'a>(target: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let target = target.to_uppercase().chars().collect::<Vec<_>>();
+    let maximum_distance = target.len().div_ceil(3).max(1);
+
+    candidates
+        .map(|candidate| {
+            let candidate_characters = candidate.to_uppercase().chars().collect::<Vec<_>>();
+
+            (candidate, edit_distance(&target, &candidate_characters))
+        })
+        .filter(|&(_, distance)| distance <= maximum_distance)
+        .min_by_key(|&(candidate, distance)| (distance, candidate))
+        .map(|(candidate, _)| candidate)
+}
+
+/// The number of insertions, deletions, substitutions, and swaps of adjacent characters needed to
+/// turn `a` into `b`.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+/// Constants share arguments with numbers and registers, so they can't look like either.
+fn is_name_valid(name: &str) -> bool {
+    is_label_valid(name)
+        && name.parse::<Integer>().is_err()
+        && ArgumentIntermediate::Token(name).as_register().is_err()
+}
+
+pub fn is_label_valid(label: &str) -> bool {
     for character in label.chars() {
         if !['_', '-'].contains(&character) && !character.is_ascii_alphanumeric() {
             return false;
@@ -731,3 +1808,252 @@ fn is_label_valid(label: &str) -> bool {
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_MACHINE_WITH_H;
+
+    fn sources(files: &[(&str, &str)]) -> SourceSet {
+        SourceSet {
+            files: (files.iter())
+                .map(|&(name, text)| source::SourceFile {
+                    name: name.to_owned(),
+                    text: text.to_owned(),
+                })
+                .collect(),
+        }
+    }
+
+    fn assemble(files: &[(&str, &str)]) -> Result<Program, Vec<ProgramAssemblyError>> {
+        let computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+
+        Program::assemble_from_sources(files[0].0.to_owned(), &sources(files), &computer)
+    }
+
+    /// The lines and messages of the errors from assembling a single file.
+    fn errors(source_code: &str) -> Vec<(Vec<u32>, String)> {
+        let errors = assemble(&[("main.zρ", source_code)]).unwrap_err();
+
+        (errors.iter())
+            .map(|error| (error.lines().collect(), error.kind.to_string()))
+            .collect()
+    }
+
+    /// The operation and arguments of each instruction in a program, ignoring which lines they
+    /// came from.
+    fn instructions(source_code: &str) -> Vec<String> {
+        let program = assemble(&[("main.zρ", source_code)]).unwrap();
+
+        (program.instructions.iter())
+            .map(|instruction| format!("{:?} {:?}", instruction.kind, instruction.arguments))
+            .collect()
+    }
+
+    #[test]
+    fn errors_are_rendered_with_the_source_and_a_suggestion() {
+        let files = [("main.zρ", "LBL LOOP\nADD X 1 X\n\tADDD X 1 X")];
+        let errors = assemble(&files).unwrap_err();
+
+        assert_eq!(
+            errors[0].render(&sources(&files)).to_string(),
+            "main.zρ: Line 2: No such operation \"ADDD\" on this machine
+  |
+2 | \tADDD X 1 X
+  | \t^^^^ did you mean \"ADD\"?
+",
+        );
+
+        let errors = assemble(&[("main.zρ", "LBL LOOP\nJMP LOPO")]).unwrap_err();
+        assert_eq!(errors[0].suggestion.as_deref(), Some("LOOP"));
+        assert_eq!(errors[0].spans[0].start..errors[0].spans[0].end, 4..8);
+    }
+
+    #[test]
+    fn suggestions_are_close_to_what_was_written() {
+        let candidates = ["ADD", "SUB", "LOOP", "DIVIDED"];
+
+        assert_eq!(closest_match("add", candidates.into_iter()), Some("ADD"));
+        assert_eq!(closest_match("LOPO", candidates.into_iter()), Some("LOOP"));
+        assert_eq!(
+            closest_match("DIVDE", candidates.into_iter()),
+            Some("DIVIDED")
+        );
+        assert_eq!(closest_match("MUL", candidates.into_iter()), None);
+
+        let characters = |text: &str| text.chars().collect::<Vec<_>>();
+        assert_eq!(edit_distance(&characters("LOPO"), &characters("LOOP")), 1);
+        assert_eq!(edit_distance(&characters("ρX"), &characters("Xρ")), 1);
+        assert_eq!(edit_distance(&characters(""), &characters("SUB")), 3);
+    }
+
+    #[test]
+    fn constants_and_macros_are_replaced() {
+        let source_code = "DEF SIZE 10
+DEF LIMIT SIZE
+MAC STORE VALUE
+SET D VALUE
+ADD I 1 I
+EMC
+LBL LOOP
+STORE LIMIT
+JMP I < SIZE LOOP";
+
+        assert_eq!(
+            instructions(source_code),
+            instructions("LBL LOOP\nSET D 10\nADD I 1 I\nJMP I < 10 LOOP"),
+        );
+    }
+
+    #[test]
+    fn invalid_constants_are_rejected() {
+        let source_code = "DEF ONE 1
+DEF ONE 2
+DEF 5 1
+DEF X 1
+DEF BIG 10000
+DEF SMALL -10000
+SET X ONE";
+        let invalid_name = |name: &str| ProgramAssemblyErrorKind::InvalidName(name.into());
+
+        assert_eq!(
+            errors(source_code),
+            [
+                (vec![0, 1], "Duplicate constant \"ONE\"".to_owned()),
+                (vec![2], invalid_name("5").to_string()),
+                (vec![3], invalid_name("X").to_string()),
+                (
+                    vec![4],
+                    "\"10000\" is too big for this machine (maximum: 9999)".to_owned()
+                ),
+                (
+                    vec![5],
+                    "\"-10000\" is too small for this machine (minimum: -9999)".to_owned()
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn invalid_macros_are_rejected() {
+        let cases: [(&str, &[u32], &str); 7] = [
+            (
+                "MAC MM\nEMC\nMAC MM\nEMC",
+                &[0, 2],
+                "Duplicate macro \"MM\"",
+            ),
+            (
+                "MAC ADD\nEMC",
+                &[0],
+                "Macro \"ADD\" has the same name as an operation",
+            ),
+            (
+                "MAC MM\nMM\nEMC\nMM",
+                &[1],
+                "Macro \"MM\" is used inside of itself",
+            ),
+            ("MAC MM\nSET X 1", &[0], "Macro \"MM\" has no EMC to end it"),
+            ("EMC", &[0], "EMC without a macro to end"),
+            (
+                "MAC MM\nMAC NN\nEMC\nEMC",
+                &[1],
+                "Macros can't be defined inside of other macros",
+            ),
+            (
+                "MAC MM\nLBL LL\nEMC\nMM\nMM",
+                &[1],
+                "Labels can't be defined inside of macros",
+            ),
+        ];
+
+        for (source_code, lines, message) in cases {
+            let errors = errors(source_code);
+
+            assert!(
+                errors.contains(&(lines.to_vec(), message.to_owned())),
+                "{source_code:?} gave {errors:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_fault_codes_are_rejected() {
+        assert!(assemble(&[("main.zρ", "HDL 0 END\nHDL 4 END\nLBL END")]).is_ok());
+
+        assert_eq!(
+            errors("HDL 5 END\nHDL -1 END\nLBL END"),
+            [
+                (
+                    vec![0],
+                    "No fault has the code 5 (expected 0 for every fault, or 1 to 4)".to_owned()
+                ),
+                (
+                    vec![1],
+                    "No fault has the code -1 (expected 0 for every fault, or 1 to 4)".to_owned()
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn labels_belong_to_their_file_unless_exported() {
+        let program = assemble(&[
+            ("main.zρ", "INC \"lib.zρ\"\nLBL LOOP\nJMP SQUARE\nJMP LOOP"),
+            (
+                "lib.zρ",
+                "EXP SQUARE\nJMP END\nLBL SQUARE\nMUL X X Y\nLBL LOOP\nLBL END",
+            ),
+        ]);
+
+        assert!(program.is_ok());
+
+        let errors = assemble(&[
+            ("main.zρ", "INC \"lib.zρ\"\nJMP HIDDEN"),
+            ("lib.zρ", "LBL HIDDEN"),
+        ])
+        .unwrap_err();
+
+        assert_eq!(errors[0].kind.to_string(), "No such label \"HIDDEN\"");
+        assert_eq!(errors[0].spans[0].file, 0);
+    }
+
+    #[test]
+    fn invalid_includes_are_rejected() {
+        let errors = assemble(&[
+            (
+                "main.zρ",
+                "INC lib.zρ\nINC \"missing.zρ\"\nINC \"lib.zρ\"\nEXP SHARED\nLBL SHARED",
+            ),
+            ("lib.zρ", "EXP SHARED\nLBL SHARED"),
+        ])
+        .unwrap_err();
+
+        let messages = (errors.iter())
+            .map(|error| error.kind.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            [
+                "Expected a path in double quotes, got \"lib.zρ\"",
+                "The file \"missing.zρ\" could not be found",
+                "The label \"SHARED\" is exported by more than one file",
+            ],
+        );
+    }
+
+    #[test]
+    fn errors_name_the_file_of_each_line() {
+        let files = [
+            ("main.zρ", "INC \"lib.zρ\"\nEXP SHARED\nLBL SHARED"),
+            ("lib.zρ", "SET X 1\nEXP SHARED\nLBL SHARED"),
+        ];
+        let errors = assemble(&files).unwrap_err();
+        let rendered = errors[0].render(&sources(&files)).to_string();
+
+        assert!(
+            rendered.starts_with("lib.zρ: Line 1; main.zρ: Line 1: "),
+            "{rendered}",
+        );
+    }
+}
@@ -0,0 +1,859 @@
+use strum::{EnumCount, IntoEnumIterator};
+use toml::{Table, Value};
+
+use super::{
+    computer::{
+        self, BlockCondition, BlockReason, BlockState, Channel, Computer, Register, RegisterValues,
+    },
+    definition::{self, DefinitionError, DefinitionErrorKind, Entry},
+    instruction::{ArgumentValues, FaultClass, Instruction, InstructionKind, InstructionKindMap},
+    integer::{DigitInteger, Integer},
+    program::Program,
+};
+
+/// The names of each [`BlockReason`] in a saved state.
+pub(super) const BLOCK_REASONS: [(&str, BlockReason); 8] = [
+    ("index_increased", BlockReason::IndexIncreased),
+    ("index_written_no_op", BlockReason::IndexWrittenNoOp),
+    ("index_decreased", BlockReason::IndexDecreased),
+    ("not_sequential", BlockReason::NotSequential),
+    ("cache_miss", BlockReason::CacheMiss),
+    ("write_back", BlockReason::WriteBack),
+    ("contention", BlockReason::Contention),
+    ("channel", BlockReason::Channel),
+];
+
+impl Computer {
+    /// The version of the files written by [`Computer::save_state`].
+    pub const STATE_VERSION: i64 = 2;
+
+    /// Writes everything about the computer which changes as it runs to a TOML file, which can be
+    /// loaded with [`Computer::load_state`]. Interrupts aren't saved, so a computer which has
+    /// stopped will stop again when it's next stepped.
+    #[must_use]
+    pub fn save_state(&self) -> String {
+        let mut table = Table::new();
+
+        let mut insert = |key: &str, value: Value| {
+            table.insert(key.to_owned(), value);
+        };
+
+        insert("version", Value::Integer(Self::STATE_VERSION));
+        insert("instruction", self.instruction.into());
+        insert("next_instruction", self.next_instruction.into());
+        insert("block_time", self.block_time.into());
+        insert("tick_complete", self.tick_complete.into());
+        insert("runtime", integer(self.runtime));
+        insert("energy_used", integer(self.energy_used));
+
+        if let Some(register) = self.waiting_for_channel {
+            insert("waiting_for_channel", register_name(register));
+        }
+
+        insert(
+            "executed_instructions",
+            save_counts(&self.executed_instructions),
+        );
+        insert(
+            "executed_instruction_groups",
+            save_counts(&self.executed_instruction_groups),
+        );
+
+        if let Some((instruction, argument_values)) = self.previous_instruction {
+            let arguments = (argument_values.iter().enumerate())
+                .filter_map(|(i, value)| Some(((i + 1).to_string(), (*value)?.into())))
+                .collect::<Table>();
+
+            insert(
+                "previous_instruction",
+                Value::Table(Table::from_iter([
+                    ("instruction".to_owned(), instruction.into()),
+                    ("arguments".to_owned(), Value::Table(arguments)),
+                ])),
+            );
+        }
+
+        if !self.return_stack.addresses.is_empty() {
+            insert(
+                "return_stack",
+                Value::Array(
+                    (self.return_stack.addresses.iter())
+                        .map(|&address| address.into())
+                        .collect(),
+                ),
+            );
+        }
+
+        let fault_handlers = (FaultClass::iter())
+            .filter_map(|class| {
+                let handler = self.fault_handlers.handler(class)?;
+
+                Some((class.name().to_owned(), handler.into()))
+            })
+            .collect::<Table>();
+
+        if !fault_handlers.is_empty() {
+            insert("fault_handlers", Value::Table(fault_handlers));
+        }
+
+        let buffered_writes = (self.registers.buffered_writes.iter())
+            .map(|&(register, value)| {
+                Value::Table(Table::from_iter([
+                    ("register".to_owned(), register_name(register)),
+                    ("value".to_owned(), value.into()),
+                ]))
+            })
+            .collect();
+
+        insert("buffered_writes", Value::Array(buffered_writes));
+
+        let registers = (self.registers.registers.iter().enumerate())
+            .filter_map(|(i, register)| {
+                let name = computer::name_of_register(i as u32)?.to_string();
+
+                Some((name, Value::Table(save_register(register.as_ref()?))))
+            })
+            .collect();
+
+        insert("registers", Value::Table(registers));
+
+        table.to_string()
+    }
+
+    /// Loads a state written by [`Computer::save_state`] for running `program`. The state is
+    /// rejected if it was saved from a machine with different registers.
+    pub fn load_state(
+        &mut self,
+        source_code: &str,
+        program: &Program,
+    ) -> Result<(), Vec<DefinitionError>> {
+        let table = definition::parse(source_code)?;
+
+        let mut errors = Vec::new();
+
+        let state = self.state_from_entry(Entry::new(String::new(), &table, &mut errors), program);
+
+        match state {
+            Some(state) if errors.is_empty() => {
+                *self = state;
+                Ok(())
+            }
+            _ => Err(errors),
+        }
+    }
+
+    fn state_from_entry(&self, mut root: Entry, program: &Program) -> Option<Self> {
+        let mut state = self.clone();
+
+        if let Some(version) = root.integer("version", 0, i64::MAX)
+            && version != Self::STATE_VERSION
+        {
+            root.error(
+                "version",
+                DefinitionErrorKind::UnsupportedVersion {
+                    got: version,
+                    expected: Self::STATE_VERSION,
+                },
+            );
+        }
+
+        let length = program.instructions.len() as i64;
+
+        let instruction = root.integer("instruction", 0, length);
+        let next_instruction = root.integer("next_instruction", 0, length);
+        let block_time = root.integer("block_time", 0, u32::MAX.into());
+        let tick_complete = root.optional_boolean("tick_complete");
+        let runtime = root.integer("runtime", 0, i64::MAX);
+        let energy_used = root.integer("energy_used", 0, i64::MAX);
+        let waiting_for_channel = root.optional_register("waiting_for_channel");
+
+        for (key, counts) in [
+            ("executed_instructions", &mut state.executed_instructions),
+            (
+                "executed_instruction_groups",
+                &mut state.executed_instruction_groups,
+            ),
+        ] {
+            if let Some(table) = root.optional_table(key) {
+                *counts = load_counts(root.child(key, table));
+            }
+        }
+
+        state.previous_instruction = match root.optional_table("previous_instruction") {
+            Some(table) => load_previous_instruction(
+                root.child("previous_instruction", table),
+                program.instructions.len(),
+            ),
+            None => None,
+        };
+
+        // Each address is the one after a call, so none of them can be the first instruction
+        match root.optional_integer_array("return_stack", 1, length) {
+            Some(Some(addresses)) if addresses.len() > state.return_stack.depth as usize => {
+                root.error(
+                    "return_stack",
+                    DefinitionErrorKind::ReturnStackTooDeep {
+                        got: addresses.len(),
+                        depth: state.return_stack.depth,
+                    },
+                );
+            }
+            Some(Some(addresses)) => {
+                state.return_stack.addresses = addresses
+                    .into_iter()
+                    .map(|address| address as u32)
+                    .collect();
+            }
+            Some(None) => state.return_stack.addresses.clear(),
+            None => (),
+        }
+
+        state.fault_handlers.handlers = match root.optional_table("fault_handlers") {
+            Some(table) => load_fault_handlers(root.child("fault_handlers", table), length),
+            None => Default::default(),
+        };
+
+        match root.table.get("buffered_writes") {
+            Some(Value::Array(writes)) => {
+                state.registers.buffered_writes = (writes.iter().enumerate())
+                    .filter_map(|(i, write)| {
+                        let key = format!("buffered_writes[{i}]");
+                        let table = root.as_table(&key, write)?;
+
+                        let mut entry = root.child(&key, table);
+                        let register = entry.register("register").and_then(|index| {
+                            let register = self.registers.get(index);
+
+                            if register.is_none() {
+                                entry.error(
+                                    "register",
+                                    DefinitionErrorKind::UndefinedRegister(index),
+                                );
+                            }
+
+                            register.map(|register| (index, register))
+                        });
+
+                        // The write is applied without checking it, so it has to fit the register
+                        let (minimum, maximum) =
+                            register.map_or((Integer::MIN, Integer::MAX), |(_, register)| {
+                                let value = register.values.all_values()[0];
+                                (value.minimum(), value.maximum())
+                            });
+                        let value = entry.integer("value", minimum.into(), maximum.into());
+
+                        entry.finish(&["register", "value"]);
+
+                        Some((register?.0, value? as Integer))
+                    })
+                    .collect();
+            }
+            Some(_) => root.error(
+                "buffered_writes",
+                DefinitionErrorKind::IncorrectType {
+                    expected: "an array of tables",
+                },
+            ),
+            None => state.registers.buffered_writes.clear(),
+        }
+
+        if let Some(registers) = root.table("registers") {
+            let mut entry = root.child("registers", registers);
+
+            let mut names = registers.keys().collect::<Vec<_>>();
+            names.sort_unstable();
+
+            for name in names {
+                let index = definition::register_index(name);
+
+                let register = index.and_then(|index| state.registers.get_mut(index));
+
+                let Some(register) = register else {
+                    match index {
+                        Some(index) => {
+                            entry.error(name, DefinitionErrorKind::UndefinedRegister(index))
+                        }
+                        None => {
+                            entry.error(name, DefinitionErrorKind::NoSuchRegister(name.clone()))
+                        }
+                    }
+                    continue;
+                };
+
+                if let Some(table) = entry.as_table(name, &registers[name]) {
+                    load_register(entry.child(name, table), register);
+                }
+            }
+
+            // Every register of the machine has to be saved
+            for (i, register) in self.registers.registers.iter().enumerate() {
+                let name = computer::name_of_register(i as u32).unwrap().to_string();
+
+                if register.is_some() && !registers.contains_key(&name) {
+                    entry.error(&name, DefinitionErrorKind::MissingEntry);
+                }
+            }
+        }
+
+        root.finish(&[
+            "version",
+            "instruction",
+            "next_instruction",
+            "block_time",
+            "tick_complete",
+            "runtime",
+            "energy_used",
+            "waiting_for_channel",
+            "executed_instructions",
+            "executed_instruction_groups",
+            "previous_instruction",
+            "return_stack",
+            "fault_handlers",
+            "buffered_writes",
+            "registers",
+        ]);
+
+        state.instruction = instruction? as u32;
+        state.next_instruction = next_instruction? as u32;
+        state.block_time = block_time? as u32;
+        state.tick_complete = tick_complete?.unwrap_or(false);
+        state.runtime = runtime? as u64;
+        state.energy_used = energy_used? as u64;
+        state.waiting_for_channel = waiting_for_channel?;
+        state.interrupt = None;
+
+        Some(state)
+    }
+}
+
+fn save_register(register: &Register) -> Table {
+    let mut table = Table::new();
+
+    let mut insert = |key: &str, value: Value| {
+        table.insert(key.to_owned(), value);
+    };
+
+    let digits = register.values.all_values()[0].num_digits() - 1;
+
+    insert("digits", (digits as i64).into());
+
+    match &register.values {
+        RegisterValues::Scalar(value) => insert("value", value.get().into()),
+        RegisterValues::Vector {
+            values,
+            index,
+            offset,
+        } => {
+            insert("length", (values.len() as i64).into());
+            insert("offset", (*offset).into());
+            insert("index", (*index).into());
+            insert(
+                "values",
+                Value::Array(values.iter().map(|value| value.get().into()).collect()),
+            );
+        }
+    }
+
+    insert("block_time", register.block_time.into());
+
+    if register.seeking {
+        insert("seeking", true.into());
+    }
+
+    if let Some(reason) = register.block_reason {
+        let (name, _) = BLOCK_REASONS
+            .iter()
+            .find(|&&(_, other)| other == reason)
+            .unwrap();
+
+        insert("block_reason", (*name).into());
+    }
+
+    let cached_lines = (register.block_state.cached_lines.iter())
+        .map_while(|&line| line.map(Value::from))
+        .collect::<Vec<_>>();
+
+    if !cached_lines.is_empty() {
+        insert("cached_lines", Value::Array(cached_lines));
+    }
+
+    if register.block_state.accesses > 0 {
+        insert("accesses", register.block_state.accesses.into());
+    }
+
+    if let Some(channel) = register.channel {
+        let mut channel_table = Table::new();
+
+        if let Some(outgoing) = channel.outgoing {
+            channel_table.insert("outgoing".to_owned(), outgoing.into());
+        }
+
+        channel_table.insert("delivered".to_owned(), channel.delivered.into());
+
+        if let Some(incoming) = channel.incoming {
+            channel_table.insert("incoming".to_owned(), incoming.into());
+        }
+
+        insert("channel", Value::Table(channel_table));
+    }
+
+    table
+}
+
+/// Loads the saved state of a register, checking that the register has the same layout as the one
+/// it was saved from.
+fn load_register(mut entry: Entry, register: &mut Register) {
+    let digits = register.values.all_values()[0].num_digits() - 1;
+
+    if let Some(saved_digits) = entry.integer("digits", 0, DigitInteger::MAXIMUM_DIGITS as i64)
+        && saved_digits as usize != digits
+    {
+        entry.error(
+            "digits",
+            DefinitionErrorKind::DifferentMachine {
+                saved: saved_digits.to_string(),
+                current: digits.to_string(),
+            },
+        );
+    }
+
+    if let Some(seeking) = entry.optional_boolean("seeking")
+        && seeking.unwrap_or(false) != register.seeking
+    {
+        let describe = |seeking| if seeking { "seeking" } else { "not seeking" }.to_owned();
+
+        entry.error(
+            "seeking",
+            DefinitionErrorKind::DifferentMachine {
+                saved: describe(seeking.unwrap_or(false)),
+                current: describe(register.seeking),
+            },
+        );
+    }
+
+    let maximum = register.values.all_values()[0].maximum();
+
+    match &mut register.values {
+        RegisterValues::Scalar(value) => {
+            if entry.table.contains_key("length") {
+                entry.error(
+                    "length",
+                    DefinitionErrorKind::DifferentMachine {
+                        saved: "a vector".to_owned(),
+                        current: "a scalar".to_owned(),
+                    },
+                );
+            } else if let Some(saved) = entry.integer("value", (-maximum).into(), maximum.into()) {
+                value.try_set(saved as Integer).unwrap();
+            }
+        }
+        RegisterValues::Vector {
+            values,
+            index,
+            offset,
+        } => {
+            if let Some(length) = entry.integer("length", 1, u32::MAX.into())
+                && length as usize != values.len()
+            {
+                entry.error(
+                    "length",
+                    DefinitionErrorKind::DifferentMachine {
+                        saved: length.to_string(),
+                        current: values.len().to_string(),
+                    },
+                );
+            }
+
+            if let Some(saved_offset) =
+                entry.integer("offset", Integer::MIN.into(), Integer::MAX.into())
+                && saved_offset != i64::from(*offset)
+            {
+                entry.error(
+                    "offset",
+                    DefinitionErrorKind::DifferentMachine {
+                        saved: saved_offset.to_string(),
+                        current: offset.to_string(),
+                    },
+                );
+            }
+
+            if let Some(saved_index) =
+                entry.integer("index", Integer::MIN.into(), Integer::MAX.into())
+            {
+                *index = saved_index as Integer;
+            }
+
+            if let Some(saved_values) =
+                entry.integer_array("values", (-maximum).into(), maximum.into())
+            {
+                if saved_values.len() == values.len() {
+                    for (value, saved) in values.iter_mut().zip(saved_values) {
+                        value.try_set(saved as Integer).unwrap();
+                    }
+                } else {
+                    entry.error(
+                        "values",
+                        DefinitionErrorKind::WrongNumberOfValues {
+                            got: saved_values.len(),
+                            length: values.len(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(block_time) = entry.integer("block_time", 0, u32::MAX.into()) {
+        register.block_time = block_time as u32;
+    }
+
+    register.block_reason = match entry.optional_string("block_reason") {
+        Some(Some(name)) => {
+            let reason = BLOCK_REASONS
+                .iter()
+                .find(|&&(other, _)| other == name)
+                .map(|&(_, reason)| reason);
+
+            if reason.is_none() {
+                entry.error(
+                    "block_reason",
+                    DefinitionErrorKind::NoSuchBlockReason(name.to_owned()),
+                );
+            }
+
+            reason
+        }
+        _ => None,
+    };
+
+    register.block_state = BlockState::default();
+
+    if let Some(Some(cached_lines)) =
+        entry.optional_integer_array("cached_lines", Integer::MIN.into(), Integer::MAX.into())
+    {
+        if cached_lines.len() <= BlockCondition::MAXIMUM_CACHE_LINES {
+            for (cached, line) in register
+                .block_state
+                .cached_lines
+                .iter_mut()
+                .zip(cached_lines)
+            {
+                *cached = Some(line as Integer);
+            }
+        } else {
+            entry.error(
+                "cached_lines",
+                DefinitionErrorKind::WrongNumberOfValues {
+                    got: cached_lines.len(),
+                    length: BlockCondition::MAXIMUM_CACHE_LINES,
+                },
+            );
+        }
+    }
+
+    if let Some(Some(accesses)) = entry.optional_integer("accesses", 0, u32::MAX.into()) {
+        register.block_state.accesses = accesses as u32;
+    }
+
+    match (entry.optional_table("channel"), &mut register.channel) {
+        (Some(table), Some(channel)) => {
+            let mut channel_entry = entry.child("channel", table);
+
+            let outgoing =
+                channel_entry.optional_integer("outgoing", (-maximum).into(), maximum.into());
+            let delivered = channel_entry.optional_boolean("delivered");
+            let incoming =
+                channel_entry.optional_integer("incoming", (-maximum).into(), maximum.into());
+
+            channel_entry.finish(&["outgoing", "delivered", "incoming"]);
+
+            if let (Some(outgoing), Some(delivered), Some(incoming)) =
+                (outgoing, delivered, incoming)
+            {
+                *channel = Channel {
+                    outgoing: outgoing.map(|value| value as Integer),
+                    delivered: delivered.unwrap_or(false),
+                    incoming: incoming.map(|value| value as Integer),
+                };
+            }
+        }
+        (Some(_), None) => entry.error(
+            "channel",
+            DefinitionErrorKind::DifferentMachine {
+                saved: "a channel".to_owned(),
+                current: "not a channel".to_owned(),
+            },
+        ),
+        (None, Some(_)) => entry.error(
+            "channel",
+            DefinitionErrorKind::DifferentMachine {
+                saved: "not a channel".to_owned(),
+                current: "a channel".to_owned(),
+            },
+        ),
+        (None, None) => (),
+    }
+
+    entry.finish(&[
+        "digits",
+        "value",
+        "length",
+        "offset",
+        "index",
+        "values",
+        "block_time",
+        "block_reason",
+        "cached_lines",
+        "accesses",
+        "seeking",
+        "channel",
+    ]);
+}
+
+/// Loads the handler of each fault class, leaving out those which weren't saved.
+fn load_fault_handlers(mut entry: Entry, length: i64) -> [Option<u32>; FaultClass::COUNT] {
+    let mut handlers = [None; FaultClass::COUNT];
+
+    for class in FaultClass::iter() {
+        handlers[class as usize] = (entry.optional_integer(class.name(), 0, length).flatten())
+            .map(|handler| handler as u32);
+    }
+
+    entry.finish(&FaultClass::NAMES.map(|(name, _)| name));
+
+    handlers
+}
+
+/// Saves the number of each kind of instruction which has run, leaving out those which haven't.
+fn save_counts(counts: &InstructionKindMap<u8>) -> Value {
+    Value::Table(
+        InstructionKind::iter()
+            .filter(|&kind| counts[kind] > 0)
+            .map(|kind| {
+                let name = kind.get_default_properties().name.to_owned();
+
+                (name, Value::Integer(counts[kind].into()))
+            })
+            .collect(),
+    )
+}
+
+fn load_counts(mut entry: Entry) -> InstructionKindMap<u8> {
+    let mut counts = InstructionKindMap::from_element(0);
+
+    let table = entry.table;
+
+    for (name, value) in table {
+        let kind = InstructionKind::iter().find(|kind| kind.get_default_properties().name == name);
+
+        let Some(kind) = kind else {
+            entry.error(name, DefinitionErrorKind::NoSuchInstruction(name.clone()));
+            continue;
+        };
+
+        if let Some(count) = entry.as_integer(name, value, 0, u8::MAX.into()) {
+            counts[kind] = count as u8;
+        }
+    }
+
+    counts
+}
+
+fn load_previous_instruction(
+    mut entry: Entry,
+    program_length: usize,
+) -> Option<(u32, ArgumentValues)> {
+    let instruction = entry.integer("instruction", 0, program_length as i64 - 1);
+    let arguments = entry.table("arguments");
+
+    let mut argument_values = ArgumentValues::default();
+
+    for (key, value) in arguments.into_iter().flatten() {
+        let argument = (key.parse::<usize>().ok())
+            .filter(|argument| (1..=Instruction::NUM_ARGUMENTS).contains(argument));
+
+        let Some(argument) = argument else {
+            entry.error(
+                &format!("arguments.{key}"),
+                DefinitionErrorKind::UnexpectedEntry,
+            );
+            continue;
+        };
+
+        argument_values[argument - 1] = entry
+            .as_integer(
+                &format!("arguments.{key}"),
+                value,
+                Integer::MIN.into(),
+                Integer::MAX.into(),
+            )
+            .map(|value| value as Integer);
+    }
+
+    entry.finish(&["instruction", "arguments"]);
+
+    Some((instruction? as u32, argument_values))
+}
+
+#[must_use]
+fn register_name(register: u32) -> Value {
+    Value::String(computer::name_of_register(register).unwrap().to_string())
+}
+
+#[must_use]
+fn integer(value: u64) -> Value {
+    Value::Integer(i64::try_from(value).unwrap_or(i64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DEFAULT_MACHINE, DEFAULT_MACHINE_WITH_H};
+
+    const SOURCE_CODE: &str =
+        "HDL 4 LOOP\nLBL LOOP\nADD M 5 M\nCAL F\nADD H 1 H\nJMP LOOP\nLBL F\nADD X 1 X\nRET";
+    /// Only uses registers which every machine here has
+    const PORTABLE_SOURCE_CODE: &str = "LBL LOOP\nCAL F\nJMP LOOP\nLBL F\nADD X 1 X\nRET";
+
+    /// Runs the program for a while, so the state has something in most of its entries.
+    fn running(machine: &str, source_code: &str) -> (Computer, Program) {
+        let mut computer = Computer::load_from(machine).unwrap();
+        let program = Program::assemble_from("t".into(), source_code, &computer).unwrap();
+
+        for _ in 0..37 {
+            computer.step_tick(&program);
+        }
+
+        (computer, program)
+    }
+
+    fn errors_loading(machine: &str, source_code: &str, state: &str) -> Vec<DefinitionError> {
+        let mut computer = Computer::load_from(machine).unwrap();
+        let program = Program::assemble_from("t".into(), source_code, &computer).unwrap();
+
+        computer.load_state(state, &program).unwrap_err()
+    }
+
+    fn has_error(
+        errors: &[DefinitionError],
+        entry: &str,
+        is_kind: impl Fn(&DefinitionErrorKind) -> bool,
+    ) -> bool {
+        (errors.iter()).any(|error| error.entry == entry && is_kind(&error.kind))
+    }
+
+    #[test]
+    fn states_round_trip() {
+        let (mut computer, program) = running(DEFAULT_MACHINE_WITH_H, SOURCE_CODE);
+        let state = computer.save_state();
+
+        let mut loaded = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        loaded.load_state(&state, &program).unwrap();
+
+        assert_eq!(loaded.save_state(), state);
+
+        for _ in 0..100 {
+            computer.step_tick(&program);
+            loaded.step_tick(&program);
+        }
+
+        assert_eq!(loaded.save_state(), computer.save_state());
+    }
+
+    #[test]
+    fn states_from_other_machines_are_rejected() {
+        let (computer, _) = running(DEFAULT_MACHINE_WITH_H, PORTABLE_SOURCE_CODE);
+        let state = computer.save_state();
+
+        let errors = errors_loading(DEFAULT_MACHINE, PORTABLE_SOURCE_CODE, &state);
+        assert!(has_error(&errors, "registers.H", |kind| matches!(
+            kind,
+            DefinitionErrorKind::UndefinedRegister(_)
+        )));
+
+        let errors = errors_loading(
+            &DEFAULT_MACHINE_WITH_H.replace("digits = 3", "digits = 2"),
+            PORTABLE_SOURCE_CODE,
+            &state,
+        );
+        assert!(has_error(&errors, "registers.X.digits", |kind| matches!(
+            kind,
+            DefinitionErrorKind::DifferentMachine { .. }
+        )));
+
+        let (computer, _) = running(DEFAULT_MACHINE, PORTABLE_SOURCE_CODE);
+        let errors = errors_loading(
+            DEFAULT_MACHINE_WITH_H,
+            PORTABLE_SOURCE_CODE,
+            &computer.save_state(),
+        );
+        assert!(has_error(&errors, "registers.H", |kind| matches!(
+            kind,
+            DefinitionErrorKind::MissingEntry
+        )));
+    }
+
+    #[test]
+    fn invalid_states_are_rejected() {
+        let (computer, _) = running(DEFAULT_MACHINE_WITH_H, SOURCE_CODE);
+        let state = computer.save_state();
+
+        let old = state.replace(
+            &format!("version = {}", Computer::STATE_VERSION),
+            "version = 0",
+        );
+        let errors = errors_loading(DEFAULT_MACHINE_WITH_H, SOURCE_CODE, &old);
+        assert!(has_error(&errors, "version", |kind| matches!(
+            kind,
+            DefinitionErrorKind::UnsupportedVersion { got: 0, .. }
+        )));
+
+        let mut table = state.parse::<Table>().unwrap();
+        table.insert("instruction".to_owned(), 10.into());
+        table.insert("return_stack".to_owned(), Value::Array(vec![0.into()]));
+
+        let errors = errors_loading(DEFAULT_MACHINE_WITH_H, SOURCE_CODE, &table.to_string());
+        assert!(has_error(&errors, "instruction", |_| true));
+        assert!(has_error(&errors, "return_stack", |_| true));
+    }
+
+    #[test]
+    fn unknown_block_reasons_list_every_reason() {
+        let message = DefinitionErrorKind::NoSuchBlockReason("chanel".to_owned()).to_string();
+
+        for (name, _) in BLOCK_REASONS {
+            assert!(message.contains(&format!("\"{name}\"")), "{message}");
+        }
+    }
+
+    #[test]
+    fn buffered_writes_must_fit_their_register() {
+        let (computer, _) = running(DEFAULT_MACHINE, PORTABLE_SOURCE_CODE);
+        let mut table = computer.save_state().parse::<Table>().unwrap();
+
+        let write = |register: &str, value: i64| {
+            Value::Table(Table::from_iter([
+                ("register".to_owned(), register.into()),
+                ("value".to_owned(), value.into()),
+            ]))
+        };
+
+        table.insert(
+            "buffered_writes".to_owned(),
+            Value::Array(vec![write("X", 10_000), write("H", 1), write("Y", -9999)]),
+        );
+
+        let errors = errors_loading(DEFAULT_MACHINE, PORTABLE_SOURCE_CODE, &table.to_string());
+        assert!(has_error(
+            &errors,
+            "buffered_writes[0].value",
+            |kind| matches!(kind, DefinitionErrorKind::OutOfRange { got: 10_000, .. })
+        ));
+        assert!(has_error(
+            &errors,
+            "buffered_writes[1].register",
+            |kind| matches!(kind, DefinitionErrorKind::UndefinedRegister(_))
+        ));
+        assert_eq!(errors.len(), 2);
+    }
+}
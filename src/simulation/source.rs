@@ -0,0 +1,154 @@
+use std::{fmt::Display, fs, io, path::Path};
+
+use super::program::{COMMENT_SEPARATOR, INCLUDE_PSEUDO_INSTRUCTION};
+
+/// A line in one of the files of a program.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceLocation {
+    /// The index of the file in its [`SourceSet`]
+    pub file: u32,
+    pub line: u32,
+}
+
+impl SourceLocation {
+    pub const MAIN_FILE: u32 = 0;
+
+    #[must_use]
+    pub fn is_in_main_file(&self) -> bool {
+        self.file == Self::MAIN_FILE
+    }
+}
+
+/// The files of a program. The first is the main file, and the others are included by it.
+#[derive(Clone, Debug, Default)]
+pub struct SourceSet {
+    pub files: Vec<SourceFile>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SourceFile {
+    /// The path of the file, which is also how it's included
+    pub name: String,
+    pub text: String,
+}
+
+impl SourceSet {
+    #[must_use]
+    pub fn single(name: String, text: String) -> Self {
+        Self {
+            files: vec![SourceFile { name, text }],
+        }
+    }
+
+    /// Reads a program and every file it includes, with includes relative to the file they're in.
+    pub fn load(path: &str) -> Result<Self, SourceLoadError> {
+        Self::load_with(path, |path| fs::read_to_string(path))
+    }
+
+    /// Like [`load`](Self::load), but reads each file with `read`, such as to use the text of
+    /// files which are being edited.
+    pub fn load_with(
+        path: &str,
+        mut read: impl FnMut(&str) -> io::Result<String>,
+    ) -> Result<Self, SourceLoadError> {
+        let mut sources = Self::default();
+        let mut unread = vec![path.to_owned()];
+
+        while let Some(path) = unread.pop() {
+            if sources.index_of(&path).is_some() {
+                continue;
+            }
+
+            let text = read(&path).map_err(|error| SourceLoadError {
+                path: path.clone(),
+                error,
+            })?;
+
+            // Included files which are missing are reported when assembling, along with the line
+            // which included them
+            for line in text.lines() {
+                if let Some(include) = include_of_line(line)
+                    && let include = include_path(&path, include)
+                    && Path::new(&include).is_file()
+                {
+                    unread.push(include);
+                }
+            }
+
+            sources.files.push(SourceFile { name: path, text });
+        }
+
+        Ok(sources)
+    }
+
+    #[must_use]
+    pub fn index_of(&self, name: &str) -> Option<u32> {
+        self.files
+            .iter()
+            .position(|file| file.name == name)
+            .map(|index| index as u32)
+    }
+
+    #[must_use]
+    pub fn name_of(&self, file: u32) -> &str {
+        self.files.get(file as usize).map_or("", |file| &file.name)
+    }
+
+    #[must_use]
+    pub fn line(&self, location: SourceLocation) -> Option<&str> {
+        self.files
+            .get(location.file as usize)?
+            .text
+            .lines()
+            .nth(location.line as usize)
+    }
+}
+
+/// Returns the file named by an include directive, if the line is one.
+#[must_use]
+pub fn include_of_line(line: &str) -> Option<&str> {
+    let line = line
+        .split_once(COMMENT_SEPARATOR)
+        .map_or(line, |(line, _)| line)
+        .trim();
+
+    let argument = line.strip_prefix(INCLUDE_PSEUDO_INSTRUCTION)?;
+
+    if !argument.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    unquote(argument.trim())
+}
+
+/// Returns the text between a pair of double quotes surrounding `text`.
+#[must_use]
+pub fn unquote(text: &str) -> Option<&str> {
+    text.strip_prefix('"')?
+        .strip_suffix('"')
+        .filter(|text| !text.contains('"'))
+}
+
+/// The path of a file included from another, which is relative to the directory of the file
+/// including it.
+#[must_use]
+pub fn include_path(from: &str, include: &str) -> String {
+    Path::new(from)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(include)
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[derive(Debug)]
+pub struct SourceLoadError {
+    pub path: String,
+    pub error: io::Error,
+}
+
+impl Display for SourceLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.error)
+    }
+}
@@ -0,0 +1,781 @@
+use std::{
+    fmt::{Display, Write},
+    ops::Add,
+};
+
+use super::{
+    argument::{Argument, NumberSource},
+    computer::{BlockCondition, Computer, Register},
+    format,
+    instruction::{
+        ArgumentValues, Instruction, InstructionKind, InstructionKindMap, InstructionProperties,
+        PropertyCondition,
+    },
+    program::{COMMENT_SEPARATOR, LABEL_PSEUDO_INSTRUCTION, Program},
+};
+
+/// The number of ticks something can take, from the fewest to the most. There's no most if it can
+/// wait forever, such as for a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TickRange {
+    pub minimum: u64,
+    pub maximum: Option<u64>,
+}
+
+impl TickRange {
+    pub const ZERO: Self = Self::exact(0);
+
+    #[must_use]
+    pub const fn exact(ticks: u64) -> Self {
+        Self {
+            minimum: ticks,
+            maximum: Some(ticks),
+        }
+    }
+}
+
+impl Add for TickRange {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            minimum: self.minimum + rhs.minimum,
+            maximum: self.maximum.zip(rhs.maximum).map(|(a, b)| a + b),
+        }
+    }
+}
+
+impl Display for TickRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.maximum {
+            Some(maximum) if maximum == self.minimum => write!(f, "{maximum}"),
+            Some(maximum) => write!(f, "{}-{maximum}", self.minimum),
+            None => write!(f, "{}+", self.minimum),
+        }
+    }
+}
+
+/// A run of instructions which is only jumped to at its start and only jumps away at its end.
+#[derive(Clone, Debug)]
+pub struct BasicBlock {
+    /// The index of the first instruction
+    pub start: u32,
+    /// The index after the last instruction
+    pub end: u32,
+    /// The indices of the blocks which can run after this one
+    pub successors: Vec<usize>,
+    /// Whether the program can end after this block
+    pub can_end: bool,
+    pub ticks: TickRange,
+    /// The ticks taken by each instruction, including any wait for `calls_per_tick_limit`
+    pub instruction_ticks: Vec<TickRange>,
+    /// Instructions which can have to wait for the next tick because `calls_per_tick_limit` has
+    /// been reached in the current one
+    pub limited_instructions: Vec<u32>,
+}
+
+impl BasicBlock {
+    #[must_use]
+    pub fn is_over_limit(&self) -> bool {
+        !self.limited_instructions.is_empty()
+    }
+}
+
+/// The cost of each basic block of a program, worked out without running it.
+#[derive(Clone, Debug)]
+pub struct TimingAnalysis {
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl TimingAnalysis {
+    #[must_use]
+    pub fn analyse(program: &Program, computer: &Computer) -> Self {
+        let instructions = &program.instructions;
+        let properties = &computer.instruction_properties;
+
+        let mut starts = vec![0];
+
+        for (index, instruction) in instructions.iter().enumerate() {
+            for argument in instruction.arguments {
+                if let Argument::Instruction(target) = argument {
+                    starts.push(target);
+                }
+            }
+
+            if is_jump(instruction.kind)
+                || matches!(
+                    instruction.kind,
+                    InstructionKind::Call | InstructionKind::Return | InstructionKind::End
+                )
+            {
+                starts.push(index as u32 + 1);
+            }
+        }
+
+        // A return can go back to after any call
+        let return_addresses = (instructions.iter().enumerate())
+            .filter(|(_, instruction)| instruction.kind == InstructionKind::Call)
+            .map(|(index, _)| index as u32 + 1)
+            .collect::<Vec<_>>();
+
+        starts.retain(|&start| (start as usize) < instructions.len());
+        starts.sort_unstable();
+        starts.dedup();
+
+        let block_of = |instruction: u32| starts.partition_point(|&start| start <= instruction) - 1;
+
+        let mut blocks = Vec::with_capacity(starts.len());
+
+        for (i, &start) in starts.iter().enumerate() {
+            let end = starts
+                .get(i + 1)
+                .copied()
+                .unwrap_or(instructions.len() as u32);
+
+            let last = &instructions[end as usize - 1];
+
+            let mut next = Vec::new();
+
+            if last.kind == InstructionKind::Call {
+                next.push(*last.arguments[0].as_instruction().unwrap());
+            } else if last.kind == InstructionKind::Return {
+                next.extend(&return_addresses);
+            } else if last.kind != InstructionKind::End {
+                let taken = is_jump(last.kind).then(|| jump_taken(last));
+
+                if let Some(taken) = taken
+                    && taken != Some(false)
+                {
+                    next.push(*last.arguments[1].as_instruction().unwrap());
+                }
+
+                if taken != Some(Some(true)) {
+                    next.push(end);
+                }
+            }
+
+            let mut successors = (next.iter())
+                .filter(|&&next| (next as usize) < instructions.len())
+                .map(|&next| block_of(next))
+                .collect::<Vec<_>>();
+            successors.sort_unstable();
+            successors.dedup();
+
+            blocks.push(BasicBlock {
+                start,
+                end,
+                successors,
+                can_end: last.kind == InstructionKind::End
+                    || next.iter().any(|&next| next as usize >= instructions.len()),
+                ticks: TickRange::ZERO,
+                instruction_ticks: Vec::new(),
+                limited_instructions: Vec::new(),
+            });
+        }
+
+        let mut predecessors = vec![Vec::new(); blocks.len()];
+
+        for (i, block) in blocks.iter().enumerate() {
+            for &successor in &block.successors {
+                predecessors[successor].push(i);
+            }
+        }
+
+        let times = (blocks.iter().enumerate())
+            .map(|(i, block)| {
+                let mut previous = (predecessors[i].iter())
+                    .flat_map(|&predecessor| {
+                        let end = blocks[predecessor].end as usize;
+                        let before_last = (end - 1 > blocks[predecessor].start as usize)
+                            .then(|| &instructions[end - 2]);
+
+                        let (_, condition) =
+                            instruction_time(&instructions[end - 1], &[before_last], computer);
+
+                        previous_after(&instructions[end - 1], condition)
+                    })
+                    .collect::<Vec<_>>();
+
+                if i == 0 || previous.is_empty() {
+                    previous.push(None);
+                }
+
+                (block.start..block.end)
+                    .map(|index| {
+                        let instruction = &instructions[index as usize];
+                        let (time, condition) = instruction_time(instruction, &previous, computer);
+
+                        previous = previous_after(instruction, condition);
+
+                        time
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // The most instructions of each group which can have run in the tick each block starts in,
+        // which is found by following the blocks around until nothing changes
+        let mut entry_counts = vec![InstructionKindMap::from_element(0u8); blocks.len()];
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for (i, block) in blocks.iter().enumerate() {
+                let (_, _, exit_counts) =
+                    count_ticks(instructions, block, &times[i], entry_counts[i], properties);
+
+                for &successor in &block.successors {
+                    for (count, &exit_count) in
+                        entry_counts[successor].0.iter_mut().zip(&exit_counts.0)
+                    {
+                        if exit_count > *count {
+                            *count = exit_count;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (i, block) in blocks.iter_mut().enumerate() {
+            let (instruction_ticks, limited_instructions, _) =
+                count_ticks(instructions, block, &times[i], entry_counts[i], properties);
+
+            block.ticks =
+                (instruction_ticks.iter()).fold(TickRange::ZERO, |total, &ticks| total + ticks);
+
+            // Running past the last instruction stops the program in a tick of its own, which END
+            // counts as its time
+            if block.can_end && instructions[block.end as usize - 1].kind != InstructionKind::End {
+                block.ticks = block.ticks
+                    + TickRange {
+                        minimum: block.successors.is_empty().into(),
+                        maximum: Some(1),
+                    };
+            }
+            block.instruction_ticks = instruction_ticks;
+            block.limited_instructions = limited_instructions;
+        }
+
+        Self { blocks }
+    }
+
+    /// Returns the index of the block containing an instruction.
+    #[must_use]
+    pub fn block_of(&self, instruction: u32) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| (block.start..block.end).contains(&instruction))
+    }
+
+    /// Writes the program in the same way as [`format::disassemble`], with the ticks taken by each
+    /// block and instruction in comments.
+    #[must_use]
+    pub fn listing(&self, program: &Program) -> String {
+        let labels = format::labels_of(program);
+
+        let mut lines = Vec::new();
+
+        for (i, block) in self.blocks.iter().enumerate() {
+            let plural = if block.ticks == TickRange::exact(1) {
+                ""
+            } else {
+                "s"
+            };
+
+            let mut header = format!("Block {i}: {} tick{plural}", block.ticks);
+
+            if block.is_over_limit() {
+                header.push_str(", over the calls per tick limit");
+            }
+
+            let mut next = (block.successors.iter())
+                .map(|successor| format!("block {successor}"))
+                .collect::<Vec<_>>();
+
+            if block.can_end {
+                next.push("the end".to_owned());
+            }
+
+            if !next.is_empty() {
+                write!(header, ", then {}", next.join(" or ")).unwrap();
+            }
+
+            lines.push((String::new(), header));
+
+            for label in labels.get(&block.start).into_iter().flatten() {
+                lines.push((format!("{LABEL_PSEUDO_INSTRUCTION} {label}"), String::new()));
+            }
+
+            for index in block.start..block.end {
+                let instruction = &program.instructions[index as usize];
+
+                let mut comment = format!(
+                    "{} ({})",
+                    block.instruction_ticks[(index - block.start) as usize],
+                    program.describe_location(instruction.location),
+                );
+
+                if block.limited_instructions.contains(&index) {
+                    comment.push_str(", can wait for the calls per tick limit");
+                }
+
+                lines.push((format::instruction_source(instruction, &labels), comment));
+            }
+        }
+
+        let comment_column = (lines.iter())
+            .map(|(code, _)| code.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut listing = String::new();
+
+        for (code, comment) in lines {
+            listing.push_str(&code);
+
+            if !comment.is_empty() {
+                if !code.is_empty() {
+                    listing.push_str(&" ".repeat(comment_column - code.chars().count() + 1));
+                }
+
+                write!(listing, "{COMMENT_SEPARATOR} {comment}").unwrap();
+            }
+
+            listing.push('\n');
+        }
+
+        listing
+    }
+}
+
+#[must_use]
+fn is_jump(kind: InstructionKind) -> bool {
+    matches!(
+        kind,
+        InstructionKind::Jump | InstructionKind::JumpCondLikely | InstructionKind::JumpCondUnlikely
+    )
+}
+
+/// Returns whether a jump is always taken, never taken, or `None` if it depends on the registers.
+#[must_use]
+fn jump_taken(instruction: &Instruction) -> Option<bool> {
+    match instruction.arguments[0] {
+        Argument::Empty => Some(true),
+        argument => argument.as_constant().map(|value| value != 0),
+    }
+}
+
+/// Returns the time taken by an instruction, and the condition for its conditional time (if it has
+/// one) along with whether it's met, given every instruction which could have run before it.
+fn instruction_time(
+    instruction: &Instruction,
+    previous: &[Option<&Instruction>],
+    computer: &Computer,
+) -> (TickRange, Option<(Option<bool>, PropertyCondition)>) {
+    let properties = &computer.instruction_properties[instruction.kind];
+
+    // Ending the program ends the tick, which isn't counted by any other instruction
+    if instruction.kind == InstructionKind::End {
+        return (TickRange::exact(1), None);
+    }
+
+    let matched = properties.conditional_time.map(|(_, condition)| {
+        let matched = condition_matches(instruction, &condition, previous, computer);
+
+        (matched, condition)
+    });
+
+    let mut times = Vec::new();
+
+    if let Some((time, _)) = properties.conditional_time
+        && matched.is_some_and(|(matched, _)| matched != Some(false))
+    {
+        times.push(time);
+    }
+
+    if matched.is_none_or(|(matched, _)| matched != Some(true)) {
+        times.push(properties.base_time);
+    }
+
+    let mut time = TickRange {
+        minimum: times.iter().copied().min().unwrap().into(),
+        maximum: times.iter().copied().max().map(u64::from),
+    };
+
+    if instruction.kind == InstructionKind::Sleep {
+        time = time
+            + match instruction.arguments[0].as_constant() {
+                Some(value) => TickRange::exact(value.max(0) as u64),
+                None => TickRange {
+                    minimum: 0,
+                    maximum: None,
+                },
+            };
+    }
+
+    let mut read_time = TickRange::ZERO;
+
+    for register in instruction.registers_read(&computer.instruction_properties) {
+        let Some(register) = computer.registers.get(register) else {
+            continue;
+        };
+
+        let access_time = TickRange::exact(register.read_time.into()) + block_time(register);
+
+        read_time = TickRange {
+            minimum: read_time.minimum.max(access_time.minimum),
+            maximum: read_time
+                .maximum
+                .zip(access_time.maximum)
+                .map(|(a, b)| a.max(b)),
+        };
+    }
+
+    let mut write_time = TickRange::ZERO;
+    let mut write_block_time = TickRange::ZERO;
+
+    if let Some(argument) = instruction.written_argument()
+        && let Some(register) = instruction.arguments[argument].as_register()
+        && let Some(register) = computer.registers.get(register)
+    {
+        write_time = TickRange::exact(register.time_to_write().into());
+        write_block_time = block_time(register);
+
+        // These only write when their condition is true or false
+        if matches!(
+            instruction.kind,
+            InstructionKind::CompareSetIfTrue | InstructionKind::CompareSetIfFalse
+        ) {
+            write_time.minimum = 0;
+        }
+    }
+
+    let total = read_time + time;
+
+    let total = TickRange {
+        minimum: total.minimum.max(write_block_time.minimum),
+        maximum: total
+            .maximum
+            .zip(write_block_time.maximum)
+            .map(|(a, b)| a.max(b)),
+    } + write_time;
+
+    (total, matched)
+}
+
+/// Returns every instruction which could be remembered as the previous instruction after an
+/// instruction runs, in the same way as [`Computer::step_cycle`].
+#[must_use]
+fn previous_after(
+    instruction: &Instruction,
+    condition: Option<(Option<bool>, PropertyCondition)>,
+) -> Vec<Option<&Instruction>> {
+    match condition {
+        Some((matched, condition)) if condition.allows_cascade() => match matched {
+            Some(true) => vec![None],
+            Some(false) => vec![Some(instruction)],
+            None => vec![Some(instruction), None],
+        },
+        _ => vec![Some(instruction)],
+    }
+}
+
+/// The time spent waiting to access a register, which is unlimited for channels.
+#[must_use]
+fn block_time(register: &Register) -> TickRange {
+    if register.channel.is_some() {
+        return TickRange {
+            minimum: 0,
+            maximum: None,
+        };
+    }
+
+    let maximum = match register.block_condition {
+        Some(
+            BlockCondition::IndexChange { block_time, .. }
+            | BlockCondition::Sequential { block_time },
+        ) => block_time,
+        Some(BlockCondition::Cache { miss_time, .. }) => miss_time,
+        Some(BlockCondition::WriteBack { latency }) => latency,
+        Some(BlockCondition::Contention { cooldown, .. }) => cooldown,
+        None => return TickRange::ZERO,
+    };
+
+    TickRange {
+        minimum: 0,
+        maximum: Some(maximum.into()),
+    }
+}
+
+/// Returns whether a condition is always met, never met, or `None` if it depends on the registers
+/// or on which instruction ran before.
+#[must_use]
+fn condition_matches(
+    instruction: &Instruction,
+    condition: &PropertyCondition,
+    previous: &[Option<&Instruction>],
+    computer: &Computer,
+) -> Option<bool> {
+    match *condition {
+        PropertyCondition::SameAsPrevious { kind, .. } => {
+            let mut matches = previous.iter().map(|previous| match previous {
+                Some(previous) if previous.kind == kind => {
+                    reads_same_values(previous, instruction, computer).then_some(true)
+                }
+                _ => Some(false),
+            });
+
+            let first = matches.next().unwrap_or(Some(false))?;
+
+            matches
+                .all(|matched| matched == Some(first))
+                .then_some(first)
+        }
+        PropertyCondition::ArgumentMatches { argument, value } => {
+            match instruction.arguments[argument] {
+                Argument::Number(NumberSource::Register(_)) | Argument::Comparison(_) => None,
+                argument => Some(argument.as_constant() == Some(value)),
+            }
+        }
+        PropertyCondition::ArgumentTypeMatches {
+            argument,
+            requirement,
+        } => Some(instruction.arguments[argument].matches_requirement(requirement)),
+        PropertyCondition::Always => Some(true),
+    }
+}
+
+/// Returns whether an instruction definitely reads the same values as the instruction just before
+/// it.
+#[must_use]
+fn reads_same_values(
+    previous: &Instruction,
+    instruction: &Instruction,
+    computer: &Computer,
+) -> bool {
+    let properties = &computer.instruction_properties;
+
+    let read = instruction.registers_read(properties);
+
+    let written = previous
+        .written_argument()
+        .and_then(|argument| previous.arguments[argument].as_register());
+
+    // Writing to an index register changes the value read from its array
+    let changes_read_value = written.is_some_and(|written| {
+        read.iter().any(|&register| {
+            register == written
+                || computer
+                    .registers
+                    .get(register)
+                    .is_some_and(|register| register.indexed_by == Some(written))
+        })
+    });
+
+    let reads_channel = read.iter().any(|&register| {
+        (computer.registers.get(register)).is_some_and(|register| register.channel.is_some())
+    });
+
+    let same_arguments = (0..Instruction::NUM_ARGUMENTS)
+        .filter(|&argument| Some(argument) != instruction.written_argument())
+        .all(|argument| {
+            previous.arguments[argument].to_string() == instruction.arguments[argument].to_string()
+        });
+
+    same_arguments && !changes_read_value && !reads_channel
+}
+
+/// Returns the ticks taken by each instruction in a block, the instructions which can wait for
+/// `calls_per_tick_limit`, and the most instructions of each group which can have run in the tick
+/// when the block ends.
+fn count_ticks(
+    instructions: &[Instruction],
+    block: &BasicBlock,
+    times: &[TickRange],
+    entry_counts: InstructionKindMap<u8>,
+    properties: &InstructionKindMap<InstructionProperties>,
+) -> (Vec<TickRange>, Vec<u32>, InstructionKindMap<u8>) {
+    // The fewest and most instructions of each group which can have run in the current tick
+    let mut fewest = InstructionKindMap::from_element(0u8);
+    let mut most = entry_counts;
+
+    let mut instruction_ticks = Vec::new();
+    let mut limited_instructions = Vec::new();
+
+    let mut previous = None;
+    let no_values = ArgumentValues::default();
+
+    for (index, &time) in (block.start..block.end).zip(times) {
+        let instruction = &instructions[index as usize];
+
+        let group = instruction.group(properties, previous.map(|previous| (previous, &no_values)));
+
+        let mut ticks = time;
+
+        if let Some(limit) = properties[instruction.kind].calls_per_tick_limit {
+            let limit = limit.get();
+
+            if fewest[group] >= limit {
+                ticks = ticks + TickRange::exact(1);
+                fewest = InstructionKindMap::from_element(0);
+                most = InstructionKindMap::from_element(0);
+                limited_instructions.push(index);
+            } else if most[group] >= limit {
+                ticks.maximum = ticks.maximum.map(|maximum| maximum + 1);
+                fewest = InstructionKindMap::from_element(0);
+                most[group] = limit - 1;
+                limited_instructions.push(index);
+            }
+        }
+
+        fewest[group] = fewest[group].saturating_add(1);
+        most[group] = most[group].saturating_add(1);
+
+        // The tick ends after any instruction which takes time
+        if time.maximum != Some(0) {
+            fewest = InstructionKindMap::from_element(0);
+        }
+
+        if time.minimum > 0 {
+            most = InstructionKindMap::from_element(0);
+        }
+
+        instruction_ticks.push(ticks);
+        previous = Some(instruction);
+    }
+
+    (instruction_ticks, limited_instructions, most)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_MACHINE_WITH_H;
+
+    fn analyse(source_code: &str) -> TimingAnalysis {
+        let computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        let program = Program::assemble_from("t".into(), source_code, &computer).unwrap();
+
+        TimingAnalysis::analyse(&program, &computer)
+    }
+
+    fn ticks(analysis: &TimingAnalysis) -> Vec<String> {
+        (analysis.blocks.iter())
+            .map(|block| block.ticks.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn dividing_the_same_values_again_is_faster() {
+        let analysis = analyse("DIV X Y Z\nMOD X Y Z");
+
+        assert_eq!(
+            analysis.blocks[0].instruction_ticks,
+            [TickRange::exact(4), TickRange::exact(1)],
+        );
+    }
+
+    #[test]
+    fn dividing_values_which_may_differ_has_a_range() {
+        // The values read might still be the same, such as if X and Y are equal
+        for source_code in [
+            "DIV X Y Z\nMOD Y X Z",
+            "DIV X Y X\nMOD X Y Z",
+            "DIV X 3 I\nMOD D 3 Y",
+        ] {
+            let analysis = analyse(source_code);
+            let block = &analysis.blocks[0];
+
+            assert_eq!(
+                block.instruction_ticks[1].maximum.unwrap() - block.instruction_ticks[1].minimum,
+                3,
+                "{source_code:?} takes {}",
+                block.instruction_ticks[1],
+            );
+        }
+    }
+
+    #[test]
+    fn blocks_include_every_instruction_which_could_run_before_them() {
+        let analysis = analyse("LJP X = 0 MODULUS\nDIV X Y Z\nLBL MODULUS\nMOD X Y Z");
+
+        assert_eq!(analysis.blocks[1].ticks, TickRange::exact(4));
+        assert_eq!(analysis.blocks[0].successors, [1, 2]);
+        assert_eq!(
+            analysis.blocks[2].instruction_ticks[0],
+            TickRange {
+                minimum: 1,
+                maximum: Some(4),
+            },
+        );
+    }
+
+    #[test]
+    fn sleeping_for_a_register_has_no_most() {
+        assert_eq!(ticks(&analyse("SLP X\nEND")), ["1+"]);
+        assert_eq!(ticks(&analyse("SLP 3\nEND")), ["4"]);
+    }
+
+    #[test]
+    fn blocks_which_can_wait_for_the_calls_per_tick_limit_are_flagged() {
+        // The second jump can run in the same tick as the first, which takes no time
+        let analysis = analyse("JMP 1 A\nLBL A\nJMP 1 B\nLBL B\nEND");
+
+        assert_eq!(ticks(&analysis), ["0", "0-1", "1"]);
+        assert!(!analysis.blocks[0].is_over_limit());
+        assert!(analysis.blocks[1].is_over_limit());
+        assert_eq!(analysis.blocks[1].limited_instructions, [1]);
+        assert!(!analysis.blocks[2].is_over_limit());
+
+        // A loop can come back to its jump in the same tick
+        let analysis = analyse("LBL L\nJMP L");
+
+        assert_eq!(ticks(&analysis), ["0-1"]);
+        assert_eq!(analysis.blocks[0].limited_instructions, [0]);
+    }
+
+    #[test]
+    fn stopping_takes_a_tick() {
+        assert_eq!(ticks(&analyse("SET X 1")), ["2"]);
+        assert_eq!(ticks(&analyse("SET X 1\nEND")), ["2"]);
+
+        // The loop only sometimes stops
+        let analysis = analyse("LBL L\nSET X 1\nLJP X = 0 L");
+        let block = &analysis.blocks[0];
+
+        assert_eq!(
+            block.ticks,
+            block.instruction_ticks[0]
+                + block.instruction_ticks[1]
+                + TickRange {
+                    minimum: 0,
+                    maximum: Some(1),
+                },
+        );
+    }
+
+    #[test]
+    fn runs_take_a_time_within_the_bounds() {
+        let source_code = "SET X 7\nSET Y 3\nDIV X Y Z\nMOD X Y Z\nDIV X 2 X\nMOD X 2 Z";
+        let analysis = analyse(source_code);
+        let ticks = analysis.blocks[0].ticks;
+
+        let mut computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        let program = Program::assemble_from("t".into(), source_code, &computer).unwrap();
+
+        while computer.interrupt.is_none() {
+            computer.step_tick(&program);
+        }
+
+        assert!(
+            computer.runtime >= ticks.minimum
+                && ticks
+                    .maximum
+                    .is_none_or(|maximum| computer.runtime <= maximum),
+            "ran for {} ticks, expected {ticks}",
+            computer.runtime,
+        );
+    }
+}
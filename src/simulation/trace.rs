@@ -0,0 +1,815 @@
+use std::{
+    fmt::{Display, Write as _},
+    io::{self, Read, Write},
+};
+
+use super::{
+    computer::{self, BlockReason, Computer, RegisterAccessError, RegisterSet},
+    instruction::{ArgumentValues, ArithmaticError, Instruction, InstructionEvaluationInterrupt},
+    integer::{AssignIntegerError, BiggerInteger, Integer},
+    program::Program,
+};
+
+/// Everything a computer did while running a program, recorded by a [`Computer`] with tracing
+/// enabled. Can be saved to a file, and checked against a fresh run to find where they diverge.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    /// The number of instructions in the program which was run
+    pub program_length: u32,
+    /// The runtime of the computer when recording stopped
+    pub runtime: u64,
+    pub events: Vec<TraceEvent>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// An instruction ran, with the values of its arguments
+    Executed {
+        instruction: u32,
+        argument_values: ArgumentValues,
+        time: u32,
+        energy: u32,
+    },
+    /// A value was written to a register once the instruction writing it finished
+    Write {
+        register: u32,
+        value: Integer,
+    },
+    /// A register was blocked, e.g. by a change to the register indexing it or by waiting for the
+    /// other end of a channel
+    Blocked {
+        register: u32,
+        reason: BlockReason,
+        time: u32,
+    },
+    /// An instruction waited for the next tick because its `calls_per_tick_limit` was reached
+    WaitedForLimit {
+        instruction: u32,
+    },
+    /// An instruction caused a fault, and the program jumped to its handler
+    Faulted {
+        instruction: u32,
+        code: Integer,
+    },
+    /// The program stopped
+    Interrupted {
+        instruction: u32,
+        interrupt: InstructionEvaluationInterrupt,
+    },
+    EndOfTick,
+}
+
+impl Trace {
+    pub const MAGIC: &[u8; 8] = b"ZRHOTRCE";
+    pub const VERSION: u16 = 4;
+
+    const EXECUTED: u8 = 0;
+    const WRITE: u8 = 1;
+    const BLOCKED: u8 = 2;
+    const WAITED_FOR_LIMIT: u8 = 3;
+    const INTERRUPTED: u8 = 4;
+    const END_OF_TICK: u8 = 5;
+    const FAULTED: u8 = 6;
+
+    /// Records a program being run from the computer's current state until it stops or reaches the
+    /// tick limit.
+    #[must_use]
+    pub fn record(mut computer: Computer, program: &Program, tick_limit: Option<u64>) -> Self {
+        computer.trace = Some(Box::new(Self {
+            program_length: program.instructions.len() as u32,
+            runtime: computer.runtime,
+            events: Vec::new(),
+        }));
+
+        while computer.interrupt.is_none()
+            && tick_limit.is_none_or(|limit| computer.runtime < limit)
+        {
+            computer.step_tick(program);
+        }
+
+        *computer.trace.unwrap()
+    }
+
+    /// Runs a program from the computer's current state for as long as the trace was recorded,
+    /// returning the first place where the run doesn't match the trace.
+    pub fn check(&self, computer: Computer, program: &Program) -> Result<(), TraceDivergence> {
+        let length = program.instructions.len() as u32;
+
+        if length != self.program_length {
+            return Err(TraceDivergence::ProgramLength {
+                expected: self.program_length,
+                got: length,
+            });
+        }
+
+        let replayed = Self::record(computer, program, Some(self.runtime));
+
+        let Some(event) = (0..self.events.len().max(replayed.events.len()))
+            .find(|&i| self.events.get(i) != replayed.events.get(i))
+        else {
+            return Ok(());
+        };
+
+        let tick = (self.events[..event.min(self.events.len())].iter())
+            .filter(|&event| *event == TraceEvent::EndOfTick)
+            .count() as u64;
+
+        Err(TraceDivergence::Event {
+            event,
+            tick,
+            expected: self.events.get(event).cloned(),
+            got: replayed.events.get(event).cloned(),
+        })
+    }
+
+    /// Applies the writes of an instruction which has finished, recording them along with any
+    /// registers they block.
+    pub fn apply_buffered_writes(&mut self, registers: &mut RegisterSet) {
+        let block_times = (registers.registers.iter())
+            .map(|register| register.as_ref().map_or(0, |register| register.block_time))
+            .collect::<Vec<_>>();
+
+        self.events.extend(
+            (registers.buffered_writes().iter())
+                .map(|&(register, value)| TraceEvent::Write { register, value }),
+        );
+
+        registers.apply_buffered_writes();
+
+        for (register, (data, block_time)) in
+            registers.registers.iter().zip(block_times).enumerate()
+        {
+            if let Some(data) = data
+                && data.block_time > block_time
+                && let Some(reason) = data.block_reason
+            {
+                self.events.push(TraceEvent::Blocked {
+                    register: register as u32,
+                    reason,
+                    time: data.block_time,
+                });
+            }
+        }
+    }
+
+    /// Writes the trace in a compact binary format, starting with a header which has its version.
+    pub fn write_to(&self, mut output: impl Write) -> io::Result<()> {
+        let mut bytes = Self::MAGIC.to_vec();
+        bytes.extend(Self::VERSION.to_le_bytes());
+
+        write_unsigned(&mut bytes, self.program_length.into());
+        write_unsigned(&mut bytes, self.runtime);
+        write_unsigned(&mut bytes, self.events.len() as u64);
+
+        for event in &self.events {
+            match event {
+                TraceEvent::Executed {
+                    instruction,
+                    argument_values,
+                    time,
+                    energy,
+                } => {
+                    bytes.push(Self::EXECUTED);
+                    write_unsigned(&mut bytes, (*instruction).into());
+
+                    // One bit for each argument which has a value
+                    bytes.push(
+                        (argument_values.iter().enumerate())
+                            .filter(|(_, value)| value.is_some())
+                            .fold(0, |mask, (i, _)| mask | 1 << i),
+                    );
+
+                    for &value in argument_values.iter().flatten() {
+                        write_signed(&mut bytes, value);
+                    }
+
+                    write_unsigned(&mut bytes, (*time).into());
+                    write_unsigned(&mut bytes, (*energy).into());
+                }
+                TraceEvent::Write { register, value } => {
+                    bytes.push(Self::WRITE);
+                    write_unsigned(&mut bytes, (*register).into());
+                    write_signed(&mut bytes, *value);
+                }
+                TraceEvent::Blocked {
+                    register,
+                    reason,
+                    time,
+                } => {
+                    bytes.push(Self::BLOCKED);
+                    write_unsigned(&mut bytes, (*register).into());
+                    bytes.push(*reason as u8);
+                    write_unsigned(&mut bytes, (*time).into());
+                }
+                TraceEvent::WaitedForLimit { instruction } => {
+                    bytes.push(Self::WAITED_FOR_LIMIT);
+                    write_unsigned(&mut bytes, (*instruction).into());
+                }
+                TraceEvent::Interrupted {
+                    instruction,
+                    interrupt,
+                } => {
+                    bytes.push(Self::INTERRUPTED);
+                    write_unsigned(&mut bytes, (*instruction).into());
+                    write_interrupt(&mut bytes, *interrupt);
+                }
+                TraceEvent::Faulted { instruction, code } => {
+                    bytes.push(Self::FAULTED);
+                    write_unsigned(&mut bytes, (*instruction).into());
+                    write_signed(&mut bytes, *code);
+                }
+                TraceEvent::EndOfTick => bytes.push(Self::END_OF_TICK),
+            }
+        }
+
+        output.write_all(&bytes)
+    }
+
+    /// Reads a trace written by [`Trace::write_to`].
+    pub fn read_from(mut input: impl Read) -> Result<Self, TraceReadError> {
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes).map_err(TraceReadError::Io)?;
+
+        let Some(bytes) = bytes.strip_prefix(Self::MAGIC) else {
+            return Err(TraceReadError::NotATrace);
+        };
+
+        let mut reader = Reader { bytes };
+
+        let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+
+        if version != Self::VERSION {
+            return Err(TraceReadError::UnsupportedVersion { got: version });
+        }
+
+        let program_length = reader.unsigned()?;
+        let runtime = reader.unsigned()?;
+        let length = reader.unsigned::<usize>()?;
+
+        let mut events = Vec::with_capacity(length.min(reader.bytes.len()));
+
+        for _ in 0..length {
+            events.push(match reader.byte()? {
+                Self::EXECUTED => {
+                    let instruction = reader.unsigned()?;
+                    let mask = reader.byte()?;
+
+                    let mut argument_values = ArgumentValues::default();
+
+                    for (i, value) in argument_values.iter_mut().enumerate() {
+                        if mask & 1 << i != 0 {
+                            *value = Some(reader.signed()?);
+                        }
+                    }
+
+                    TraceEvent::Executed {
+                        instruction,
+                        argument_values,
+                        time: reader.unsigned()?,
+                        energy: reader.unsigned()?,
+                    }
+                }
+                Self::WRITE => TraceEvent::Write {
+                    register: reader.unsigned()?,
+                    value: reader.signed()?,
+                },
+                Self::BLOCKED => TraceEvent::Blocked {
+                    register: reader.unsigned()?,
+                    reason: match reader.byte()? {
+                        0 => BlockReason::IndexIncreased,
+                        1 => BlockReason::IndexWrittenNoOp,
+                        2 => BlockReason::IndexDecreased,
+                        3 => BlockReason::NotSequential,
+                        4 => BlockReason::CacheMiss,
+                        5 => BlockReason::WriteBack,
+                        6 => BlockReason::Contention,
+                        7 => BlockReason::Channel,
+                        _ => return Err(TraceReadError::Invalid),
+                    },
+                    time: reader.unsigned()?,
+                },
+                Self::WAITED_FOR_LIMIT => TraceEvent::WaitedForLimit {
+                    instruction: reader.unsigned()?,
+                },
+                Self::INTERRUPTED => TraceEvent::Interrupted {
+                    instruction: reader.unsigned()?,
+                    interrupt: reader.interrupt()?,
+                },
+                Self::END_OF_TICK => TraceEvent::EndOfTick,
+                Self::FAULTED => TraceEvent::Faulted {
+                    instruction: reader.unsigned()?,
+                    code: reader.signed()?,
+                },
+                _ => return Err(TraceReadError::Invalid),
+            });
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(TraceReadError::Invalid);
+        }
+
+        Ok(Self {
+            program_length,
+            runtime,
+            events,
+        })
+    }
+
+    /// Writes the trace as text, with one event on each line after the tick it happened in, so
+    /// that two traces can be compared with a diff.
+    #[must_use]
+    pub fn dump(&self) -> String {
+        let mut dump = format!(
+            "trace version {version}, {length} instructions, {runtime} ticks\n",
+            version = Self::VERSION,
+            length = self.program_length,
+            runtime = self.runtime,
+        );
+
+        let mut tick = 0;
+
+        for event in &self.events {
+            if *event == TraceEvent::EndOfTick {
+                tick += 1;
+            } else {
+                writeln!(dump, "{tick}: {event}").unwrap();
+            }
+        }
+
+        dump
+    }
+}
+
+impl Display for TraceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let register_name = |register: u32| computer::name_of_register(register).unwrap_or('?');
+
+        match self {
+            TraceEvent::Executed {
+                instruction,
+                argument_values,
+                time,
+                energy,
+            } => {
+                write!(f, "run {instruction}")?;
+
+                for value in argument_values.iter().take(Instruction::NUM_ARGUMENTS) {
+                    match value {
+                        Some(value) => write!(f, " {value}")?,
+                        None => write!(f, " _")?,
+                    }
+                }
+
+                write!(f, ", time {time}, energy {energy}")
+            }
+            TraceEvent::Write { register, value } => {
+                write!(f, "write {value} to {}", register_name(*register))
+            }
+            TraceEvent::Blocked {
+                register,
+                reason,
+                time,
+            } => write!(
+                f,
+                "block {} for {time} ({reason:?})",
+                register_name(*register)
+            ),
+            TraceEvent::WaitedForLimit { instruction } => {
+                write!(f, "wait {instruction} for the calls per tick limit")
+            }
+            TraceEvent::Interrupted {
+                instruction,
+                interrupt,
+            } => write!(f, "interrupt {instruction}: {interrupt:?}"),
+            TraceEvent::Faulted { instruction, code } => {
+                write!(f, "fault {instruction} with code {code}")
+            }
+            TraceEvent::EndOfTick => write!(f, "end of tick"),
+        }
+    }
+}
+
+/// Writes a number in as few bytes as possible, seven bits at a time.
+fn write_unsigned(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    bytes.push(value as u8);
+}
+
+/// Writes a signed number so that numbers close to zero take few bytes.
+fn write_signed(bytes: &mut Vec<u8>, value: impl Into<BiggerInteger>) {
+    let value = value.into();
+
+    write_unsigned(
+        bytes,
+        ((value << 1) ^ (value >> (BiggerInteger::BITS - 1))) as u64,
+    );
+}
+
+/// Writes an interrupt as a tag for its kind followed by its fields, with the errors it holds
+/// written the same way.
+fn write_interrupt(bytes: &mut Vec<u8>, interrupt: InstructionEvaluationInterrupt) {
+    match interrupt {
+        InstructionEvaluationInterrupt::RegisterError { register, error } => {
+            bytes.push(0);
+            write_unsigned(bytes, register.into());
+
+            match error {
+                RegisterAccessError::IndexTooBig { got, maximum } => {
+                    bytes.push(0);
+                    write_signed(bytes, got);
+                    write_signed(bytes, maximum);
+                }
+                RegisterAccessError::IndexTooSmall { got, minimum } => {
+                    bytes.push(1);
+                    write_signed(bytes, got);
+                    write_signed(bytes, minimum);
+                }
+                RegisterAccessError::NoSuchRegister { got } => {
+                    bytes.push(2);
+                    write_unsigned(bytes, got.into());
+                }
+                RegisterAccessError::InvalidAssignment { error } => {
+                    bytes.push(3);
+
+                    match error {
+                        AssignIntegerError::ValueTooBig { got, maximum } => {
+                            bytes.push(0);
+                            write_signed(bytes, got);
+                            write_signed(bytes, maximum);
+                        }
+                        AssignIntegerError::ValueTooSmall { got, minimum } => {
+                            bytes.push(1);
+                            write_signed(bytes, got);
+                            write_signed(bytes, minimum);
+                        }
+                        AssignIntegerError::ValueMuchTooBig { got, maximum } => {
+                            bytes.push(2);
+                            write_signed(bytes, got);
+                            write_signed(bytes, maximum);
+                        }
+                        AssignIntegerError::ValueMuchTooSmall { got, minimum } => {
+                            bytes.push(3);
+                            write_signed(bytes, got);
+                            write_signed(bytes, minimum);
+                        }
+                        AssignIntegerError::NumDigitsNotSupported => bytes.push(4),
+                    }
+                }
+                RegisterAccessError::ChannelEmpty => bytes.push(4),
+            }
+        }
+        InstructionEvaluationInterrupt::ArithmaticError {
+            error: ArithmaticError::DivideByZero,
+        } => bytes.extend([1, 0]),
+        InstructionEvaluationInterrupt::ProgramComplete => bytes.push(2),
+        InstructionEvaluationInterrupt::ReturnStackOverflow => bytes.push(3),
+        InstructionEvaluationInterrupt::ReturnStackUnderflow => bytes.push(4),
+        InstructionEvaluationInterrupt::NoSuchFaultClass { got } => {
+            bytes.push(5);
+            write_signed(bytes, got);
+        }
+        InstructionEvaluationInterrupt::RuntimeCounterOverflow => bytes.push(6),
+        InstructionEvaluationInterrupt::EnergyCounterOverflow => bytes.push(7),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, TraceReadError> {
+        let (&byte, rest) = self.bytes.split_first().ok_or(TraceReadError::Truncated)?;
+        self.bytes = rest;
+
+        Ok(byte)
+    }
+
+    fn unsigned<T: TryFrom<u64>>(&mut self) -> Result<T, TraceReadError> {
+        let mut value = 0u64;
+
+        for shift in (0..u64::BITS).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return T::try_from(value).map_err(|_| TraceReadError::Invalid);
+            }
+        }
+
+        Err(TraceReadError::Invalid)
+    }
+
+    fn signed<T: TryFrom<BiggerInteger>>(&mut self) -> Result<T, TraceReadError> {
+        let value = self.unsigned::<u64>()?;
+
+        T::try_from((value >> 1) as BiggerInteger ^ -((value & 1) as BiggerInteger))
+            .map_err(|_| TraceReadError::Invalid)
+    }
+
+    /// Reads an interrupt written by `write_interrupt`.
+    fn interrupt(&mut self) -> Result<InstructionEvaluationInterrupt, TraceReadError> {
+        Ok(match self.byte()? {
+            0 => InstructionEvaluationInterrupt::RegisterError {
+                register: self.unsigned()?,
+                error: match self.byte()? {
+                    0 => RegisterAccessError::IndexTooBig {
+                        got: self.signed()?,
+                        maximum: self.signed()?,
+                    },
+                    1 => RegisterAccessError::IndexTooSmall {
+                        got: self.signed()?,
+                        minimum: self.signed()?,
+                    },
+                    2 => RegisterAccessError::NoSuchRegister {
+                        got: self.unsigned()?,
+                    },
+                    3 => RegisterAccessError::InvalidAssignment {
+                        error: match self.byte()? {
+                            0 => AssignIntegerError::ValueTooBig {
+                                got: self.signed()?,
+                                maximum: self.signed()?,
+                            },
+                            1 => AssignIntegerError::ValueTooSmall {
+                                got: self.signed()?,
+                                minimum: self.signed()?,
+                            },
+                            2 => AssignIntegerError::ValueMuchTooBig {
+                                got: self.signed()?,
+                                maximum: self.signed()?,
+                            },
+                            3 => AssignIntegerError::ValueMuchTooSmall {
+                                got: self.signed()?,
+                                minimum: self.signed()?,
+                            },
+                            4 => AssignIntegerError::NumDigitsNotSupported,
+                            _ => return Err(TraceReadError::Invalid),
+                        },
+                    },
+                    4 => RegisterAccessError::ChannelEmpty,
+                    _ => return Err(TraceReadError::Invalid),
+                },
+            },
+            1 => InstructionEvaluationInterrupt::ArithmaticError {
+                error: match self.byte()? {
+                    0 => ArithmaticError::DivideByZero,
+                    _ => return Err(TraceReadError::Invalid),
+                },
+            },
+            2 => InstructionEvaluationInterrupt::ProgramComplete,
+            3 => InstructionEvaluationInterrupt::ReturnStackOverflow,
+            4 => InstructionEvaluationInterrupt::ReturnStackUnderflow,
+            5 => InstructionEvaluationInterrupt::NoSuchFaultClass {
+                got: self.signed()?,
+            },
+            6 => InstructionEvaluationInterrupt::RuntimeCounterOverflow,
+            7 => InstructionEvaluationInterrupt::EnergyCounterOverflow,
+            _ => return Err(TraceReadError::Invalid),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceReadError {
+    Io(io::Error),
+    NotATrace,
+    UnsupportedVersion { got: u16 },
+    Truncated,
+    Invalid,
+}
+
+impl Display for TraceReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceReadError::Io(error) => write!(f, "{error}"),
+            TraceReadError::NotATrace => write!(f, "Not a trace file"),
+            TraceReadError::UnsupportedVersion { got } => write!(
+                f,
+                "Trace version {got} isn't supported (expected version {})",
+                Trace::VERSION,
+            ),
+            TraceReadError::Truncated => write!(f, "The trace ends early"),
+            TraceReadError::Invalid => write!(f, "The trace is corrupted"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum TraceDivergence {
+    ProgramLength {
+        expected: u32,
+        got: u32,
+    },
+    Event {
+        /// The index of the first event which doesn't match
+        event: usize,
+        tick: u64,
+        expected: Option<TraceEvent>,
+        got: Option<TraceEvent>,
+    },
+}
+
+impl Display for TraceDivergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let describe = |event: &Option<TraceEvent>| {
+            event
+                .as_ref()
+                .map_or_else(|| "nothing".to_owned(), TraceEvent::to_string)
+        };
+
+        match self {
+            TraceDivergence::ProgramLength { expected, got } => write!(
+                f,
+                "The trace was recorded with a program of {expected} instructions, but this one \
+                 has {got}",
+            ),
+            TraceDivergence::Event {
+                event,
+                tick,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Diverged at event {event} (tick {tick}): expected {expected}, got {got}",
+                expected = describe(expected),
+                got = describe(got),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{computer::register_with_name, *};
+    use crate::DEFAULT_MACHINE_WITH_H;
+
+    const SOURCE_CODE: &str = "HDL 4 DIVIDED\nSET M 5\nSET H 3\nDIV H 0 X\nLBL DIVIDED\nADD H 1 X";
+
+    fn record(source_code: &str) -> (Trace, Computer, Program) {
+        let computer = Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap();
+        let program = Program::assemble_from("t".into(), source_code, &computer).unwrap();
+
+        (
+            Trace::record(computer.clone(), &program, None),
+            computer,
+            program,
+        )
+    }
+
+    fn write(trace: &Trace) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        trace.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn traces_round_trip_through_files() {
+        let (trace, computer, program) = record(SOURCE_CODE);
+
+        for expected in [
+            TraceEvent::EndOfTick,
+            TraceEvent::Write {
+                register: register_with_name('X').unwrap(),
+                value: 4,
+            },
+        ] {
+            assert!(trace.events.contains(&expected), "missing {expected}");
+        }
+        assert!((trace.events.iter()).any(|event| matches!(event, TraceEvent::Blocked { .. })));
+        assert!((trace.events.iter()).any(|event| matches!(event, TraceEvent::Faulted { .. })));
+
+        let read = Trace::read_from(write(&trace).as_slice()).unwrap();
+
+        assert_eq!(read, trace);
+        assert!(read.check(computer, &program).is_ok());
+    }
+
+    #[test]
+    fn interrupts_round_trip_through_files() {
+        let assignment = |error| InstructionEvaluationInterrupt::RegisterError {
+            register: 3,
+            error: RegisterAccessError::InvalidAssignment { error },
+        };
+
+        let interrupts = [
+            InstructionEvaluationInterrupt::RegisterError {
+                register: 8,
+                error: RegisterAccessError::IndexTooBig {
+                    got: 100,
+                    maximum: 99,
+                },
+            },
+            InstructionEvaluationInterrupt::RegisterError {
+                register: 8,
+                error: RegisterAccessError::IndexTooSmall {
+                    got: -1,
+                    minimum: 0,
+                },
+            },
+            InstructionEvaluationInterrupt::RegisterError {
+                register: 0,
+                error: RegisterAccessError::NoSuchRegister { got: 7 },
+            },
+            InstructionEvaluationInterrupt::RegisterError {
+                register: 2,
+                error: RegisterAccessError::ChannelEmpty,
+            },
+            assignment(AssignIntegerError::ValueTooBig {
+                got: 10_000,
+                maximum: 9999,
+            }),
+            assignment(AssignIntegerError::ValueTooSmall {
+                got: -10_000,
+                minimum: -9999,
+            }),
+            assignment(AssignIntegerError::ValueMuchTooBig {
+                got: BiggerInteger::MAX,
+                maximum: 9999,
+            }),
+            assignment(AssignIntegerError::ValueMuchTooSmall {
+                got: BiggerInteger::MIN,
+                minimum: -9999,
+            }),
+            assignment(AssignIntegerError::NumDigitsNotSupported),
+            ArithmaticError::DivideByZero.into(),
+            InstructionEvaluationInterrupt::ProgramComplete,
+            InstructionEvaluationInterrupt::ReturnStackOverflow,
+            InstructionEvaluationInterrupt::ReturnStackUnderflow,
+            InstructionEvaluationInterrupt::NoSuchFaultClass { got: Integer::MIN },
+            InstructionEvaluationInterrupt::RuntimeCounterOverflow,
+            InstructionEvaluationInterrupt::EnergyCounterOverflow,
+        ];
+
+        let trace = Trace {
+            program_length: 1,
+            runtime: 0,
+            events: (interrupts.into_iter())
+                .map(|interrupt| TraceEvent::Interrupted {
+                    instruction: 0,
+                    interrupt,
+                })
+                .collect(),
+        };
+
+        assert_eq!(Trace::read_from(write(&trace).as_slice()).unwrap(), trace);
+    }
+
+    #[test]
+    fn changed_programs_diverge() {
+        let (trace, computer, _) = record(SOURCE_CODE);
+
+        let changed = Program::assemble_from(
+            "t".into(),
+            &SOURCE_CODE.replace("ADD H 1 X", "ADD H 2 X"),
+            &computer,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            trace.check(computer.clone(), &changed),
+            Err(TraceDivergence::Event {
+                expected: Some(TraceEvent::Executed { .. }),
+                got: Some(TraceEvent::Executed { .. }),
+                ..
+            })
+        ));
+
+        let longer =
+            Program::assemble_from("t".into(), &format!("{SOURCE_CODE}\nEND"), &computer).unwrap();
+
+        assert!(matches!(
+            trace.check(computer, &longer),
+            Err(TraceDivergence::ProgramLength {
+                expected: 5,
+                got: 6,
+            })
+        ));
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let (trace, _, _) = record(SOURCE_CODE);
+        let bytes = write(&trace);
+
+        assert!(matches!(
+            Trace::read_from(&b"not a trace"[..]),
+            Err(TraceReadError::NotATrace)
+        ));
+        assert!(matches!(
+            Trace::read_from(&bytes[..bytes.len() - 1]),
+            Err(TraceReadError::Truncated)
+        ));
+
+        let mut old = bytes.clone();
+        old[Trace::MAGIC.len()..][..2].copy_from_slice(&0u16.to_le_bytes());
+
+        assert!(matches!(
+            Trace::read_from(old.as_slice()),
+            Err(TraceReadError::UnsupportedVersion { got: 0 })
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+use std::{env, fs, path::PathBuf, process};
+
+/// A directory of files for a test, for the parts of the program which only read from disk. The
+/// directory and everything in it is deleted when this is dropped, even if the test fails.
+pub struct TestDirectory {
+    path: PathBuf,
+}
+
+impl TestDirectory {
+    /// Creates an empty directory. `name` has to be unique among the tests, since they run at the
+    /// same time.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("zrho-{name}-{}", process::id()));
+
+        // Left over if a previous run with the same process ID was killed
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        Self { path }
+    }
+
+    /// Writes a file to the directory, returning its path.
+    pub fn write(&self, name: &str, text: &str) -> String {
+        let path = self.path.join(name);
+        fs::write(&path, text).unwrap();
+
+        path.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -1,10 +1,19 @@
+pub mod register_visualisation;
 pub mod state;
+pub mod text_editor;
 pub mod utils;
 pub mod window;
+pub mod workspace;
 
 use std::{env, sync::LazyLock};
 
 const START_IN_FULLSCREEN: bool = true;
 
+/// The name the monospace font is registered with. The width of each character is
+/// [`FONT_ASPECT`] times the font size.
+const FONT: &str = "CommitMono";
+
+const FONT_ASPECT: f32 = 0.6;
+
 static DRAW_FPS: LazyLock<bool> =
     LazyLock::new(|| env::args().skip(1).any(|argument| argument == "--draw-fps"));
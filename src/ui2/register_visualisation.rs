@@ -0,0 +1,725 @@
+use std::{cell::RefCell, f32::consts::FRAC_1_SQRT_2, rc::Rc};
+
+use ggez::{
+    Context,
+    graphics::{
+        Canvas, Color, DrawParam, Mesh, MeshBuilder, Quad, Rect, Text, TextFragment, TextLayout,
+    },
+};
+use nalgebra::{Point2, Vector2, vector};
+
+use crate::{
+    simulation::{
+        computer::{self, BlockReason, Computer, Register, RegisterAccessError, RegisterValues},
+        integer::Integer,
+    },
+    ui2::{
+        FONT, FONT_ASPECT, utils,
+        window::{ElementDrawInfo, ElementUpdateEvent, ElementUpdateInfo, WindowElement},
+    },
+};
+
+/// Shows the registers of a computer, which may be changed by other elements while it runs.
+#[derive(Debug)]
+pub struct RegisterPanel {
+    pub computer: Rc<RefCell<Computer>>,
+    pub layout: RegisterVisualisationLayout,
+    pub num_rows: usize,
+}
+
+impl WindowElement for RegisterPanel {
+    fn update(&mut self, ctx: &mut Context, _info: ElementUpdateInfo) -> Vec<ElementUpdateEvent> {
+        let computer = self.computer.borrow();
+        let mut events = Vec::new();
+
+        // The computer may have been replaced with one for a different machine
+        if !self.layout.matches(&computer) {
+            self.layout = RegisterVisualisationLayout::new(&computer, self.num_rows);
+
+            events.push(ElementUpdateEvent::SetHeight {
+                new_height: self.layout.size().y + Self::PADDING * 2.0,
+            });
+        }
+
+        self.layout
+            .update(&computer, ctx.time.delta().as_secs_f32());
+
+        events
+    }
+
+    fn draw(&mut self, ctx: &mut Context, info: ElementDrawInfo) {
+        self.layout.draw_at(
+            ctx,
+            info.canvas,
+            info.offset.add_scalar(Self::PADDING),
+            &self.computer.borrow(),
+        );
+    }
+}
+
+impl RegisterPanel {
+    pub const PADDING: f32 = 10.0;
+
+    #[must_use]
+    pub fn new(computer: Rc<RefCell<Computer>>, num_rows: usize) -> Self {
+        let layout = RegisterVisualisationLayout::new(&computer.borrow(), num_rows);
+
+        Self {
+            computer,
+            layout,
+            num_rows,
+        }
+    }
+
+    /// The size needed to show every register.
+    #[must_use]
+    pub fn size(&self) -> Vector2<f32> {
+        self.layout.size().add_scalar(Self::PADDING * 2.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RegisterVisualisationLayout {
+    pub visualisations: Vec<RegisterVisualisation>,
+}
+
+impl RegisterVisualisationLayout {
+    pub const TEXT_HEIGHT: f32 = 15.0;
+    pub const TEXT_WIDTH: f32 = Self::TEXT_HEIGHT * FONT_ASPECT;
+
+    pub const HORIZONTAL_SPACING: f32 = Self::TEXT_WIDTH * 1.5;
+    pub const VERTICAL_SPACING: f32 = Self::TEXT_HEIGHT / 2.0;
+    pub const COLUMN_WIDTH: f32 = RegisterVisualisation::WIDTH + Self::HORIZONTAL_SPACING;
+
+    pub const TITLE_COLOR: Color = Color::new(0.0, 0.5, 1.0, 1.0);
+    pub const BACKGROUND_COLOR: Color = Color::new(0.05, 0.05, 0.05, 1.0);
+    pub const ERROR_COLOR: Color = Color::new(1.0, 0.0, 0.0, 1.0);
+    pub const CHANGED_COLOR: Color = Color::new(1.0, 0.94, 0.0, 1.0);
+    pub const CURRENT_ELEMENT_COLOR: Color = Color::new(0.2, 0.2, 0.2, 1.0);
+    pub const FADED_TEXT_COLOR: Color = Color::new(0.5, 0.5, 0.5, 1.0);
+
+    /// Scalars are placed in columns of `num_rows`, followed by a column for each vector with its
+    /// index register above it.
+    #[must_use]
+    pub fn new(computer: &Computer, num_rows: usize) -> Self {
+        let num_rows = num_rows.max(1);
+
+        let mut scalars = Vec::new();
+        let mut indexes = Vec::new();
+        let mut vectors = Vec::new();
+
+        for (i, register) in computer.registers.registers.iter().enumerate() {
+            let Some(register) = register else { continue };
+
+            let visualisation = RegisterVisualisation::new(i as u32, register);
+
+            let visualisation_set = match visualisation.value_visualisation {
+                ValueVisualisation::Scalar => &mut scalars,
+                ValueVisualisation::Index => &mut indexes,
+                ValueVisualisation::Vector { .. } => &mut vectors,
+            };
+
+            visualisation_set.push(visualisation);
+        }
+
+        let mut visualisations = Vec::new();
+
+        scalars.sort_by_key(|visualisation| computer::ordering_of_register(visualisation.register));
+
+        let vectors_start = scalars.len().div_ceil(num_rows);
+
+        for (i, mut visualisation) in scalars.into_iter().enumerate() {
+            visualisation.position = RegisterVisualisationPosition {
+                column: i / num_rows,
+                row: i % num_rows,
+            };
+
+            visualisations.push(visualisation);
+        }
+
+        for (visualisation, column) in vectors.iter_mut().zip(vectors_start..) {
+            let indexed = computer
+                .registers
+                .get(visualisation.register)
+                .is_some_and(|register| register.indexed_by.is_some());
+
+            visualisation.position = RegisterVisualisationPosition {
+                column,
+                row: indexed as usize,
+            };
+
+            visualisations.push(visualisation.clone());
+        }
+
+        for mut visualisation in indexes {
+            let target_register = computer
+                .registers
+                .get(visualisation.register)
+                .and_then(|register| register.indexes_array);
+
+            let Some(target) = vectors
+                .iter()
+                .find(|vector| Some(vector.register) == target_register)
+            else {
+                continue;
+            };
+
+            visualisation.position = RegisterVisualisationPosition {
+                column: target.position.column,
+                row: 0,
+            };
+
+            visualisations.push(visualisation);
+        }
+
+        Self { visualisations }
+    }
+
+    /// Returns `true` if the layout shows exactly the registers of the computer.
+    #[must_use]
+    pub fn matches(&self, computer: &Computer) -> bool {
+        let registers = computer
+            .registers
+            .registers
+            .iter()
+            .filter(|register| register.is_some());
+
+        registers.count() == self.visualisations.len()
+            && self.visualisations.iter().all(|visualisation| {
+                computer
+                    .registers
+                    .get(visualisation.register)
+                    .is_some_and(|register| {
+                        register.values.is_vector() == visualisation.value_visualisation.is_vector()
+                    })
+            })
+    }
+
+    #[must_use]
+    pub fn size(&self) -> Vector2<f32> {
+        self.visualisations
+            .iter()
+            .map(|visualisation| visualisation.offset() + visualisation.size())
+            .fold(vector![0.0, 0.0], |size: Vector2<f32>, corner| {
+                size.sup(&corner)
+            })
+    }
+
+    pub fn update(&mut self, computer: &Computer, frame_time: f32) {
+        for visualisation in &mut self.visualisations {
+            if let Some(register) = computer.registers.get(visualisation.register) {
+                visualisation.update(register, frame_time);
+            }
+        }
+    }
+
+    pub fn draw_at(
+        &self,
+        ctx: &Context,
+        canvas: &mut Canvas,
+        location: Vector2<f32>,
+        computer: &Computer,
+    ) {
+        for visualisation in &self.visualisations {
+            let Some(register) = computer.registers.get(visualisation.register) else {
+                continue;
+            };
+
+            visualisation.draw_at(canvas, location + visualisation.offset(), register);
+        }
+
+        // Arrows from each index register to the current element of its array
+        for visualisation in &self.visualisations {
+            let Some(array) = computer
+                .registers
+                .get(visualisation.register)
+                .and_then(|register| register.indexes_array)
+            else {
+                continue;
+            };
+
+            let Some(array_visualisation) = self
+                .visualisations
+                .iter()
+                .find(|other| other.register == array)
+            else {
+                continue;
+            };
+
+            let start = location
+                + visualisation.offset()
+                + vector![RegisterVisualisation::WIDTH / 2.0, Self::TEXT_HEIGHT * 2.0];
+            let end = location
+                + array_visualisation.offset()
+                + vector![RegisterVisualisation::WIDTH / 2.0, Self::TEXT_HEIGHT];
+
+            draw_arrow(
+                ctx,
+                canvas,
+                start.into(),
+                end.into(),
+                2.0,
+                Self::VERTICAL_SPACING / 2.0,
+                Self::TITLE_COLOR,
+            );
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RegisterVisualisationPosition {
+    pub column: usize,
+    pub row: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct RegisterVisualisation {
+    pub register: u32,
+    pub value_visualisation: ValueVisualisation,
+    pub position: RegisterVisualisationPosition,
+    /// The values shown last frame, to detect changes
+    pub previous_values: Box<[Integer]>,
+    /// How recently each value changed, from 1 when it changes down to 0
+    pub change_highlights: Box<[f32]>,
+}
+
+impl RegisterVisualisation {
+    pub const WIDTH: f32 = RegisterVisualisationLayout::TEXT_WIDTH * 6.0;
+    pub const BASE_HEIGHT: f32 = RegisterVisualisationLayout::TEXT_HEIGHT * 2.0;
+    pub const HEIGHT: f32 = Self::BASE_HEIGHT + RegisterVisualisationLayout::VERTICAL_SPACING;
+
+    /// The number of elements shown around the current element of a vector
+    pub const VECTOR_ELEMENTS: usize = 7;
+
+    pub const CHANGE_FADE_SPEED: f32 = 4.0;
+    pub const SCROLL_SPEED: f32 = 25.0;
+
+    #[must_use]
+    pub fn new(index: u32, register: &Register) -> Self {
+        let values = register
+            .values
+            .all_values()
+            .iter()
+            .map(|value| value.get())
+            .collect::<Box<[_]>>();
+
+        Self {
+            register: index,
+            value_visualisation: match register.values {
+                RegisterValues::Scalar(..) => {
+                    if register.indexes_array.is_none() {
+                        ValueVisualisation::Scalar
+                    } else {
+                        ValueVisualisation::Index
+                    }
+                }
+                RegisterValues::Vector { .. } => {
+                    let index = register
+                        .values
+                        .element_index()
+                        .min(values.len().saturating_sub(1));
+
+                    ValueVisualisation::Vector {
+                        index,
+                        scroll: index as f32,
+                    }
+                }
+            },
+            position: RegisterVisualisationPosition::default(),
+            change_highlights: vec![0.0; values.len()].into_boxed_slice(),
+            previous_values: values,
+        }
+    }
+
+    #[must_use]
+    pub fn offset(&self) -> Vector2<f32> {
+        let nudge_up = self.value_visualisation.is_vector() && self.position.row % 2 == 1;
+
+        vector![
+            self.position.column as f32 * RegisterVisualisationLayout::COLUMN_WIDTH,
+            self.position.row as f32 * Self::HEIGHT
+                - nudge_up as usize as f32 * RegisterVisualisationLayout::VERTICAL_SPACING,
+        ]
+    }
+
+    #[must_use]
+    pub fn size(&self) -> Vector2<f32> {
+        let rows = if self.value_visualisation.is_vector() {
+            Self::VECTOR_ELEMENTS
+        } else {
+            1
+        };
+
+        vector![
+            Self::WIDTH,
+            (rows + 1) as f32 * RegisterVisualisationLayout::TEXT_HEIGHT,
+        ]
+    }
+
+    pub fn update(&mut self, register: &Register, frame_time: f32) {
+        let values = register.values.all_values();
+
+        if values.len() != self.previous_values.len() {
+            *self = Self {
+                position: self.position,
+                ..Self::new(self.register, register)
+            };
+        }
+
+        for ((previous, highlight), value) in self
+            .previous_values
+            .iter_mut()
+            .zip(&mut self.change_highlights)
+            .zip(values)
+        {
+            if *previous != value.get() {
+                *previous = value.get();
+                *highlight = 1.0;
+            } else {
+                *highlight = utils::exp_decay_cutoff(
+                    *highlight,
+                    0.0,
+                    Self::CHANGE_FADE_SPEED,
+                    frame_time,
+                    0.01,
+                )
+                .0;
+            }
+        }
+
+        if let ValueVisualisation::Vector { index, scroll } = &mut self.value_visualisation {
+            *index = register
+                .values
+                .element_index()
+                .min(values.len().saturating_sub(1));
+
+            *scroll = utils::exp_decay_cutoff(
+                *scroll,
+                *index as f32,
+                Self::SCROLL_SPEED,
+                frame_time,
+                0.01,
+            )
+            .0;
+        }
+    }
+
+    pub fn draw_at(&self, canvas: &mut Canvas, location: Vector2<f32>, register: &Register) {
+        const TEXT_HEIGHT: f32 = RegisterVisualisationLayout::TEXT_HEIGHT;
+        const TEXT_WIDTH: f32 = RegisterVisualisationLayout::TEXT_WIDTH;
+
+        let name = computer::name_of_register(self.register).unwrap();
+
+        // Title, which is shortened to make room for the block time while blocked
+        let title_width = if register.block_time > 0 {
+            TEXT_WIDTH * 2.0
+        } else {
+            Self::WIDTH
+        };
+
+        draw_rectangle(
+            canvas,
+            location,
+            vector![title_width, TEXT_HEIGHT],
+            RegisterVisualisationLayout::TITLE_COLOR,
+        );
+        draw_centered_text(
+            canvas,
+            &name.to_string(),
+            location,
+            title_width,
+            RegisterVisualisationLayout::BACKGROUND_COLOR,
+        );
+
+        if register.block_time > 0 {
+            let reason = match register.block_reason {
+                Some(BlockReason::IndexIncreased) => "↑",
+                Some(BlockReason::IndexDecreased) => "↓",
+                Some(BlockReason::IndexWrittenNoOp) => "=",
+                Some(BlockReason::NotSequential) => "↕",
+                Some(BlockReason::CacheMiss) => "×",
+                Some(BlockReason::WriteBack) => "←",
+                Some(BlockReason::Contention) => "#",
+                Some(BlockReason::Channel) => "↔",
+                None => "",
+            };
+
+            let location = location + vector![title_width, 0.0];
+            let width = Self::WIDTH - title_width;
+
+            draw_rectangle(
+                canvas,
+                location,
+                vector![width, TEXT_HEIGHT],
+                RegisterVisualisationLayout::BACKGROUND_COLOR,
+            );
+            draw_centered_text(
+                canvas,
+                &format!("{}{reason}", register.block_time),
+                location,
+                width,
+                Color::WHITE,
+            );
+        }
+
+        let location = location + vector![0.0, TEXT_HEIGHT];
+
+        match self.value_visualisation {
+            ValueVisualisation::Scalar | ValueVisualisation::Index => {
+                self.draw_value(canvas, location, register, 0);
+            }
+            ValueVisualisation::Vector { index, scroll } => {
+                let values = register.values.all_values();
+
+                draw_rectangle(
+                    canvas,
+                    location,
+                    vector![Self::WIDTH, Self::VECTOR_ELEMENTS as f32 * TEXT_HEIGHT],
+                    RegisterVisualisationLayout::BACKGROUND_COLOR,
+                );
+
+                // The current element is kept in the middle
+                let middle = (Self::VECTOR_ELEMENTS / 2) as f32;
+                let first = (scroll - middle).floor().max(0.0) as usize;
+                let last = ((scroll + middle).ceil() as usize).min(values.len().saturating_sub(1));
+
+                for (element, value) in values.iter().enumerate().take(last + 1).skip(first) {
+                    let row = element as f32 - scroll + middle;
+
+                    if !(-0.5..Self::VECTOR_ELEMENTS as f32 - 0.5).contains(&row) {
+                        continue;
+                    }
+
+                    let location = location + vector![0.0, row * TEXT_HEIGHT];
+
+                    if element == index {
+                        self.draw_value(canvas, location, register, element);
+                    } else {
+                        self.draw_element(canvas, location, element, value.get());
+                    }
+                }
+
+                draw_outline(
+                    canvas,
+                    location,
+                    vector![Self::WIDTH, Self::VECTOR_ELEMENTS as f32 * TEXT_HEIGHT],
+                    RegisterVisualisationLayout::TITLE_COLOR,
+                );
+            }
+        }
+    }
+
+    /// Draws the value which the register would read, or why it can't be read.
+    fn draw_value(
+        &self,
+        canvas: &mut Canvas,
+        location: Vector2<f32>,
+        register: &Register,
+        element: usize,
+    ) {
+        const TEXT_HEIGHT: f32 = RegisterVisualisationLayout::TEXT_HEIGHT;
+
+        let size = vector![Self::WIDTH, TEXT_HEIGHT];
+
+        let (value, background_color, foreground_color) = match register.values.value() {
+            Ok(value) => (
+                value.to_string(),
+                utils::color_lerp(
+                    RegisterVisualisationLayout::CURRENT_ELEMENT_COLOR,
+                    RegisterVisualisationLayout::CHANGED_COLOR,
+                    self.change_highlights[element] * 0.5,
+                ),
+                Color::WHITE,
+            ),
+            Err(error) => {
+                let index_name = register
+                    .indexed_by
+                    .and_then(computer::name_of_register)
+                    .unwrap_or('?');
+
+                let message = match error {
+                    RegisterAccessError::IndexTooBig { maximum, .. } => {
+                        format!("{index_name}>{maximum}")
+                    }
+                    RegisterAccessError::IndexTooSmall { minimum, .. } => {
+                        format!("{index_name}<{minimum}")
+                    }
+                    _ => "?".to_owned(),
+                };
+
+                (
+                    message,
+                    RegisterVisualisationLayout::ERROR_COLOR,
+                    RegisterVisualisationLayout::BACKGROUND_COLOR,
+                )
+            }
+        };
+
+        draw_rectangle(canvas, location, size, background_color);
+        draw_centered_text(canvas, &value, location, Self::WIDTH, foreground_color);
+        draw_outline(
+            canvas,
+            location,
+            size,
+            RegisterVisualisationLayout::TITLE_COLOR,
+        );
+    }
+
+    /// Draws an element of a vector other than the current one.
+    fn draw_element(
+        &self,
+        canvas: &mut Canvas,
+        location: Vector2<f32>,
+        element: usize,
+        value: Integer,
+    ) {
+        let size = vector![Self::WIDTH, RegisterVisualisationLayout::TEXT_HEIGHT];
+
+        let highlight = self.change_highlights[element];
+
+        if highlight > 0.0 {
+            draw_rectangle(
+                canvas,
+                location,
+                size,
+                utils::color_lerp(
+                    RegisterVisualisationLayout::BACKGROUND_COLOR,
+                    RegisterVisualisationLayout::CHANGED_COLOR,
+                    highlight * 0.5,
+                ),
+            );
+        }
+
+        draw_centered_text(
+            canvas,
+            &value.to_string(),
+            location,
+            Self::WIDTH,
+            RegisterVisualisationLayout::FADED_TEXT_COLOR,
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ValueVisualisation {
+    Scalar,
+    Index,
+    Vector {
+        /// The position of the current element in the vector
+        index: usize,
+        /// Follows `index` smoothly
+        scroll: f32,
+    },
+}
+
+impl ValueVisualisation {
+    /// Returns `true` if the value visualisation is [`Scalar`].
+    ///
+    /// [`Scalar`]: ValueVisualisation::Scalar
+    #[must_use]
+    pub fn is_scalar(&self) -> bool {
+        matches!(self, Self::Scalar)
+    }
+
+    /// Returns `true` if the value visualisation is [`Index`].
+    ///
+    /// [`Index`]: ValueVisualisation::Index
+    #[must_use]
+    pub fn is_index(&self) -> bool {
+        matches!(self, Self::Index)
+    }
+
+    /// Returns `true` if the value visualisation is [`Vector`].
+    ///
+    /// [`Vector`]: ValueVisualisation::Vector
+    #[must_use]
+    pub fn is_vector(&self) -> bool {
+        matches!(self, Self::Vector { .. })
+    }
+}
+
+fn draw_rectangle(canvas: &mut Canvas, location: Vector2<f32>, size: Vector2<f32>, color: Color) {
+    canvas.draw(
+        &Quad,
+        DrawParam::new()
+            .dest_rect(Rect::new(location.x, location.y, size.x, size.y))
+            .color(color),
+    );
+}
+
+fn draw_outline(canvas: &mut Canvas, location: Vector2<f32>, size: Vector2<f32>, color: Color) {
+    const THICKNESS: f32 = 1.0;
+
+    for (location, size) in [
+        (location, vector![size.x, THICKNESS]),
+        (
+            location + vector![0.0, size.y - THICKNESS],
+            vector![size.x, THICKNESS],
+        ),
+        (location, vector![THICKNESS, size.y]),
+        (
+            location + vector![size.x - THICKNESS, 0.0],
+            vector![THICKNESS, size.y],
+        ),
+    ] {
+        draw_rectangle(canvas, location, size, color);
+    }
+}
+
+fn draw_centered_text(
+    canvas: &mut Canvas,
+    text: &str,
+    location: Vector2<f32>,
+    width: f32,
+    color: Color,
+) {
+    let mut text = Text::new(
+        TextFragment::new(text)
+            .font(FONT)
+            .scale(RegisterVisualisationLayout::TEXT_HEIGHT)
+            .color(color),
+    );
+    text.set_layout(TextLayout::center());
+
+    let center = location + vector![width, RegisterVisualisationLayout::TEXT_HEIGHT] / 2.0;
+
+    canvas.draw(&text, DrawParam::new().dest([center.x, center.y]));
+}
+
+pub fn draw_arrow(
+    ctx: &Context,
+    canvas: &mut Canvas,
+    start: Point2<f32>,
+    end: Point2<f32>,
+    thickness: f32,
+    tip_size: f32,
+    color: Color,
+) {
+    let Some(direction) = (end - start).try_normalize(0.0) else {
+        return;
+    };
+
+    // The tip is made of two lines at 45 degrees back from the direction of the arrow
+    let rotate = |x: f32, y: f32| {
+        vector![
+            direction.x * x - direction.y * y,
+            direction.x * y + direction.y * x,
+        ]
+    };
+
+    let mut builder = MeshBuilder::new();
+
+    let _ = builder.line(&[start, end], thickness, color);
+
+    for tip_direction in [
+        rotate(-FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+        rotate(-FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+    ] {
+        let tip_start = end - direction * thickness / 2.0;
+        let tip_end = tip_start + tip_direction * tip_size;
+
+        let _ = builder.line(&[tip_start, tip_end], thickness, color);
+    }
+
+    canvas.draw(&Mesh::from_data(ctx, builder.build()), DrawParam::new());
+}
@@ -1,8 +1,10 @@
+use std::{cell::RefCell, rc::Rc};
+
 use ggez::{
     Context, ContextBuilder, GameResult,
     conf::{Backend, Conf, FullscreenType, WindowMode, WindowSetup},
     event::{self, EventHandler},
-    graphics::{Canvas, Color, Sampler},
+    graphics::{Canvas, Color, FontData, Sampler},
     input::keyboard::KeyInput,
     winit::{
         event::{KeyEvent, MouseButton},
@@ -12,13 +14,25 @@ use ggez::{
 };
 use nalgebra::{Point2, Vector2, point, vector};
 
-use crate::ui2::{DRAW_FPS, START_IN_FULLSCREEN, utils};
+use crate::{
+    DEFAULT_MACHINE_WITH_H, KOLAKOSKI_SEQUENCE,
+    simulation::computer::Computer,
+    ui2::{
+        DRAW_FPS, FONT, START_IN_FULLSCREEN,
+        register_visualisation::RegisterPanel,
+        text_editor::{TextEditor, operations::TextEditorOperations},
+        utils,
+        window::{Window, WindowSet},
+        workspace::Workspace,
+    },
+};
 
 pub struct State {
     fullscreen: bool,
     was_maximized: bool,
 
     input: GlobalInput,
+    windows: WindowSet,
 }
 
 impl Default for State {
@@ -28,6 +42,7 @@ impl Default for State {
             was_maximized: START_IN_FULLSCREEN,
 
             input: GlobalInput::default(),
+            windows: WindowSet::default(),
         }
     }
 }
@@ -35,8 +50,11 @@ impl Default for State {
 #[derive(Debug)]
 pub struct GlobalInput {
     pub keys_down: Vec<(Key, KeyLocation)>,
+    /// Includes key repeats
     pub keys_pressed: Vec<(Key, KeyLocation)>,
 
+    /// Typed text, with backspace, delete and enter given as `'\u{8}'`, `'\u{7f}'` and `'\n'`.
+    /// Letters pressed while holding control are included without the modifier.
     pub text_input: String,
 
     pub mouse_in_window: bool,
@@ -65,11 +83,39 @@ impl Default for GlobalInput {
     }
 }
 
+impl GlobalInput {
+    #[must_use]
+    pub fn is_key_down(&self, key: NamedKey) -> bool {
+        self.keys_down
+            .iter()
+            .any(|(down, _)| *down == Key::Named(key))
+    }
+
+    #[must_use]
+    pub fn is_key_pressed(&self, key: NamedKey) -> bool {
+        self.keys_pressed
+            .iter()
+            .any(|(pressed, _)| *pressed == Key::Named(key))
+    }
+
+    #[must_use]
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons_down.contains(&button)
+    }
+
+    #[must_use]
+    pub fn is_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons_pressed.contains(&button)
+    }
+}
+
 impl State {
+    const WORKSPACE_WIDTH: f32 = 600.0;
+
     pub fn run(self) -> GameResult {
-        let state = State::default();
+        let mut state = self;
 
-        let (ctx, event_loop) = ContextBuilder::new("ggez_test", "Mycellf")
+        let (mut ctx, event_loop) = ContextBuilder::new("ggez_test", "Mycellf")
             .default_conf(Conf {
                 window_mode: WindowMode {
                     fullscreen_type: if state.fullscreen {
@@ -91,13 +137,46 @@ impl State {
             .build()
             .unwrap();
 
+        ctx.gfx.add_font(
+            FONT,
+            FontData::from_path(&ctx, "/CommitMono-400-Regular.otf")?,
+        );
+
+        let computer = Rc::new(RefCell::new(
+            Computer::load_from(DEFAULT_MACHINE_WITH_H).unwrap(),
+        ));
+
+        let workspace = Workspace::new(
+            TextEditor::new(
+                TextEditorOperations::new(KOLAKOSKI_SEQUENCE.to_owned()),
+                30.0,
+                5.0,
+            ),
+            computer.clone(),
+        );
+        let height = workspace.height();
+
+        let registers = RegisterPanel::new(computer, 4);
+        let registers_size = registers.size();
+
+        let mut window = Window::new(
+            &ctx,
+            point![50.0, 50.0],
+            registers_size.x.max(Self::WORKSPACE_WIDTH),
+        );
+
+        window.push_element(workspace, height);
+        window.push_element(registers, registers_size.y);
+
+        state.windows.add_window(window);
+
         event::run(ctx, event_loop, state)
     }
 }
 
 impl EventHandler for State {
-    fn update(&mut self, _ctx: &mut Context) -> GameResult {
-        println!("{:?}", self.input);
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.windows.update(ctx, &self.input);
 
         self.input.text_input.clear();
 
@@ -112,6 +191,8 @@ impl EventHandler for State {
         let mut canvas = Canvas::from_frame(ctx, Some(Color::BLACK));
         canvas.set_sampler(Sampler::nearest_clamp());
 
+        self.windows.draw(ctx, &mut canvas);
+
         if *DRAW_FPS {
             utils::draw_fps(ctx, &mut canvas);
         }
@@ -153,6 +234,24 @@ impl EventHandler for State {
             _ => (),
         }
 
+        match input.event.logical_key {
+            Key::Named(NamedKey::Backspace) => self.input.text_input.push('\u{8}'),
+            Key::Named(NamedKey::Delete) => self.input.text_input.push('\u{7f}'),
+            Key::Named(NamedKey::Enter) => self.input.text_input.push('\n'),
+            _ if input.mods.control_key() => {
+                if let Key::Character(text) = input.event.key_without_modifiers() {
+                    self.input.text_input.push_str(&text);
+                }
+            }
+            _ => {
+                if let Some(text) = &input.event.text {
+                    self.input
+                        .text_input
+                        .extend(text.chars().filter(|character| !character.is_control()));
+                }
+            }
+        }
+
         let key = (input.event.key_without_modifiers(), input.event.location);
 
         if !self.input.keys_down.contains(&key) {
@@ -230,10 +329,4 @@ impl EventHandler for State {
 
         Ok(())
     }
-
-    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
-        self.input.text_input.push(character);
-
-        Ok(())
-    }
 }
@@ -0,0 +1,661 @@
+use ggez::{
+    Context,
+    graphics::{Canvas, Color, DrawParam, Quad, Rect, Text, TextFragment},
+    winit::{event::MouseButton, keyboard::NamedKey},
+};
+use nalgebra::{Vector2, vector};
+
+use crate::ui2::{
+    FONT, FONT_ASPECT, utils,
+    window::{ElementDrawInfo, ElementUpdateEvent, ElementUpdateInfo, WindowElement},
+};
+
+use operations::{CharacterPosition, Cursor, CursorLocation, TextEditorOperations};
+
+pub mod operations;
+
+#[derive(Debug)]
+pub struct TextEditor {
+    pub editor: TextEditorOperations,
+    pub border_size: f32,
+    pub lines: f32,
+
+    pub scroll: f32,
+    pub target_scroll: f32,
+    pub scroll_speed: f32,
+
+    pub last_scroll_input: f32,
+
+    pub cursors_fit_in_page: bool,
+    /// Whether the mouse is held down to select text, which continues outside of the editor
+    pub selecting: bool,
+    /// Measured from the font once the editor is first drawn
+    pub character_width: f32,
+
+    /// A line to mark, such as the one being run
+    pub highlighted_line: Option<usize>,
+    /// Messages shown after the end of lines
+    pub annotations: Vec<LineAnnotation>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LineAnnotation {
+    pub line: usize,
+    pub message: String,
+    pub color: Color,
+}
+
+impl WindowElement for TextEditor {
+    fn update(&mut self, ctx: &mut Context, info: ElementUpdateInfo) -> Vec<ElementUpdateEvent> {
+        let mut events = Vec::new();
+
+        self.lines = (info.size.y - self.border_size * 2.0) / Self::TEXT_HEIGHT;
+
+        // Scrolling
+        let frame_time = ctx.time.delta().as_secs_f32();
+
+        if info.mouse_focus {
+            let mut scroll_input = info.input.mouse_scroll.y.clamp(-1.0, 1.0);
+
+            if self.last_scroll_input.abs() > 0.99 && scroll_input.abs() > 0.99 {
+                scroll_input *= (frame_time * 240.0)
+                    .clamp(1.0, self.last_scroll_input.abs() + frame_time * 5.0);
+            }
+
+            self.last_scroll_input = scroll_input;
+
+            if scroll_input != 0.0 {
+                self.target_scroll -= scroll_input;
+
+                if scroll_input >= 1.0 {
+                    self.target_scroll = self.target_scroll.ceil();
+                } else if scroll_input <= -1.0 {
+                    self.target_scroll = self.target_scroll.floor();
+                }
+                self.target_scroll = self.target_scroll.clamp(0.0, self.maximum_scroll());
+                self.scroll_speed = Self::SCROLL_SPEED;
+            }
+        }
+
+        if self.target_scroll != self.scroll {
+            self.scroll = utils::exp_decay_cutoff(
+                self.scroll,
+                self.target_scroll,
+                self.scroll_speed,
+                frame_time,
+                0.01,
+            )
+            .0;
+        }
+
+        // Editor controls
+        if info.mouse_focus && info.input.is_mouse_button_pressed(MouseButton::Left) {
+            events.push(ElementUpdateEvent::TakeKeyboardFocus);
+        }
+
+        if info.mouse_focus || self.selecting {
+            self.move_cursors_with_mouse(&info);
+        }
+
+        if info.keyboard_focus {
+            let (mut moved, mut page_scrolled) = self
+                .editor
+                .move_cursors_with_keybinds(&info.input, self.lines.floor() as usize);
+
+            if moved {
+                self.editor.history.finish_edit_group();
+            }
+
+            let (moved_by_typing, page_scrolled_by_typing, typed, seperate_edits_in_history) =
+                self.editor.type_from_input_characters(&info.input);
+
+            moved |= moved_by_typing;
+
+            page_scrolled |= page_scrolled_by_typing;
+
+            if seperate_edits_in_history {
+                self.editor.history.finish_edit_group();
+            }
+
+            self.editor.history.insert_buffered_edits();
+
+            if seperate_edits_in_history {
+                self.editor.history.finish_edit_group();
+            }
+
+            if moved || typed {
+                self.editor.deduplicate_cursors();
+            }
+
+            if moved {
+                self.scroll_to_cursors(
+                    page_scrolled,
+                    self.editor
+                        .cursors
+                        .clone()
+                        .into_iter()
+                        .map(|cursor| cursor.position.line),
+                );
+            }
+        }
+
+        events
+    }
+
+    fn draw(&mut self, ctx: &mut Context, info: ElementDrawInfo) {
+        if self.character_width == 0.0 {
+            self.character_width = Self::measure_character_width(ctx);
+        }
+
+        let ElementDrawInfo {
+            canvas,
+            offset,
+            size,
+            keyboard_focus,
+        } = info;
+
+        let page = self.page_rect(offset, size);
+
+        canvas.draw(
+            &Quad,
+            DrawParam::new()
+                .dest_rect(Rect::new(offset.x, offset.y, size.x, size.y))
+                .color(Self::BORDER_COLOR),
+        );
+
+        canvas.draw(
+            &Quad,
+            DrawParam::new()
+                .dest_rect(page)
+                .color(Self::BACKGROUND_COLOR),
+        );
+
+        if canvas.set_scissor_rect(page).is_err() {
+            return;
+        }
+
+        let origin = offset + self.text_offset();
+
+        self.draw_line_backgrounds(canvas, origin, page);
+        self.draw_selections(canvas, origin);
+        self.draw_text(canvas, origin);
+        self.draw_annotations(canvas, origin);
+        self.draw_cursors(canvas, origin, keyboard_focus);
+
+        canvas.set_default_scissor_rect();
+    }
+}
+
+impl TextEditor {
+    pub const TEXT_OFFSET: Vector2<f32> = vector![5.0, 0.0];
+    pub const TEXT_HEIGHT: f32 = 15.0;
+
+    pub const CURSOR_COLOR_FOCUSED: Color = Color::WHITE;
+    pub const CURSOR_COLOR_UNFOCUSED: Color = Color::new(0.5, 0.5, 0.5, 1.0);
+
+    pub const BACKGROUND_COLOR: Color = Color::new(0.05, 0.05, 0.05, 1.0);
+    pub const BORDER_COLOR: Color = Color::new(0.15, 0.15, 0.15, 1.0);
+
+    pub const SCROLL_SPEED: f32 = 25.0;
+    pub const FOLLOW_SPEED: f32 = f32::INFINITY;
+    pub const PAGE_FOLLOW_SPEED: f32 = 10.0;
+
+    pub const SELECTION_COLOR: Color = Color::new(0.376, 0.627, 1.0, 2.0 / 7.0);
+    pub const HIGHLIGHTED_LINE_COLOR: Color = Color::new(1.0, 0.94, 0.0, 0.15);
+
+    /// The opacity of the background behind lines with annotations
+    pub const ANNOTATION_BACKGROUND_ALPHA: f32 = 0.15;
+
+    #[must_use]
+    pub fn new(editor: TextEditorOperations, lines: f32, border_size: f32) -> Self {
+        Self {
+            editor,
+            border_size,
+            lines,
+
+            scroll: 0.0,
+            target_scroll: 0.0,
+            scroll_speed: 0.0,
+
+            last_scroll_input: 0.0,
+
+            cursors_fit_in_page: true,
+            selecting: false,
+            character_width: 0.0,
+
+            highlighted_line: None,
+            annotations: Vec::new(),
+        }
+    }
+
+    /// The height needed to show every line, including the border.
+    #[must_use]
+    pub fn height(&self) -> f32 {
+        self.lines * Self::TEXT_HEIGHT + self.border_size * 2.0
+    }
+
+    #[must_use]
+    pub fn text_offset(&self) -> Vector2<f32> {
+        Self::TEXT_OFFSET.add_scalar(self.border_size)
+    }
+
+    #[must_use]
+    pub fn page_rect(&self, offset: Vector2<f32>, size: Vector2<f32>) -> Rect {
+        Rect::new(
+            offset.x + self.border_size,
+            offset.y + self.border_size,
+            size.x - self.border_size * 2.0,
+            size.y - self.border_size * 2.0,
+        )
+    }
+
+    /// In lines
+    #[must_use]
+    pub fn maximum_scroll(&self) -> f32 {
+        (self.editor.num_lines() - 1) as f32
+    }
+
+    #[must_use]
+    pub fn character_width(&self) -> f32 {
+        if self.character_width > 0.0 {
+            self.character_width
+        } else {
+            Self::TEXT_HEIGHT * FONT_ASPECT
+        }
+    }
+
+    fn measure_character_width(ctx: &Context) -> f32 {
+        const CHARACTERS: usize = 16;
+
+        let text = Text::new(
+            TextFragment::new("0".repeat(CHARACTERS))
+                .font(FONT)
+                .scale(Self::TEXT_HEIGHT),
+        );
+
+        text.measure(ctx)
+            .map_or(Self::TEXT_HEIGHT * FONT_ASPECT, |size| {
+                size.x / CHARACTERS as f32
+            })
+    }
+
+    /// The range of lines which are at least partly visible.
+    #[must_use]
+    pub fn visible_lines(&self) -> (usize, usize) {
+        let start_line = self.scroll.floor() as usize;
+        let end_line = (self.scroll + self.lines).ceil() as usize;
+
+        (start_line, end_line)
+    }
+
+    pub fn draw_text(&self, canvas: &mut Canvas, origin: Vector2<f32>) {
+        let (start_line, end_line) = self.visible_lines();
+
+        for line in start_line..end_line {
+            let Some(segments) = self.editor.color_segments_of_line(line) else {
+                break;
+            };
+
+            let mut text = Text::default();
+
+            for (range, color_choice) in segments {
+                text.add(
+                    TextFragment::new(&self.editor.text[range])
+                        .font(FONT)
+                        .scale(Self::TEXT_HEIGHT)
+                        .color(Color::from(color_choice)),
+                );
+            }
+
+            canvas.draw(
+                &text,
+                DrawParam::new().dest([origin.x, origin.y + self.vertical_offset_of_line(line)]),
+            );
+        }
+    }
+
+    /// Marks the highlighted line and lines with annotations across the whole page.
+    pub fn draw_line_backgrounds(&self, canvas: &mut Canvas, origin: Vector2<f32>, page: Rect) {
+        let (start_line, end_line) = self.visible_lines();
+
+        let annotated_lines = self.annotations.iter().map(|annotation| {
+            let mut color = annotation.color;
+            color.a = Self::ANNOTATION_BACKGROUND_ALPHA;
+
+            (annotation.line, color)
+        });
+
+        let lines = annotated_lines.chain(
+            self.highlighted_line
+                .map(|line| (line, Self::HIGHLIGHTED_LINE_COLOR)),
+        );
+
+        for (line, color) in lines {
+            if line < start_line || line >= end_line {
+                continue;
+            }
+
+            self.draw_rectangle(
+                canvas,
+                vector![page.x, origin.y + self.vertical_offset_of_line(line)],
+                vector![page.w, Self::TEXT_HEIGHT],
+                color,
+            );
+        }
+    }
+
+    /// Draws the message of each annotation a short distance after the end of its line.
+    pub fn draw_annotations(&self, canvas: &mut Canvas, origin: Vector2<f32>) {
+        let (start_line, end_line) = self.visible_lines();
+
+        let mut offsets = Vec::<(usize, f32)>::new();
+
+        for annotation in &self.annotations {
+            if annotation.line < start_line || annotation.line >= end_line {
+                continue;
+            }
+
+            // Annotations on the same line are placed one after the other
+            let offset = match offsets
+                .iter_mut()
+                .find(|(line, _)| *line == annotation.line)
+            {
+                Some((_, offset)) => offset,
+                None => {
+                    let start = self.width_of_line(annotation.line) + self.character_width() * 2.0;
+
+                    offsets.push((annotation.line, start));
+                    &mut offsets.last_mut().unwrap().1
+                }
+            };
+
+            let text = Text::new(
+                TextFragment::new(&annotation.message)
+                    .font(FONT)
+                    .scale(Self::TEXT_HEIGHT)
+                    .color(annotation.color),
+            );
+
+            canvas.draw(
+                &text,
+                DrawParam::new().dest([
+                    origin.x + *offset,
+                    origin.y + self.vertical_offset_of_line(annotation.line),
+                ]),
+            );
+
+            *offset += (annotation.message.chars().count() + 2) as f32 * self.character_width();
+        }
+    }
+
+    pub fn draw_cursors(&self, canvas: &mut Canvas, origin: Vector2<f32>, keyboard_focus: bool) {
+        let (start_line, end_line) = self.visible_lines();
+
+        for cursor in &self.editor.cursors {
+            if cursor.position.line < start_line || cursor.position.line >= end_line {
+                continue;
+            }
+
+            self.draw_rectangle(
+                canvas,
+                origin
+                    + vector![
+                        self.horizontal_offset_of(cursor.start),
+                        self.vertical_offset_of_line(cursor.position.line),
+                    ],
+                vector![1.0, Self::TEXT_HEIGHT],
+                if keyboard_focus {
+                    Self::CURSOR_COLOR_FOCUSED
+                } else {
+                    Self::CURSOR_COLOR_UNFOCUSED
+                },
+            );
+        }
+    }
+
+    pub fn draw_selections(&self, canvas: &mut Canvas, origin: Vector2<f32>) {
+        let (start_line, end_line) = self.visible_lines();
+
+        for cursor in &self.editor.cursors {
+            if cursor.end.is_none() {
+                continue;
+            }
+
+            let range = cursor.range();
+            let (start, end) = (range.start, range.end);
+
+            if end.position.line < start_line || start.position.line >= end_line {
+                continue;
+            }
+
+            let start_offset = self.horizontal_offset_of(start);
+            let end_offset = self.horizontal_offset_of(end);
+
+            if start.position.line == end.position.line {
+                self.draw_rectangle(
+                    canvas,
+                    origin
+                        + vector![
+                            start_offset,
+                            self.vertical_offset_of_line(start.position.line)
+                        ],
+                    vector![end_offset - start_offset, Self::TEXT_HEIGHT],
+                    Self::SELECTION_COLOR,
+                );
+
+                continue;
+            }
+
+            // The newline at the end of each selected line is shown as one extra character
+            self.draw_rectangle(
+                canvas,
+                origin
+                    + vector![
+                        start_offset,
+                        self.vertical_offset_of_line(start.position.line)
+                    ],
+                vector![
+                    self.width_of_line(start.position.line) - start_offset + self.character_width(),
+                    Self::TEXT_HEIGHT,
+                ],
+                Self::SELECTION_COLOR,
+            );
+
+            let middle_lines =
+                (start.position.line + 1).max(start_line)..end.position.line.min(end_line);
+
+            for line in middle_lines {
+                self.draw_rectangle(
+                    canvas,
+                    origin + vector![0.0, self.vertical_offset_of_line(line)],
+                    vector![
+                        self.width_of_line(line) + self.character_width(),
+                        Self::TEXT_HEIGHT,
+                    ],
+                    Self::SELECTION_COLOR,
+                );
+            }
+
+            self.draw_rectangle(
+                canvas,
+                origin + vector![0.0, self.vertical_offset_of_line(end.position.line)],
+                vector![end_offset, Self::TEXT_HEIGHT],
+                Self::SELECTION_COLOR,
+            );
+        }
+    }
+
+    fn draw_rectangle(
+        &self,
+        canvas: &mut Canvas,
+        position: Vector2<f32>,
+        size: Vector2<f32>,
+        color: Color,
+    ) {
+        canvas.draw(
+            &Quad,
+            DrawParam::new()
+                .dest_rect(Rect::new(position.x, position.y, size.x, size.y))
+                .color(color),
+        );
+    }
+
+    #[must_use]
+    pub fn vertical_offset_of_line(&self, line: usize) -> f32 {
+        (line as f32 - self.scroll) * Self::TEXT_HEIGHT
+    }
+
+    /// The offset of a location from the start of its line, which may be less than its column
+    /// if the column is past the end of the line.
+    #[must_use]
+    pub fn horizontal_offset_of(&self, location: CursorLocation) -> f32 {
+        let line_start = self.editor.lines[location.position.line].byte_offset;
+        let columns = self.editor.text[line_start..location.index].chars().count();
+
+        columns as f32 * self.character_width()
+    }
+
+    #[must_use]
+    pub fn width_of_line(&self, line: usize) -> f32 {
+        self.editor.length_of_line(line).unwrap_or(0) as f32 * self.character_width()
+    }
+
+    pub fn scroll_to_cursors(
+        &mut self,
+        page_scrolled: bool,
+        lines: impl Iterator<Item = usize> + Clone,
+    ) {
+        if lines.clone().peekable().peek().is_none() {
+            return;
+        }
+
+        let min_line = lines.clone().min().unwrap() as f32;
+
+        let max_line = lines.max().unwrap() as f32;
+
+        let height_offset = self.lines - 1.0;
+
+        let cursors_fit_in_window = min_line >= max_line - height_offset;
+
+        let (min_scroll, max_scroll) = if cursors_fit_in_window {
+            (max_line - height_offset, min_line)
+        } else {
+            (min_line - height_offset, max_line)
+        };
+
+        let mut follow_scroll = false;
+
+        if self.target_scroll > max_scroll {
+            self.target_scroll = max_scroll;
+            follow_scroll = true;
+        } else if self.target_scroll < min_scroll {
+            self.target_scroll = min_scroll;
+            follow_scroll = true;
+        }
+
+        if follow_scroll {
+            self.scroll_speed =
+                if page_scrolled || cursors_fit_in_window && !self.cursors_fit_in_page {
+                    Self::PAGE_FOLLOW_SPEED
+                } else {
+                    Self::FOLLOW_SPEED
+                };
+        }
+
+        self.cursors_fit_in_page = cursors_fit_in_window;
+    }
+
+    /// Finds the character nearest to a point relative to the element. Points outside of the
+    /// element give `None` unless `clamp` is set.
+    #[must_use]
+    pub fn position_of_point_in_text(
+        &self,
+        point: Vector2<f32>,
+        clamp: bool,
+        size: Vector2<f32>,
+    ) -> Option<CharacterPosition> {
+        let inside = point.x >= 0.0 && point.y >= 0.0 && point.x < size.x && point.y < size.y;
+
+        if !(clamp || inside) {
+            return None;
+        }
+
+        let point = point.zip_map(&size, |point, size| point.clamp(0.0, size));
+
+        let text_space = point - self.text_offset() + vector![0.0, self.scroll * Self::TEXT_HEIGHT];
+
+        let column = (text_space.x / self.character_width()).round().max(0.0) as usize;
+        let line = (text_space.y / Self::TEXT_HEIGHT).max(0.0) as usize;
+
+        let final_line = self.editor.num_lines() - 1;
+
+        Some(if line > final_line {
+            CharacterPosition {
+                line: final_line,
+                column: self.editor.length_of_line(final_line).unwrap(),
+            }
+        } else {
+            CharacterPosition { line, column }
+        })
+    }
+
+    pub fn move_cursors_with_mouse(&mut self, info: &ElementUpdateInfo) {
+        let input = &info.input;
+
+        if !(self.selecting || input.is_mouse_button_pressed(MouseButton::Left)) {
+            return;
+        }
+
+        if self.selecting && !input.is_mouse_button_down(MouseButton::Left) {
+            self.selecting = false;
+        } else if let Some(position) =
+            self.position_of_point_in_text(input.mouse_position, self.selecting, info.size)
+        {
+            let clicked_location = CursorLocation {
+                position,
+                index: self.editor.index_of_position(position).unwrap(),
+            };
+
+            if input.is_key_down(NamedKey::Shift) || self.selecting {
+                let cursor = self.editor.cursors.last_mut().unwrap();
+                if cursor.end.is_none() {
+                    cursor.end = Some(cursor.start);
+                }
+
+                cursor.start = clicked_location;
+
+                if cursor.end == Some(cursor.start) {
+                    cursor.end = None;
+                }
+
+                self.selecting = true;
+            } else {
+                let cursor = Cursor {
+                    start: clicked_location,
+                    ..Default::default()
+                };
+
+                if input.is_key_down(NamedKey::Alt) {
+                    if let Some(index) = self
+                        .editor
+                        .cursors
+                        .iter()
+                        .position(|&other_cursor| other_cursor == cursor)
+                    {
+                        if self.editor.cursors.len() > 1 {
+                            self.editor.cursors.remove(index);
+                        }
+                    } else {
+                        self.editor.cursors.push(cursor);
+                        self.selecting = true;
+                    }
+                } else {
+                    self.editor.cursors = vec![cursor];
+                    self.selecting = true;
+                }
+            }
+        }
+
+        self.editor.history.finish_edit_group();
+    }
+}
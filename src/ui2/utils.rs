@@ -35,3 +35,28 @@ pub fn draw_fps(ctx: &Context, canvas: &mut Canvas) {
         },
     );
 }
+
+#[must_use]
+pub fn exp_decay_cutoff(a: f32, b: f32, decay: f32, dt: f32, cutoff: f32) -> (f32, bool) {
+    if (a - b).abs() < cutoff {
+        (b, true)
+    } else {
+        (exp_decay(a, b, decay, dt), false)
+    }
+}
+
+/// CREDIT: Freya Holmér: <https://www.youtube.com/watch?v=LSNQuFEDOyQ>
+#[must_use]
+pub fn exp_decay(a: f32, b: f32, decay: f32, dt: f32) -> f32 {
+    b + (a - b) * (-decay * dt).exp()
+}
+
+#[must_use]
+pub fn color_lerp(a: Color, b: Color, t: f32) -> Color {
+    Color::new(
+        a.r + (b.r - a.r) * t,
+        a.g + (b.g - a.g) * t,
+        a.b + (b.b - a.b) * t,
+        a.a + (b.a - a.a) * t,
+    )
+}
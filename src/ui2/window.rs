@@ -2,9 +2,10 @@ use std::{fmt::Debug, ops::Deref};
 
 use ggez::{
     Context,
-    graphics::{Canvas, Color, Image},
+    graphics::{Canvas, Color, DrawParam, Image},
+    winit::event::MouseButton,
 };
-use nalgebra::{Point2, Vector2};
+use nalgebra::{Point2, Vector2, vector};
 use slotmap::{SlotMap, new_key_type};
 
 use crate::ui2::state::GlobalInput;
@@ -21,6 +22,14 @@ pub struct WindowSet {
 }
 
 impl WindowSet {
+    /// Adds a window in front of the others.
+    pub fn add_window(&mut self, window: Window) -> WindowKey {
+        let key = self.windows.insert(window);
+        self.ordering.insert(0, key);
+
+        key
+    }
+
     pub fn update(&mut self, ctx: &mut Context, input: &GlobalInput) {
         let mut mouse_focus = true;
         let mut new_front_window = None;
@@ -30,6 +39,7 @@ impl WindowSet {
             let info = WindowUpdateInfo {
                 input,
                 mouse_focus: mouse_focus && window.contains_point(input.mouse_position),
+                keyboard_focus: i == 0,
             };
 
             if info.mouse_focus {
@@ -53,37 +63,148 @@ impl WindowSet {
         }
     }
 
-    pub fn draw(&mut self, ctx: &mut Context) {
-        let mut canvas = Canvas::from_frame(ctx, Some(Color::BLACK));
-
+    pub fn draw(&mut self, ctx: &mut Context, canvas: &mut Canvas) {
         for &key in self.ordering.iter().rev() {
             let window = &mut self.windows[key];
-            let info = WindowDrawInfo {
-                canvas: &mut canvas,
-            };
+            let info = WindowDrawInfo { canvas };
 
             window.draw(ctx, info);
         }
-
-        canvas.finish(ctx).unwrap();
     }
 }
 
 #[derive(Debug)]
 pub struct Window {
     pub elements: SlotMap<ElementKey, WindowElementEntry>,
+    /// Elements are stacked from top to bottom in this order
     pub ordering: Vec<ElementKey>,
+    pub keyboard_focus: Option<ElementKey>,
     pub position: Point2<f32>,
+    /// The offset of the mouse from the window's position while it's being dragged
+    pub grab_position: Option<Vector2<f32>>,
     pub size: Vector2<f32>,
     pub render_target: Image,
 }
 
 impl Window {
+    pub const BACKGROUND_COLOR: Color = Color::new(0.15, 0.15, 0.15, 1.0);
+
+    #[must_use]
+    pub fn new(ctx: &Context, position: Point2<f32>, width: f32) -> Self {
+        Self {
+            elements: SlotMap::default(),
+            ordering: Vec::new(),
+            keyboard_focus: None,
+            position,
+            grab_position: None,
+            size: vector![width, 0.0],
+            render_target: Image::new_canvas_image(ctx, 1, 1, 1),
+        }
+    }
+
+    /// Adds an element below the others, growing the window to fit it.
+    pub fn push_element(
+        &mut self,
+        element: impl WindowElement + 'static,
+        height: f32,
+    ) -> ElementKey {
+        let key = self.elements.insert(WindowElementEntry {
+            element: Box::new(element),
+            height,
+        });
+
+        self.ordering.push(key);
+        self.size.y += height;
+
+        key
+    }
+
     pub fn update(&mut self, ctx: &mut Context, info: WindowUpdateInfo) -> Vec<WindowUpdateEvent> {
-        Vec::new()
+        let mut events = Vec::new();
+
+        if self.grab_position.is_some() && !info.input.is_mouse_button_down(MouseButton::Left) {
+            self.grab_position = None;
+        }
+
+        if let Some(grab_position) = self.grab_position {
+            self.position = info.input.mouse_position - grab_position;
+        }
+
+        if info.mouse_focus && !info.input.mouse_buttons_pressed.is_empty() {
+            events.push(WindowUpdateEvent::MoveToFront);
+        }
+
+        let mouse_position = info.input.mouse_position - self.position;
+        let mut offset = 0.0;
+
+        for &key in &self.ordering {
+            let entry = &mut self.elements[key];
+
+            let element_mouse_position = mouse_position - vector![0.0, offset];
+            let size = vector![self.size.x, entry.height];
+
+            let element_info = ElementUpdateInfo {
+                input: ElementInput {
+                    mouse_position: element_mouse_position,
+                    global_input: info.input,
+                },
+                keyboard_focus: info.keyboard_focus && self.keyboard_focus == Some(key),
+                mouse_focus: info.mouse_focus
+                    && (0.0..entry.height).contains(&element_mouse_position.y),
+                size,
+            };
+
+            for event in entry.element.update(ctx, element_info) {
+                match event {
+                    ElementUpdateEvent::WindowDrag { grab } => {
+                        self.grab_position =
+                            grab.then(|| info.input.mouse_position - self.position);
+                    }
+                    ElementUpdateEvent::TakeKeyboardFocus => self.keyboard_focus = Some(key),
+                    ElementUpdateEvent::SetHeight { new_height } => entry.height = new_height,
+                }
+            }
+
+            offset += entry.height;
+        }
+
+        self.size.y = offset;
+
+        events
     }
 
-    pub fn draw(&mut self, ctx: &mut Context, info: WindowDrawInfo) {}
+    pub fn draw(&mut self, ctx: &mut Context, info: WindowDrawInfo) {
+        let width = (self.size.x.ceil() as u32).max(1);
+        let height = (self.size.y.ceil() as u32).max(1);
+
+        if (self.render_target.width(), self.render_target.height()) != (width, height) {
+            self.render_target = Image::new_canvas_image(ctx, width, height, 1);
+        }
+
+        let mut canvas =
+            Canvas::from_image(ctx, self.render_target.clone(), Self::BACKGROUND_COLOR);
+        let mut offset = 0.0;
+
+        for &key in &self.ordering {
+            let entry = &mut self.elements[key];
+
+            let element_info = ElementDrawInfo {
+                canvas: &mut canvas,
+                offset: vector![0.0, offset],
+                size: vector![self.size.x, entry.height],
+                keyboard_focus: self.keyboard_focus == Some(key),
+            };
+
+            entry.element.draw(ctx, element_info);
+
+            offset += entry.height;
+        }
+
+        canvas.finish(ctx).unwrap();
+
+        info.canvas
+            .draw(&self.render_target, DrawParam::new().dest(self.position));
+    }
 
     pub fn contains_point(&self, point: Point2<f32>) -> bool {
         let offset = point - self.position;
@@ -94,6 +215,7 @@ impl Window {
 #[derive(Debug)]
 pub struct WindowElementEntry {
     pub element: Box<dyn WindowElement>,
+    pub height: f32,
 }
 
 pub trait WindowElement: Debug {
@@ -109,6 +231,8 @@ pub enum WindowUpdateEvent {
 pub struct WindowUpdateInfo<'a> {
     pub input: &'a GlobalInput,
     pub mouse_focus: bool,
+    /// Only the front window can take keyboard input
+    pub keyboard_focus: bool,
 }
 
 pub struct WindowDrawInfo<'a> {
@@ -132,6 +256,7 @@ pub struct ElementDrawInfo<'a> {
     pub canvas: &'a mut Canvas,
     pub offset: Vector2<f32>,
     pub size: Vector2<f32>,
+    pub keyboard_focus: bool,
 }
 
 pub struct ElementInput<'a> {
@@ -0,0 +1,467 @@
+use std::{cell::RefCell, rc::Rc};
+
+use ggez::{
+    Context,
+    graphics::{Canvas, Color, DrawParam, Quad, Rect, Text, TextAlign, TextFragment, TextLayout},
+    winit::event::MouseButton,
+};
+use nalgebra::{Vector2, vector};
+
+use crate::{
+    simulation::{
+        computer::Computer,
+        instruction::InstructionEvaluationInterrupt,
+        program::{Program, ProgramAssemblyError},
+        source::SourceLocation,
+    },
+    ui2::{
+        FONT, FONT_ASPECT,
+        text_editor::{LineAnnotation, TextEditor},
+        window::{
+            ElementDrawInfo, ElementInput, ElementUpdateEvent, ElementUpdateInfo, WindowElement,
+        },
+    },
+};
+
+/// An editor for a program which is assembled as it's written, with controls to run it on a
+/// computer shared with other elements.
+#[derive(Debug)]
+pub struct Workspace {
+    pub editor: TextEditor,
+    pub computer: Rc<RefCell<Computer>>,
+    /// The state of the computer before the program started running
+    pub initial_computer: Computer,
+
+    /// `None` if the program failed to assemble
+    pub program: Option<Program>,
+    /// The text which `program` was assembled from
+    pub assembled_text: String,
+    pub errors: Vec<ProgramAssemblyError>,
+
+    pub running: bool,
+    pub ticks_per_second: f32,
+    /// The fraction of a tick which has built up while running
+    pub tick_progress: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkspaceAction {
+    Run,
+    Pause,
+    StepCycle,
+    StepInstruction,
+    StepTick,
+    Reset,
+}
+
+impl WindowElement for Workspace {
+    fn update(&mut self, ctx: &mut Context, info: ElementUpdateInfo) -> Vec<ElementUpdateEvent> {
+        let mut events = Vec::new();
+
+        let input = &info.input;
+        let in_control_bar = info.mouse_focus && input.mouse_position.y < Self::CONTROL_BAR_HEIGHT;
+
+        if in_control_bar && input.is_mouse_button_pressed(MouseButton::Left) {
+            match self.action_at(input.mouse_position) {
+                Some(action) => self.perform(action),
+                None => events.push(ElementUpdateEvent::WindowDrag { grab: true }),
+            }
+        }
+
+        let editor_info = ElementUpdateInfo {
+            input: ElementInput {
+                mouse_position: input.mouse_position - vector![0.0, Self::CONTROL_BAR_HEIGHT],
+                global_input: input.global_input,
+            },
+            keyboard_focus: info.keyboard_focus,
+            mouse_focus: info.mouse_focus && !in_control_bar,
+            size: info.size - vector![0.0, Self::CONTROL_BAR_HEIGHT],
+        };
+
+        events.extend(self.editor.update(ctx, editor_info));
+
+        if self.editor.editor.text != self.assembled_text {
+            self.assemble();
+        }
+
+        if self.running {
+            self.run_for(ctx.time.delta().as_secs_f32());
+        }
+
+        self.update_editor_highlights();
+
+        events
+    }
+
+    fn draw(&mut self, ctx: &mut Context, info: ElementDrawInfo) {
+        let ElementDrawInfo {
+            canvas,
+            offset,
+            size,
+            keyboard_focus,
+        } = info;
+
+        canvas.draw(
+            &Quad,
+            DrawParam::new()
+                .dest_rect(Rect::new(
+                    offset.x,
+                    offset.y,
+                    size.x,
+                    Self::CONTROL_BAR_HEIGHT,
+                ))
+                .color(Self::CONTROL_BAR_COLOR),
+        );
+
+        let mut status_offset = Self::BUTTON_SPACING;
+
+        for (action, rect) in self.buttons() {
+            let rect = Rect::new(rect.x + offset.x, rect.y + offset.y, rect.w, rect.h);
+
+            let text_color = if self.can_perform(action) {
+                Color::WHITE
+            } else {
+                Self::DISABLED_TEXT_COLOR
+            };
+
+            canvas.draw(
+                &Quad,
+                DrawParam::new().dest_rect(rect).color(Self::BUTTON_COLOR),
+            );
+
+            draw_text(
+                canvas,
+                action.label(),
+                vector![rect.x + rect.w / 2.0, rect.y + rect.h / 2.0],
+                text_color,
+                TextLayout::center(),
+            );
+
+            status_offset = rect.right() - offset.x + Self::BUTTON_SPACING * 2.0;
+        }
+
+        let (status, status_color) = self.status();
+
+        draw_text(
+            canvas,
+            &status,
+            vector![
+                offset.x + status_offset,
+                offset.y + Self::CONTROL_BAR_HEIGHT / 2.0
+            ],
+            status_color,
+            TextLayout {
+                h_align: TextAlign::Begin,
+                v_align: TextAlign::Middle,
+            },
+        );
+
+        self.editor.draw(
+            ctx,
+            ElementDrawInfo {
+                canvas,
+                offset: offset + vector![0.0, Self::CONTROL_BAR_HEIGHT],
+                size: size - vector![0.0, Self::CONTROL_BAR_HEIGHT],
+                keyboard_focus,
+            },
+        );
+    }
+}
+
+impl Workspace {
+    pub const CONTROL_BAR_HEIGHT: f32 = 25.0;
+    pub const BUTTON_SPACING: f32 = 4.0;
+    pub const BUTTON_PADDING: f32 = 6.0;
+    pub const TEXT_HEIGHT: f32 = 15.0;
+
+    pub const CONTROL_BAR_COLOR: Color = Color::new(0.1, 0.1, 0.1, 1.0);
+    pub const BUTTON_COLOR: Color = Color::new(0.25, 0.25, 0.25, 1.0);
+    pub const DISABLED_TEXT_COLOR: Color = Color::new(0.5, 0.5, 0.5, 1.0);
+    pub const STATUS_COLOR: Color = Color::new(0.7, 0.7, 0.7, 1.0);
+    pub const ERROR_COLOR: Color = Color::new(1.0, 0.3, 0.3, 1.0);
+
+    pub const DEFAULT_TICKS_PER_SECOND: f32 = 30.0;
+
+    #[must_use]
+    pub fn new(editor: TextEditor, computer: Rc<RefCell<Computer>>) -> Self {
+        let initial_computer = computer.borrow().clone();
+
+        let mut result = Self {
+            editor,
+            computer,
+            initial_computer,
+
+            program: None,
+            assembled_text: String::new(),
+            errors: Vec::new(),
+
+            running: false,
+            ticks_per_second: Self::DEFAULT_TICKS_PER_SECOND,
+            tick_progress: 0.0,
+        };
+
+        result.assemble();
+        result.update_editor_highlights();
+
+        result
+    }
+
+    /// The height needed to show the controls and every line of the editor.
+    #[must_use]
+    pub fn height(&self) -> f32 {
+        Self::CONTROL_BAR_HEIGHT + self.editor.height()
+    }
+
+    /// Assembles the text in the editor and resets the computer to run it from the start.
+    pub fn assemble(&mut self) {
+        self.assembled_text.clone_from(&self.editor.editor.text);
+
+        match Program::assemble_from(
+            "Workspace".to_owned(),
+            &self.assembled_text,
+            &self.initial_computer,
+        ) {
+            Ok(program) => {
+                self.program = Some(program);
+                self.errors.clear();
+            }
+            Err(errors) => {
+                self.program = None;
+                self.errors = errors;
+            }
+        }
+
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        *self.computer.borrow_mut() = self.initial_computer.clone();
+
+        self.running = false;
+        self.tick_progress = 0.0;
+    }
+
+    #[must_use]
+    pub fn can_perform(&self, action: WorkspaceAction) -> bool {
+        match action {
+            WorkspaceAction::Pause | WorkspaceAction::Reset => true,
+            WorkspaceAction::Run
+            | WorkspaceAction::StepCycle
+            | WorkspaceAction::StepInstruction
+            | WorkspaceAction::StepTick => {
+                self.program.is_some() && self.computer.borrow().interrupt.is_none()
+            }
+        }
+    }
+
+    pub fn perform(&mut self, action: WorkspaceAction) {
+        if !self.can_perform(action) {
+            return;
+        }
+
+        self.running = false;
+
+        match action {
+            WorkspaceAction::Run => {
+                self.running = true;
+                self.tick_progress = 0.0;
+                return;
+            }
+            WorkspaceAction::Pause => return,
+            WorkspaceAction::Reset => {
+                self.reset();
+                return;
+            }
+            _ => (),
+        }
+
+        let Some(program) = &self.program else {
+            return;
+        };
+
+        let mut computer = self.computer.borrow_mut();
+
+        match action {
+            WorkspaceAction::StepCycle => {
+                computer.step_cycle(program);
+            }
+            WorkspaceAction::StepInstruction => {
+                computer.step_instruction(program);
+            }
+            WorkspaceAction::StepTick => computer.step_tick(program),
+            WorkspaceAction::Run | WorkspaceAction::Pause | WorkspaceAction::Reset => (),
+        }
+    }
+
+    /// Runs as many ticks as have built up over `frame_time`, stopping at an interrupt.
+    pub fn run_for(&mut self, frame_time: f32) {
+        let Some(program) = &self.program else {
+            self.running = false;
+            return;
+        };
+
+        let mut computer = self.computer.borrow_mut();
+
+        self.tick_progress += frame_time * self.ticks_per_second;
+
+        while self.tick_progress >= 1.0 {
+            self.tick_progress -= 1.0;
+
+            computer.step_tick(program);
+
+            if computer.interrupt.is_some() {
+                self.running = false;
+                break;
+            }
+        }
+    }
+
+    /// Marks the line being run and the lines which failed to assemble.
+    pub fn update_editor_highlights(&mut self) {
+        let computer = self.computer.borrow();
+
+        // The editor only shows the main file
+        self.editor.highlighted_line = self
+            .program
+            .as_ref()
+            .and_then(|program| program.instructions.get(computer.instruction as usize))
+            .filter(|instruction| instruction.location.is_in_main_file())
+            .map(|instruction| instruction.location.line as usize);
+
+        self.editor.annotations = self
+            .errors
+            .iter()
+            .flat_map(|error| {
+                let mut locations = error
+                    .locations()
+                    .filter(SourceLocation::is_in_main_file)
+                    .collect::<Vec<_>>();
+                locations.dedup();
+
+                locations.into_iter().map(|location| LineAnnotation {
+                    line: location.line as usize,
+                    message: error.kind.to_string(),
+                    color: Self::ERROR_COLOR,
+                })
+            })
+            .collect();
+
+        if let Some(interrupt) = computer.interrupt
+            && let Some(line) = self.editor.highlighted_line
+            && !matches!(interrupt, InstructionEvaluationInterrupt::ProgramComplete)
+        {
+            self.editor.annotations.push(LineAnnotation {
+                line,
+                message: format!("{interrupt:?}"),
+                color: Self::ERROR_COLOR,
+            });
+        }
+    }
+
+    /// A short description of the state of the computer, and the color to show it in.
+    #[must_use]
+    pub fn status(&self) -> (String, Color) {
+        let computer = self.computer.borrow();
+
+        if self.program.is_none() {
+            let plural = if self.errors.len() == 1 { "" } else { "s" };
+
+            return (
+                format!("{} error{plural}", self.errors.len()),
+                Self::ERROR_COLOR,
+            );
+        }
+
+        let counters = format!(
+            "Runtime: {} Energy: {}",
+            computer.runtime, computer.energy_used,
+        );
+
+        match computer.interrupt {
+            Some(InstructionEvaluationInterrupt::ProgramComplete) => {
+                (format!("Complete  {counters}"), Self::STATUS_COLOR)
+            }
+            Some(_) => (format!("Halted  {counters}"), Self::ERROR_COLOR),
+            None => (counters, Self::STATUS_COLOR),
+        }
+    }
+
+    /// The buttons in the control bar, relative to the element.
+    #[must_use]
+    pub fn buttons(&self) -> Vec<(WorkspaceAction, Rect)> {
+        let run_or_pause = if self.running {
+            WorkspaceAction::Pause
+        } else {
+            WorkspaceAction::Run
+        };
+
+        let actions = [
+            run_or_pause,
+            WorkspaceAction::StepCycle,
+            WorkspaceAction::StepInstruction,
+            WorkspaceAction::StepTick,
+            WorkspaceAction::Reset,
+        ];
+
+        let mut x = Self::BUTTON_SPACING;
+
+        actions
+            .into_iter()
+            .map(|action| {
+                let width = action.label().chars().count() as f32 * Self::TEXT_HEIGHT * FONT_ASPECT
+                    + Self::BUTTON_PADDING * 2.0;
+
+                let rect = Rect::new(
+                    x,
+                    Self::BUTTON_SPACING,
+                    width,
+                    Self::CONTROL_BAR_HEIGHT - Self::BUTTON_SPACING * 2.0,
+                );
+
+                x += width + Self::BUTTON_SPACING;
+
+                (action, rect)
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub fn action_at(&self, point: Vector2<f32>) -> Option<WorkspaceAction> {
+        self.buttons()
+            .into_iter()
+            .find(|(_, rect)| rect.contains([point.x, point.y]))
+            .map(|(action, _)| action)
+    }
+}
+
+impl WorkspaceAction {
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            WorkspaceAction::Run => "Run",
+            WorkspaceAction::Pause => "Pause",
+            WorkspaceAction::StepCycle => "Cycle",
+            WorkspaceAction::StepInstruction => "Instruction",
+            WorkspaceAction::StepTick => "Tick",
+            WorkspaceAction::Reset => "Reset",
+        }
+    }
+}
+
+fn draw_text(
+    canvas: &mut Canvas,
+    text: &str,
+    position: Vector2<f32>,
+    color: Color,
+    layout: TextLayout,
+) {
+    let mut text = Text::new(
+        TextFragment::new(text)
+            .font(FONT)
+            .scale(Workspace::TEXT_HEIGHT)
+            .color(color),
+    );
+    text.set_layout(layout);
+
+    canvas.draw(&text, DrawParam::new().dest([position.x, position.y]));
+}